@@ -9,11 +9,21 @@ pub enum UseKind {
     Std,
 }
 #[derive(Debug, Clone)]
+pub enum MethodKind {
+    Normal,
+    Static,
+    /// dispatched through the vtable of the class, so subclasses can override it
+    Override,
+}
+#[derive(Debug, Clone)]
 pub enum Expr {
     Nothing(),
     // Literals
     Float(f64),
     Int(i32),
+    /// a literal of a number type other than i32 and f64, like `10u64`, without its suffix (but
+    /// with its sign)
+    Number(String, Type),
     Bool(bool),
     Str(String),
     Char(String),
@@ -35,13 +45,19 @@ pub enum Expr {
     Greater(Box<Expr>, Box<Expr>, Span),
     And(Box<Expr>, Box<Expr>, Span),
     Or(Box<Expr>, Box<Expr>, Span),
+    Xor(Box<Expr>, Box<Expr>, Span),
+    ShiftLeft(Box<Expr>, Box<Expr>, Span),
+    ShiftRight(Box<Expr>, Box<Expr>, Span),
 
     Nth(Box<Expr>, Box<Expr>, Span),
 
     // Unary Operators
     Not(Box<Expr>, Span),
+    Negate(Box<Expr>, Span),
     Ref(Box<Expr>, Span),
     Deref(Box<Expr>, Span),
+    /// value -> type, an explicit conversion between number types (and chars and bools)
+    Cast(Box<Expr>, Type, Span),
 
     // Statements
     StmtBlockWithScope(Vec<Box<Expr>>, Span),
@@ -49,6 +65,11 @@ pub enum Expr {
     Print(Box<Expr>, Span),
     Discard(Box<Expr>),
     Stmt(Box<Expr>),
+    /// a block used as a value, which is the value of its last statement (if it has no ';')
+    Block(Box<Expr>),
+    /// evaluated by the compiler: a `comp fn` declaration, or an expression that is replaced by
+    /// the literal it evaluates to
+    Comp(Box<Expr>),
 
     // Functions
     DeclareFunction(
@@ -60,29 +81,55 @@ pub enum Expr {
         Span,
     ),
     Function(Box<Expr>, Type, Vec<(String, Type, bool)>, Vec<String>),
+    /// name, parameters, body, span. Calls to it are expanded before anything else sees them
+    DeclareMacro(String, Vec<String>, Box<Expr>, Span),
     /// function, generics, args, span
     CallFunc(String, Vec<Type>, Vec<Box<Expr>>, Span),
     Return(Box<Expr>, Span),
+    /// `err message`, a failed res of the type the function returns
+    Err(Box<Expr>, Span),
+    /// `value$?`, the value of a res, or returning its err from the function
+    Propagate(Box<Expr>, Span),
 
     // Variables
     Variable(String, Span),
     Declare(String, Option<Type>, Option<Box<Expr>>, bool, Span),
     Assign(Box<Expr>, Box<Expr>, Span),
+    /// `target += value`: target, the operation with the target as its left operand, span
+    CompoundAssign(Box<Expr>, Box<Expr>, Span),
     Delete(String),
     This(Span),
 
     // Control Flow
     /// if condition, if block, else block, is an expression (and not a statement)
     If(Box<Expr>, Box<Expr>, Option<Box<Expr>>, bool),
-    While(Box<Expr>, Box<Expr>),
-    /// loopee, looper, block
-    For(String, Box<Expr>, Box<Expr>, Span),
+    /// condition, block, label, span
+    While(Box<Expr>, Box<Expr>, Option<String>, Span),
+    /// loopee, looper, block, label, span
+    For(String, Box<Expr>, Box<Expr>, Option<String>, Span),
+    /// `break`, out of the loop with the label (or the innermost loop)
+    Break(Option<String>, Span),
+    /// `continue`, with the next iteration of the loop with the label (or the innermost loop)
+    Continue(Option<String>, Span),
 
     // Data Structures
-    /// new class, Vec<(member name, type)>, span
-    Class(Type, Vec<(String, Type)>, Span),
+    /// new class (with conceptual generics if it is generic), parent class,
+    /// Vec<(member name, type)>, Vec<(method kind, method declaration)>, span
+    Class(
+        Type,
+        Option<Type>,
+        Vec<(String, Type)>,
+        Vec<(MethodKind, Expr)>,
+        Span,
+    ),
+    /// new enum, Vec<(variant, payload types)>, span
+    Enum(Type, Vec<(String, Vec<Type>)>, Span),
+    /// value, Vec<(variant or "_", names bound to its payload, block)>, is an expression, span
+    Match(Box<Expr>, Vec<(String, Vec<String>, Expr)>, bool, Span),
     /// value, member, span
     Member(Box<Expr>, String, Span),
+    /// value, method, args, span
+    CallMethod(Box<Expr>, String, Vec<Expr>, Span),
 
     // Others
     Custom(fn(&mut RuntimeEnvironment) -> Value),
@@ -94,3 +141,191 @@ pub enum Expr {
         span: Span,
     },
 }
+
+impl Expr {
+    /// Returns the direct sub-expressions of this expression, in source order.
+    pub fn children(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Nothing()
+            | Expr::Float(_)
+            | Expr::Int(_)
+            | Expr::Number(..)
+            | Expr::Bool(_)
+            | Expr::Str(_)
+            | Expr::Char(_)
+            | Expr::Variable(..)
+            | Expr::Delete(_)
+            | Expr::Break(..)
+            | Expr::Continue(..)
+            | Expr::This(_)
+            | Expr::Custom(_)
+            | Expr::Custom2(_)
+            | Expr::Value(_)
+            | Expr::DeclareMacro(..)
+            | Expr::Enum(..)
+            | Expr::Use { .. } => vec![],
+
+            Expr::Vector(exprs) | Expr::Array(exprs) => exprs.iter().collect(),
+
+            Expr::Class(_, _, _, methods, _) => methods.iter().map(|m| &m.1).collect(),
+
+            Expr::CallMethod(value, _, args, _) => {
+                let mut children = vec![value.as_ref()];
+                children.extend(args.iter());
+                children
+            }
+
+            Expr::Add(l, r, _)
+            | Expr::Sub(l, r, _)
+            | Expr::Mult(l, r, _)
+            | Expr::Div(l, r, _)
+            | Expr::Mod(l, r, _)
+            | Expr::Power(l, r, _)
+            | Expr::EqualEqual(l, r, _)
+            | Expr::BangEqual(l, r, _)
+            | Expr::GreaterEqual(l, r, _)
+            | Expr::LessEqual(l, r, _)
+            | Expr::Less(l, r, _)
+            | Expr::Greater(l, r, _)
+            | Expr::And(l, r, _)
+            | Expr::Or(l, r, _)
+            | Expr::Xor(l, r, _)
+            | Expr::ShiftLeft(l, r, _)
+            | Expr::ShiftRight(l, r, _)
+            | Expr::Nth(l, r, _)
+            | Expr::Assign(l, r, _)
+            | Expr::CompoundAssign(l, r, _)
+            | Expr::While(l, r, ..) => vec![l, r],
+
+            Expr::Not(expr, _)
+            | Expr::Negate(expr, _)
+            | Expr::Ref(expr, _)
+            | Expr::Deref(expr, _)
+            | Expr::Cast(expr, ..)
+            | Expr::Print(expr, _)
+            | Expr::Discard(expr)
+            | Expr::Stmt(expr)
+            | Expr::Block(expr)
+            | Expr::Comp(expr)
+            | Expr::Return(expr, _)
+            | Expr::Err(expr, _)
+            | Expr::Propagate(expr, _)
+            | Expr::Member(expr, _, _)
+            | Expr::DeclareFunction(_, expr, ..)
+            | Expr::Function(expr, ..) => vec![expr],
+
+            Expr::StmtBlockWithScope(exprs, _)
+            | Expr::StmtBlock(exprs, _)
+            | Expr::CallFunc(_, _, exprs, _) => exprs.iter().map(|e| e.as_ref()).collect(),
+
+            Expr::Declare(_, _, expr, _, _) => expr.iter().map(|e| e.as_ref()).collect(),
+
+            Expr::If(cond, block, else_block, _) => {
+                let mut children = vec![cond.as_ref(), block.as_ref()];
+                if let Some(else_block) = else_block {
+                    children.push(else_block);
+                }
+                children
+            }
+            Expr::For(_, looper, block, ..) => vec![looper, block],
+            Expr::Match(value, arms, ..) => {
+                let mut children = vec![value.as_ref()];
+                children.extend(arms.iter().map(|arm| &arm.2));
+                children
+            }
+        }
+    }
+
+    /// Returns mutable references to the direct sub-expressions of this expression, in source order.
+    pub fn children_mut(&mut self) -> Vec<&mut Expr> {
+        match self {
+            Expr::Nothing()
+            | Expr::Float(_)
+            | Expr::Int(_)
+            | Expr::Number(..)
+            | Expr::Bool(_)
+            | Expr::Str(_)
+            | Expr::Char(_)
+            | Expr::Variable(..)
+            | Expr::Delete(_)
+            | Expr::Break(..)
+            | Expr::Continue(..)
+            | Expr::This(_)
+            | Expr::Custom(_)
+            | Expr::Custom2(_)
+            | Expr::Value(_)
+            | Expr::DeclareMacro(..)
+            | Expr::Enum(..)
+            | Expr::Use { .. } => vec![],
+
+            Expr::Vector(exprs) | Expr::Array(exprs) => exprs.iter_mut().collect(),
+
+            Expr::Class(_, _, _, methods, _) => methods.iter_mut().map(|m| &mut m.1).collect(),
+
+            Expr::CallMethod(value, _, args, _) => {
+                let mut children = vec![value.as_mut()];
+                children.extend(args.iter_mut());
+                children
+            }
+
+            Expr::Add(l, r, _)
+            | Expr::Sub(l, r, _)
+            | Expr::Mult(l, r, _)
+            | Expr::Div(l, r, _)
+            | Expr::Mod(l, r, _)
+            | Expr::Power(l, r, _)
+            | Expr::EqualEqual(l, r, _)
+            | Expr::BangEqual(l, r, _)
+            | Expr::GreaterEqual(l, r, _)
+            | Expr::LessEqual(l, r, _)
+            | Expr::Less(l, r, _)
+            | Expr::Greater(l, r, _)
+            | Expr::And(l, r, _)
+            | Expr::Or(l, r, _)
+            | Expr::Xor(l, r, _)
+            | Expr::ShiftLeft(l, r, _)
+            | Expr::ShiftRight(l, r, _)
+            | Expr::Nth(l, r, _)
+            | Expr::Assign(l, r, _)
+            | Expr::CompoundAssign(l, r, _)
+            | Expr::While(l, r, ..) => vec![l, r],
+
+            Expr::Not(expr, _)
+            | Expr::Negate(expr, _)
+            | Expr::Ref(expr, _)
+            | Expr::Deref(expr, _)
+            | Expr::Cast(expr, ..)
+            | Expr::Print(expr, _)
+            | Expr::Discard(expr)
+            | Expr::Stmt(expr)
+            | Expr::Block(expr)
+            | Expr::Comp(expr)
+            | Expr::Return(expr, _)
+            | Expr::Err(expr, _)
+            | Expr::Propagate(expr, _)
+            | Expr::Member(expr, _, _)
+            | Expr::DeclareFunction(_, expr, ..)
+            | Expr::Function(expr, ..) => vec![expr],
+
+            Expr::StmtBlockWithScope(exprs, _)
+            | Expr::StmtBlock(exprs, _)
+            | Expr::CallFunc(_, _, exprs, _) => exprs.iter_mut().map(|e| e.as_mut()).collect(),
+
+            Expr::Declare(_, _, expr, _, _) => expr.iter_mut().map(|e| e.as_mut()).collect(),
+
+            Expr::If(cond, block, else_block, _) => {
+                let mut children = vec![cond.as_mut(), block.as_mut()];
+                if let Some(else_block) = else_block {
+                    children.push(else_block);
+                }
+                children
+            }
+            Expr::For(_, looper, block, ..) => vec![looper, block],
+            Expr::Match(value, arms, ..) => {
+                let mut children = vec![value.as_mut()];
+                children.extend(arms.iter_mut().map(|arm| &mut arm.2));
+                children
+            }
+        }
+    }
+}
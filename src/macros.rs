@@ -0,0 +1,162 @@
+use crate::error;
+use crate::expr::Expr;
+use std::collections::HashMap;
+
+/// How deeply macros can expand into other macros, so that a macro expanding into itself is
+/// reported instead of hanging the compiler.
+const MAX_EXPANSION_DEPTH: usize = 64;
+
+/// Expands every call to a macro declared with `mac`, in place.\
+/// A macro can be called anywhere after its declaration in the same file. A call becomes a block
+/// expression of the body of the macro, with its arguments substituted for its parameters.
+pub fn expand_macros(program: &mut Expr) {
+    MacroExpander {
+        macros: HashMap::new(),
+        expansions: 0,
+    }
+    .expand(program, 0);
+}
+
+struct MacroExpander {
+    /// HashMap<macro name, (parameters, body)>
+    macros: HashMap<String, (Vec<String>, Expr)>,
+    /// The number of calls expanded so far, which keeps the renamed locals of each one unique
+    expansions: usize,
+}
+
+impl MacroExpander {
+    fn expand(&mut self, expr: &mut Expr, depth: usize) {
+        if let Expr::DeclareMacro(name, params, body, _) = expr {
+            self.macros
+                .insert(name.clone(), (params.clone(), body.as_ref().clone()));
+            return;
+        }
+
+        // arguments are expanded before they are substituted in
+        for child in expr.children_mut() {
+            self.expand(child, depth);
+        }
+
+        let Expr::CallFunc(name, gens, args, span) = expr else {
+            return;
+        };
+        let Some((params, mut body)) = self.macros.get(name).cloned() else {
+            return;
+        };
+        if depth >= MAX_EXPANSION_DEPTH {
+            error(
+                *span,
+                &format!("Macro '{}' expands into itself too many times", name),
+                "expanding macros",
+            );
+            *expr = Expr::Nothing();
+            return;
+        }
+        if !gens.is_empty() {
+            error(*span, "Macros cannot take generics", "expanding macros");
+        }
+        if args.len() != params.len() {
+            error(
+                *span,
+                &format!(
+                    "Macro '{}' takes {} arguments but was given {}",
+                    name,
+                    params.len(),
+                    args.len()
+                ),
+                "expanding macros",
+            );
+            *expr = Expr::Nothing();
+            return;
+        }
+
+        // Hygiene: the names the macro declares are renamed to hidden names, unique to this
+        // call, so they can never capture (or be captured by) the variables of the caller
+        self.expansions += 1;
+        let mut locals = vec![];
+        declared_names(&body, &mut locals);
+        let renames: HashMap<String, String> = locals
+            .into_iter()
+            .map(|local| {
+                let hidden = format!("%mac{}_{}", self.expansions, local);
+                (local, hidden)
+            })
+            .collect();
+        rename(&mut body, &renames);
+
+        let args: HashMap<String, Expr> = params
+            .into_iter()
+            .zip(args.iter().map(|arg| arg.as_ref().clone()))
+            .collect();
+        substitute(&mut body, &args);
+
+        self.expand(&mut body, depth + 1);
+        *expr = Expr::Block(Box::new(body));
+    }
+}
+
+/// Collects the names of the variables, loop variables, lambda parameters and match bindings an
+/// expression declares.
+fn declared_names(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Declare(name, ..) | Expr::For(name, ..) => names.push(name.clone()),
+        Expr::Function(_, _, params, _) => names.extend(params.iter().map(|p| p.0.clone())),
+        Expr::Match(_, arms, ..) => names.extend(arms.iter().flat_map(|arm| arm.1.clone())),
+        _ => {}
+    }
+    for child in expr.children() {
+        declared_names(child, names);
+    }
+}
+
+fn rename(expr: &mut Expr, renames: &HashMap<String, String>) {
+    match expr {
+        Expr::Variable(name, _)
+        | Expr::Declare(name, ..)
+        | Expr::For(name, ..)
+        | Expr::CallFunc(name, ..)
+        | Expr::Delete(name) => {
+            if let Some(hidden) = renames.get(name) {
+                *name = hidden.clone();
+            }
+        }
+        Expr::Function(_, _, params, _) => {
+            for param in params {
+                if let Some(hidden) = renames.get(&param.0) {
+                    param.0 = hidden.clone();
+                }
+            }
+        }
+        Expr::Match(_, arms, ..) => {
+            for binding in arms.iter_mut().flat_map(|arm| arm.1.iter_mut()) {
+                if let Some(hidden) = renames.get(binding) {
+                    *binding = hidden.clone();
+                }
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children_mut() {
+        rename(child, renames);
+    }
+}
+
+/// Replaces the parameters of a macro with the expressions it was called with. A parameter that
+/// is called, like `f(x)`, calls the function it was given by name.
+fn substitute(expr: &mut Expr, args: &HashMap<String, Expr>) {
+    match expr {
+        Expr::Variable(name, _) if args.contains_key(name) => {
+            *expr = args[name].clone();
+            return;
+        }
+        Expr::CallFunc(name, ..) => {
+            if let Some(Expr::Variable(func, _)) = args.get(name) {
+                *name = func.clone();
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children_mut() {
+        substitute(child, args);
+    }
+}
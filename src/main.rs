@@ -6,12 +6,15 @@ const DEFAULT_PATH: &str = "/home/aster/dev/obstruct/main.obs";
 mod error;
 mod expr;
 mod init;
+mod macros;
 mod runtime_env;
 
 mod transpiler {
     pub mod code_gen_context;
     pub mod compiletime_env;
+    pub mod comptime;
     pub mod expr_to_c;
+    pub mod type_check;
 }
 mod parser;
 mod scanner;
@@ -29,16 +32,8 @@ mod tests;
 //  Rework generic types
 
 // TODO
-//  Add "Vec"
-//  Add "str"
-//  Add conversion (a function) from "strlit" to "&str"
-//  Add generic functions:
-//      Create function instances for each generic variant
-//  Add generic classes
 //  Add checks for "\x"
 //  Let function calls accept any expression as left hand expression, not just directly calling a variable
-//  Add global variables handling
-//  Add compilation-time functions
 
 use crate::error::ObstructError;
 use crate::expr::Expr;
@@ -47,8 +42,10 @@ use crate::scanner::Scanner;
 use crate::span::Span;
 use crate::transpiler::code_gen_context::CodeGenContext;
 use crate::transpiler::compiletime_env::CompileTimeEnv;
+use crate::transpiler::type_check::type_check;
 use colored::Colorize;
 use image::GenericImageView;
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
@@ -68,6 +65,10 @@ static ERROR: Mutex<Result<(), ObstructError>> = Mutex::new(Ok(()));
 static CALL_STACK: Mutex<Vec<String>> = Mutex::new(Vec::new());
 static PROGRAM_NAME: Mutex<String> = Mutex::new(String::new());
 static RUNNING_TESTS: Mutex<bool> = Mutex::new(true);
+thread_local! {
+    // errors are not printed while running tests, they are kept here so that the tests can check them
+    static TEST_ERRORS: RefCell<Vec<(Span, String)>> = const { RefCell::new(Vec::new()) };
+}
 
 // Basic Colors
 const BLACK: &str = "\x1b[30m";
@@ -279,17 +280,18 @@ fn run(filepath: &str) -> Result<(), ObstructError> {
     let mut ctx = CodeGenContext::new();
     let mut cte = CompileTimeEnv::new(&mut ctx);
 
-    Expr::Discard(Box::new(Expr::Declare(
+    let mut programs_to_transpile = HashMap::new();
+
+    // DEBUG is a global like any other, declared before the program so every module can read it
+    Expr::Declare(
         "DEBUG".to_string(),
         None,
         Some(Box::new(Expr::Bool(debug_val))),
         false,
         Span::empty(),
-    )))
-    .to_c(&mut cte, &mut ctx);
-    ctx.body.push_str(";\n");
+    )
+    .pre_transpile(&mut cte, &mut ctx, &mut programs_to_transpile, "");
 
-    let mut programs_to_transpile = HashMap::new();
     programs_to_transpile.insert(filepath.to_string(), false);
 
     loop {
@@ -316,11 +318,26 @@ fn run(filepath: &str) -> Result<(), ObstructError> {
         }
     }
 
-    for programs in programs_to_transpile.keys() {
-        let source = fs::read_to_string(&programs)
-            .map_err(|_| ObstructError::file_not_found(programs.clone()))?;
-        SOURCES.lock().unwrap().push((programs.clone(), source.clone()));
-        let ast = parse(source);
+    // every program is type checked before any C is emitted, so all mismatches are reported
+    let mut programs = Vec::new();
+    for program in programs_to_transpile.keys() {
+        let source = fs::read_to_string(program)
+            .map_err(|_| ObstructError::file_not_found(program.clone()))?;
+        SOURCES
+            .lock()
+            .unwrap()
+            .push((program.clone(), source.clone()));
+        let ast = parse(source.clone());
+        type_check(&ast, &mut cte);
+        SOURCES.lock().unwrap().pop();
+        programs.push((program.clone(), source, ast));
+    }
+    if ERROR.lock().unwrap().is_err() {
+        return ERROR.lock().unwrap().clone();
+    }
+
+    for (program, source, ast) in programs {
+        SOURCES.lock().unwrap().push((program, source));
         ast.to_c(&mut cte, &mut ctx);
         SOURCES.lock().unwrap().pop();
     }
@@ -338,14 +355,22 @@ pub fn error(span: Span, message: &str, place: &str) {
         report(span.line, span.column, message, place, file.as_deref());
 
         panic::set_hook(Box::new(|_| {}));
+    } else {
+        TEST_ERRORS.with_borrow_mut(|errors| errors.push((span, message.to_string())));
     }
 }
 
+/// Takes the errors reported on this thread while running tests
+#[cfg(test)]
+pub fn take_test_errors() -> Vec<(Span, String)> {
+    TEST_ERRORS.with_borrow_mut(std::mem::take)
+}
+
 fn get_source_and_file() -> Option<(String, String)> {
     SOURCES.lock().unwrap().last().cloned()
 }
 
-fn get_current_file() -> Option<String> {
+pub fn get_current_file() -> Option<String> {
     SOURCES.lock().unwrap().last().map(|(path, _)| path.clone())
 }
 
@@ -1,4 +1,5 @@
-use crate::expr::UseKind;
+use crate::expr::{MethodKind, UseKind};
+use crate::macros::expand_macros;
 use crate::span::Span;
 use crate::token_type::TokenType::Pound;
 use crate::type_env::{nil_type, Type};
@@ -31,7 +32,9 @@ impl<'a> Parser<'a> {
             }
         }
 
-        Expr::StmtBlock(statements, self.get_span())
+        let mut program = Expr::StmtBlock(statements, self.get_span());
+        expand_macros(&mut program);
+        program
     }
 
     // ---------- STATEMENTS ----------
@@ -45,13 +48,17 @@ impl<'a> Parser<'a> {
         }
 
         if self.match_any(&[Pound]) {
-            return self.while_loop();
+            return self.while_loop(None);
         }
 
         if self.match_any(&[TokenType::Fn]) {
             return self.define_function();
         }
 
+        if self.match_any(&[TokenType::Mac]) {
+            return self.define_macro();
+        }
+
         if self.match_any(&[TokenType::Hash]) {
             return self.declaration();
         }
@@ -69,13 +76,46 @@ impl<'a> Parser<'a> {
         }
 
         if self.match_any(&[TokenType::For]) {
-            return self.for_loop();
+            return self.for_loop(None);
+        }
+
+        // a labelled loop, `outer: £ cond { ... }`
+        if self.check(TokenType::Ident)
+            && self.peek_next(TokenType::Colon)
+            && (self.peek_next_next(Pound) || self.peek_next_next(TokenType::For))
+        {
+            let label = Some(self.advance().lexeme);
+            self.advance();
+            return if self.match_any(&[Pound]) {
+                self.while_loop(label)
+            } else {
+                self.advance();
+                self.for_loop(label)
+            };
+        }
+
+        if self.match_any(&[TokenType::Break, TokenType::Continue]) {
+            return self.loop_jump();
         }
 
         if self.match_any(&[TokenType::Cls]) {
             return self.class();
         }
 
+        if self.match_any(&[TokenType::Enm]) {
+            return self.enumeration();
+        }
+
+        if self.match_any(&[TokenType::Match]) {
+            return self.match_statement(false);
+        }
+
+        if self.check(TokenType::Comp) && self.peek_next(TokenType::Fn) {
+            self.advance();
+            self.advance();
+            return Expr::Comp(Box::new(self.define_function()));
+        }
+
         Expr::Stmt(Box::new(self.expression()))
     }
 
@@ -142,11 +182,54 @@ impl<'a> Parser<'a> {
     fn class(&mut self) -> Expr {
         self.consume(TokenType::Ident, "Expected class name after 'cls'.");
         let name = self.previous().lexeme;
+
+        let mut generic_params = vec![];
+
+        if self.match_any(&[TokenType::LessLess]) {
+            loop {
+                self.consume(TokenType::Ident, "Expected generic name");
+                generic_params.push(self.previous().lexeme);
+
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::GreaterGreater, "Expected '>'");
+        }
+
+        let parent = if self.match_any(&[TokenType::Colon]) {
+            self.consume(TokenType::Ident, "Expected parent class name after ':'.");
+            Some(Type::simple(&self.previous().lexeme))
+        } else {
+            None
+        };
+
         self.consume(TokenType::LeftBrace, "Expected '{' after class name");
 
         let mut members = Vec::new();
+        let mut methods = Vec::new();
 
         while !self.match_any(&[TokenType::RightBrace]) {
+            if self.match_any(&[TokenType::Fn]) {
+                methods.push((MethodKind::Normal, self.define_function()));
+                self.match_any(&[TokenType::Semicolon]);
+                continue;
+            }
+
+            if self.match_any(&[TokenType::Ovr]) {
+                self.consume(TokenType::Fn, "Expected 'fn' after 'ovr'.");
+                methods.push((MethodKind::Override, self.define_function()));
+                self.match_any(&[TokenType::Semicolon]);
+                continue;
+            }
+
+            if self.match_any(&[TokenType::Stc]) {
+                self.consume(TokenType::Fn, "Expected 'fn' after 'stc'.");
+                methods.push((MethodKind::Static, self.define_function()));
+                self.match_any(&[TokenType::Semicolon]);
+                continue;
+            }
+
             self.consume(
                 TokenType::Ident,
                 "Expected an identifier at start of line (in class declaration)",
@@ -157,30 +240,100 @@ impl<'a> Parser<'a> {
                 "Expected colon after identifier in class declaration",
             );
 
-            members.push((name, self.get_type()));
+            members.push((name, self.get_type().with_conceptuals(&generic_params)));
+            self.match_any(&[TokenType::Comma]);
         }
 
-        let class_type = Type::simple(&name);
+        let generics = generic_params.iter().map(|g| Type::conceptual(g)).collect();
+        let class_type = Type::with_generics(&name, generics);
 
-        Expr::Class(class_type, members, self.get_span())
+        Expr::Class(class_type, parent, members, methods, self.get_span())
     }
+    // ---------- NEW ENUM -----------
+
+    fn enumeration(&mut self) -> Expr {
+        self.consume(TokenType::Ident, "Expected enum name after 'enm'.");
+        let name = self.previous().lexeme;
+        self.consume(TokenType::LeftBrace, "Expected '{' after enum name");
+
+        let mut variants = Vec::new();
+        while !self.match_any(&[TokenType::RightBrace]) {
+            let variant = self.ident();
+            let mut payload = vec![];
+            if self.match_any(&[TokenType::LeftParen]) {
+                loop {
+                    payload.push(self.get_type());
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expected ')' after variant payload.");
+            }
+            variants.push((variant, payload));
+            self.match_any(&[TokenType::Comma]);
+        }
+
+        Expr::Enum(Type::simple(&name), variants, self.get_span())
+    }
+
+    // ---------- MATCH -----------
+
+    fn match_statement(&mut self, is_expr: bool) -> Expr {
+        let value = self.expression();
+        let span = self.get_span();
+        self.consume(TokenType::LeftBrace, "Expected '{' after 'match' value.");
+
+        let mut arms = Vec::new();
+        while !self.match_any(&[TokenType::RightBrace]) {
+            let variant = self.ident();
+            let mut bindings = vec![];
+            if self.match_any(&[TokenType::LeftParen]) {
+                loop {
+                    bindings.push(self.ident());
+                    if !self.match_any(&[TokenType::Comma]) {
+                        break;
+                    }
+                }
+                self.consume(TokenType::RightParen, "Expected ')' after variant bindings.");
+            }
+            self.consume(TokenType::MinusRight, "Expected '->' after match pattern.");
+            self.consume(TokenType::LeftBrace, "Expected '{' after '->'.");
+            arms.push((variant, bindings, self.statement_block()));
+            self.match_any(&[TokenType::Comma]);
+        }
+
+        Expr::Match(Box::new(value), arms, is_expr, span)
+    }
+
     // ---------- MEMBER VARIABLE -----------
 
     fn member(&mut self) -> Expr {
         let variable = self.nth();
 
-        if self.match_any(&[TokenType::Dot]) {
+        let mut expr = if self.match_any(&[TokenType::Dot]) {
             let member = self.ident();
 
-            Expr::Member(Box::new(variable), member, self.get_span())
+            if self.check(TokenType::LeftParen) {
+                let arguments = self.call_arguments();
+                Expr::CallMethod(Box::new(variable), member, arguments, self.get_span())
+            } else {
+                Expr::Member(Box::new(variable), member, self.get_span())
+            }
         } else {
             variable
+        };
+
+        // value$? returns the err of a res from the function
+        while self.match_any(&[TokenType::DollarQuestionMark]) {
+            expr = Expr::Propagate(Box::new(expr), self.get_span());
         }
+        expr
     }
 
     // ---------- WHILE LOOP -----------
 
-    fn while_loop(&mut self) -> Expr {
+    fn while_loop(&mut self, label: Option<String>) -> Expr {
+        let span = self.get_span();
         let cond = self.expression();
         // self.current += 1;
         let block = if self.match_any(&[TokenType::LeftBrace]) {
@@ -197,12 +350,12 @@ impl<'a> Parser<'a> {
             Expr::Nothing()
         };
 
-        Expr::While(Box::new(cond), Box::new(block))
+        Expr::While(Box::new(cond), Box::new(block), label, span)
     }
 
     // ----------- FOR LOOP -----------
 
-    fn for_loop(&mut self) -> Expr {
+    fn for_loop(&mut self, label: Option<String>) -> Expr {
         self.consume(TokenType::Ident, "Expected identifier after 'For' token");
         let loopee = self.previous().lexeme;
         self.consume(TokenType::Colon, "Expected ':' after loopee");
@@ -210,7 +363,28 @@ impl<'a> Parser<'a> {
         self.consume(TokenType::LeftBrace, "Expected '{' after looper");
         let block = self.statement_block();
 
-        Expr::For(loopee, Box::new(looper), Box::new(block), self.get_span())
+        Expr::For(
+            loopee,
+            Box::new(looper),
+            Box::new(block),
+            label,
+            self.get_span(),
+        )
+    }
+
+    // ----------- BREAK / CONTINUE -----------
+
+    /// Parses the `break` or `continue` just consumed, with the label of the loop it refers to.
+    fn loop_jump(&mut self) -> Expr {
+        let is_break = self.previous().token_type == TokenType::Break;
+        let label = self
+            .match_any(&[TokenType::Ident])
+            .then(|| self.previous().lexeme);
+        if is_break {
+            Expr::Break(label, self.get_span())
+        } else {
+            Expr::Continue(label, self.get_span())
+        }
     }
 
     // ---------- DECLARATION ----------
@@ -249,6 +423,25 @@ impl<'a> Parser<'a> {
         if self.match_any(&[TokenType::Equal]) {
             let right = self.bools();
             Expr::Assign(Box::new(left), Box::new(right), self.get_span())
+        } else if self.match_any(&[
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::ModEqual,
+        ]) {
+            let operator = self.previous().token_type;
+            let target = Box::new(left.clone());
+            let right = Box::new(self.bools());
+            let span = self.get_span();
+            let operation = match operator {
+                TokenType::PlusEqual => Expr::Add(target, right, span),
+                TokenType::MinusEqual => Expr::Sub(target, right, span),
+                TokenType::StarEqual => Expr::Mult(target, right, span),
+                TokenType::SlashEqual => Expr::Div(target, right, span),
+                _ => Expr::Mod(target, right, span),
+            };
+            Expr::CompoundAssign(Box::new(left), Box::new(operation), span)
         } else {
             left
         }
@@ -306,6 +499,29 @@ impl<'a> Parser<'a> {
 
     // ---------- FUNCTIONS ------------
 
+    fn define_macro(&mut self) -> Expr {
+        self.consume(TokenType::Ident, "Expected macro name after 'mac'.");
+        let name = self.previous().lexeme;
+        let span = self.get_span();
+
+        let mut parameters = vec![];
+        if self.match_any(&[TokenType::LeftParen]) {
+            while !self.is_at_end() && !self.check(TokenType::RightParen) {
+                self.consume(TokenType::Ident, "Expected parameter name.");
+                parameters.push(self.previous().lexeme);
+                if !self.match_any(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+            self.consume(TokenType::RightParen, "Expected ')' after macro parameters.");
+        }
+
+        self.consume(TokenType::LeftBrace, "Expected '{' after macro declaration.");
+        let body = Box::new(self.statement_block());
+
+        Expr::DeclareMacro(name, parameters, body, span)
+    }
+
     fn define_function(&mut self) -> Expr {
         let mut generic_params = vec![];
 
@@ -444,13 +660,19 @@ impl<'a> Parser<'a> {
             self.consume(TokenType::GreaterGreater, "Expected '>' after generics");
         }
 
+        let arguments = self.call_arguments().into_iter().map(Box::new).collect();
+
+        Expr::CallFunc(name, generics, arguments, self.get_span())
+    }
+
+    fn call_arguments(&mut self) -> Vec<Expr> {
         self.consume(TokenType::LeftParen, "Expected '(' after function name.");
 
         let mut arguments = vec![];
 
         if !self.check(TokenType::RightParen) {
             loop {
-                arguments.push(Box::new(self.expression()));
+                arguments.push(self.expression());
                 if !self.match_any(&[TokenType::Comma]) {
                     break;
                 }
@@ -459,7 +681,7 @@ impl<'a> Parser<'a> {
 
         self.consume(TokenType::RightParen, "Missing ')' after function call.");
 
-        Expr::CallFunc(name, generics, arguments, self.get_span())
+        arguments
     }
 
     // ---------- EXPRESSIONS ----------
@@ -470,12 +692,13 @@ impl<'a> Parser<'a> {
     fn bools(&mut self) -> Expr {
         let mut expr = self.compare();
 
-        while self.match_any(&[TokenType::And, TokenType::Or]) {
+        while self.match_any(&[TokenType::And, TokenType::Or, TokenType::UpArrow]) {
             let op = self.previous().token_type;
             let right = self.compare();
             expr = match op {
                 TokenType::And => Expr::And(Box::new(expr), Box::new(right), self.get_span()),
                 TokenType::Or => Expr::Or(Box::new(expr), Box::new(right), self.get_span()),
+                TokenType::UpArrow => Expr::Xor(Box::new(expr), Box::new(right), self.get_span()),
                 _ => unreachable!(),
             };
         }
@@ -484,7 +707,7 @@ impl<'a> Parser<'a> {
     }
 
     fn compare(&mut self) -> Expr {
-        let mut expr = self.term();
+        let mut expr = self.shift();
 
         while self.match_any(&[
             TokenType::EqualEqual,
@@ -495,7 +718,7 @@ impl<'a> Parser<'a> {
             TokenType::LessEqual,
         ]) {
             let op = self.previous().token_type;
-            let right = self.term();
+            let right = self.shift();
             expr = match op {
                 TokenType::EqualEqual => {
                     Expr::EqualEqual(Box::new(expr), Box::new(right), self.get_span())
@@ -520,6 +743,26 @@ impl<'a> Parser<'a> {
         expr
     }
 
+    fn shift(&mut self) -> Expr {
+        let mut expr = self.term();
+
+        while self.match_any(&[TokenType::LessLess, TokenType::GreaterGreater]) {
+            let op = self.previous().token_type;
+            let right = self.term();
+            expr = match op {
+                TokenType::LessLess => {
+                    Expr::ShiftLeft(Box::new(expr), Box::new(right), self.get_span())
+                }
+                TokenType::GreaterGreater => {
+                    Expr::ShiftRight(Box::new(expr), Box::new(right), self.get_span())
+                }
+                _ => unreachable!(),
+            };
+        }
+
+        expr
+    }
+
     fn term(&mut self) -> Expr {
         let mut expr = self.factor();
 
@@ -537,11 +780,11 @@ impl<'a> Parser<'a> {
     }
 
     fn factor(&mut self) -> Expr {
-        let mut expr = self.unary();
+        let mut expr = self.cast();
 
         while self.match_any(&[TokenType::Star, TokenType::Slash, TokenType::Mod]) {
             let op = self.previous().token_type;
-            let right = self.unary();
+            let right = self.cast();
             expr = match op {
                 TokenType::Star => Expr::Mult(Box::new(expr), Box::new(right), self.get_span()),
                 TokenType::Slash => Expr::Div(Box::new(expr), Box::new(right), self.get_span()),
@@ -553,21 +796,31 @@ impl<'a> Parser<'a> {
         expr
     }
 
+    fn cast(&mut self) -> Expr {
+        let mut expr = self.unary();
+
+        while self.match_any(&[TokenType::MinusRight]) {
+            expr = Expr::Cast(Box::new(expr), self.get_type(), self.get_span());
+        }
+
+        expr
+    }
+
     fn unary(&mut self) -> Expr {
         if self.match_any(&[TokenType::Minus]) {
-            return Expr::Sub(
-                Box::new(Expr::Nothing()),
-                Box::new(self.unary()),
-                self.get_span(),
-            );
+            // a negative number is a single literal, but -2 ** 2 is still -(2 ** 2)
+            if (self.check(TokenType::Int) || self.check(TokenType::Float))
+                && !self.peek_next(TokenType::StarStar)
+            {
+                self.advance();
+                return self.number(true);
+            }
+            return Expr::Negate(Box::new(self.unary()), self.get_span());
         }
 
         if self.match_any(&[TokenType::Plus]) {
-            return Expr::Add(
-                Box::new(Expr::Nothing()),
-                Box::new(self.unary()),
-                self.get_span(),
-            );
+            // +x is just x
+            return self.unary();
         }
 
         if self.match_any(&[TokenType::Bang]) {
@@ -655,24 +908,25 @@ impl<'a> Parser<'a> {
     // ---------- PRIMARY ----------
     fn primary(&mut self) -> Expr {
         if self.match_any(&[TokenType::LeftBrace]) {
-            return self.statement_block();
+            return Expr::Block(Box::new(self.statement_block()));
         }
 
         if self.check(TokenType::Ident) {
-            let item = self.item();
+            let mut item = self.item();
+
+            // static functions of a class: Type::func
+            if self.match_any(&[TokenType::DoubleColon]) {
+                item = format!("{}::{}", item, self.ident());
+            }
 
-            if self.check(TokenType::LeftParen) || self.check(TokenType::LessLess) {
+            if self.check(TokenType::LeftParen) || self.is_generic_call() {
                 return self.call_function(item);
             }
             return Expr::Variable(item, self.get_span());
         }
 
-        if self.match_any(&[TokenType::Float]) {
-            return Expr::Float(self.previous().literal.parse().unwrap_or(0.0));
-        }
-
-        if self.match_any(&[TokenType::Int]) {
-            return Expr::Int(self.previous().literal.parse().unwrap_or(0));
+        if self.match_any(&[TokenType::Int, TokenType::Float]) {
+            return self.number(false);
         }
 
         if self.match_any(&[TokenType::True]) {
@@ -701,10 +955,23 @@ impl<'a> Parser<'a> {
             return self.if_statement(true);
         }
 
+        if self.match_any(&[TokenType::Match]) {
+            return self.match_statement(true);
+        }
+
         if self.match_any(&[TokenType::This]) {
             return Expr::This(self.get_span());
         }
 
+        if self.match_any(&[TokenType::Comp]) {
+            return Expr::Comp(Box::new(self.expression()));
+        }
+
+        if self.match_any(&[TokenType::Err]) {
+            let span = self.get_span();
+            return Expr::Err(Box::new(self.expression()), span);
+        }
+
         if self.match_any(&[TokenType::Lam]) {
             return self.define_lambda();
         }
@@ -740,6 +1007,9 @@ impl<'a> Parser<'a> {
 
             return Type::simple(&name);
         } else if self.match_any(&[TokenType::LeftBrack]) {
+            if self.match_any(&[TokenType::RightBrack]) {
+                return nil_type();
+            }
             let mut gens = vec![];
 
             loop {
@@ -760,6 +1030,62 @@ impl<'a> Parser<'a> {
         nil_type()
     }
 
+    /// Parses the number literal just consumed, negated if it follows a `-`. Without a type
+    /// suffix, like the `u64` of `10u64`, it is an i32 or an f64.
+    fn number(&mut self, negative: bool) -> Expr {
+        let token = self.previous();
+        let is_float = token.token_type == TokenType::Float;
+        let sign = if negative { "-" } else { "" };
+        let literal = format!("{}{}", sign, token.literal);
+        let (number, suffix) = literal.split_at(
+            literal
+                .find(|c: char| c.is_ascii_alphabetic())
+                .unwrap_or(literal.len()),
+        );
+        let ty = match suffix {
+            "" if is_float => Type::simple("f64"),
+            "" => Type::simple("i32"),
+            _ => Type::simple(suffix),
+        };
+
+        if is_float && !ty.is_float() {
+            error(
+                self.get_span(),
+                &format!("The float '{}' cannot have the suffix '{}'", literal, ty),
+                "parsing",
+            );
+            return Expr::Float(0.0);
+        }
+        if let Some((bits, signed)) = ty.integer_bits() {
+            // a signed integer goes one further below zero than above it
+            let max = match (signed, negative) {
+                (true, _) => (1u64 << (bits - 1)) - 1 + negative as u64,
+                (false, false) => u64::MAX >> (64 - bits),
+                (false, true) => 0,
+            };
+            let digits = number.trim_start_matches('-');
+            if !digits.parse::<u64>().is_ok_and(|n| n <= max) {
+                let hint = if suffix.is_empty() {
+                    format!(", give it a suffix like '{}i64'", number)
+                } else {
+                    String::new()
+                };
+                error(
+                    self.get_span(),
+                    &format!("The integer '{}' does not fit in '{}'{}", literal, ty, hint),
+                    "parsing",
+                );
+                return Expr::Int(0);
+            }
+        }
+
+        match ty.name() {
+            "i32" => Expr::Int(number.parse().unwrap_or(0)),
+            "f64" => Expr::Float(number.parse().unwrap_or(0.0)),
+            _ => Expr::Number(number.to_string(), ty),
+        }
+    }
+
     // ---------- UTIL ----------
     fn get_span(&self) -> Span {
         let token = self.previous();
@@ -781,6 +1107,34 @@ impl<'a> Parser<'a> {
         ident
     }
 
+    /// Returns true if the `<<` after a name opens the generics of a call, like `f<<i32>>(x)`,
+    /// rather than being a left shift, like `x << 2`.
+    fn is_generic_call(&self) -> bool {
+        let mut depth = 0;
+        for (i, token) in self.tokens[self.current..].iter().enumerate() {
+            match token.token_type {
+                TokenType::LessLess => depth += 1,
+                TokenType::GreaterGreater => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return self
+                            .tokens
+                            .get(self.current + i + 1)
+                            .is_some_and(|t| t.token_type == TokenType::LeftParen);
+                    }
+                }
+                TokenType::Ident
+                | TokenType::Comma
+                | TokenType::LeftBrack
+                | TokenType::RightBrack
+                | TokenType::And
+                    if depth > 0 => {}
+                _ => return false,
+            }
+        }
+        false
+    }
+
     fn match_any(&mut self, types: &[TokenType]) -> bool {
         for t in types {
             if self.check(*t) {
@@ -851,28 +1205,42 @@ impl<'a> Parser<'a> {
 
 // Grammar:
 /*
-statement_block -> "{" ( statement )* "}"
-statement       -> ( print | declaration | expression | return | function ) ";"
+statement_block -> "{" ( statement ";" )* statement? "}" // a last statement without ";" is its value
+statement       -> ( print | declaration | expression | return | while | for | break | continue | function | comp_function | macro | class | enum | match ) ";"
 
 print           -> "$" ( "$" )? expression
 declaration     -> "#" ( "@" )? IDENTIFIER ( ":" type )? ( "=" expression )? // need one or both
 return          -> "ret" expression
-while           -> "£" expression statement_block
+while           -> ( IDENTIFIER ":" )? "£" expression statement_block
+for             -> ( IDENTIFIER ":" )? "for" IDENTIFIER ":" expression statement_block
+break           -> "break" IDENTIFIER? // the label of the loop, by default the innermost one
+continue        -> "continue" IDENTIFIER?
 
 if_statement    -> "?" expression statement_block ( "~?" expression statement_block )* ( "~" statement_block )?
 function_call   -> IDENTIFIER "(" (expression)* ")"
 function        -> "fn" ( "<<" IDENTIFIER* ">>" )? IDENTIFIER ( "(" (IDENTIFIER ":" IDENTIFIER)* ")" )? type? statement_block
+comp_function   -> "comp" function
+macro           -> "mac" IDENTIFIER ( "(" IDENTIFIER* ")" )? statement_block
+enum            -> "enm" IDENTIFIER "{" ( IDENTIFIER ( "(" type* ")" )? ","? )* "}"
+match           -> "match" expression "{" ( IDENTIFIER ( "(" IDENTIFIER* ")" )? "->" statement_block ","? )* "}" // "_" matches the rest
+class           -> "cls" IDENTIFIER ( "<<" IDENTIFIER* ">>" )? ( ":" IDENTIFIER )? "{" ( IDENTIFIER ":" type ","? | ( "stc" | "ovr" )? function ";"? )* "}"
+method_call     -> nth "." IDENTIFIER "(" (expression)* ")"
+static_call     -> IDENTIFIER "::" IDENTIFIER "(" (expression)* ")"
 
 type            -> IDENTIFIER | "[" type* "]" | "<<" type* ">>"
 
-expression      -> bool
-bool            -> compare ( ( "&" | "|" ) compare )*
-compare         -> term ( ( "==" | ">=" | "<=" | ">" | "<" | "!=" ) term )*
+expression      -> assignment
+assignment      -> bool ( ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) bool )?
+bool            -> compare ( ( "&" | "|" | "^" ) compare )* // bitwise on integers
+compare         -> shift ( ( "==" | ">=" | "<=" | ">" | "<" | "!=" ) shift )*
+shift           -> term ( ( "<<" | ">>" ) term )*
 term            -> factor ( ( "+" | "-" ) factor )*
-factor          -> unary ( ( "*" | "/" | "%" ) unary )*
-unary           -> ( "-" | "+" ) unary | power
-power           -> nth ( ( "**" ) nth )*
+factor          -> cast ( ( "*" | "/" | "%" ) cast )*
+cast            -> unary ( "->" type )*
+unary           -> ( "-" | "+" | "!" ) unary | power // "!" is bitwise on integers, "-" NUMBER is a literal
+power           -> member ( ( "**" ) member )*
+member          -> nth ( "." IDENTIFIER ( "(" (expression)* ")" )? )? ( "$?" )*
 nth             -> primary ( "[" expression "]" )?
-primary         -> NUMBER | STRING | BOOLEAN | IDENTIFIER | "(" expression ")" | statement_block | if_statement | function_call
+primary         -> NUMBER ( SUFFIX )? | STRING | BOOLEAN | IDENTIFIER | "(" expression ")" | statement_block | if_statement | function_call | "comp" expression | "err" expression | match
 
 */
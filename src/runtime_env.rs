@@ -12,6 +12,10 @@ pub struct RuntimeEnvironment {
     this: Vec<String>,
     window: Option<CWindow>,
     storage: Vec<Option<Variable>>,
+    /// The loop iterations and calls a compile-time evaluation can still make
+    pub(crate) fuel: usize,
+    /// The number of `comp fn` calls being evaluated, one inside the other
+    pub(crate) call_depth: usize,
 }
 
 impl RuntimeEnvironment {
@@ -21,6 +25,8 @@ impl RuntimeEnvironment {
             this: vec![],
             window: None,
             storage: vec![],
+            fuel: 0,
+            call_depth: 0,
         }
     }
 
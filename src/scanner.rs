@@ -2,6 +2,7 @@ use crate::error;
 use crate::span::Span;
 use crate::token::Token;
 use crate::token_type::TokenType;
+use crate::type_env::NUMBER_TYPES;
 use std::collections::HashMap;
 
 pub struct Scanner {
@@ -20,6 +21,8 @@ impl Scanner {
         let mut keywords = HashMap::new();
 
         keywords.insert("cls".into(), TokenType::Cls);
+        keywords.insert("enm".into(), TokenType::Enm);
+        keywords.insert("match".into(), TokenType::Match);
         keywords.insert("ret".into(), TokenType::Ret);
         keywords.insert("comp".into(), TokenType::Comp);
         keywords.insert("stc".into(), TokenType::Stc);
@@ -28,6 +31,8 @@ impl Scanner {
         keywords.insert("del".into(), TokenType::Del);
         keywords.insert("use".into(), TokenType::Use);
         keywords.insert("for".into(), TokenType::For);
+        keywords.insert("break".into(), TokenType::Break);
+        keywords.insert("continue".into(), TokenType::Continue);
         keywords.insert("fn".into(), TokenType::Fn);
         keywords.insert("mac".into(), TokenType::Mac);
         keywords.insert("lam".into(), TokenType::Lam);
@@ -78,15 +83,29 @@ impl Scanner {
             '|' => self.add_token(TokenType::Or),
             ',' => self.add_token(TokenType::Comma),
             '.' => self.add_token(TokenType::Dot),
-            '%' => self.add_token(TokenType::Mod),
+            '%' => {
+                if self.match_char('=') {
+                    self.add_token(TokenType::ModEqual);
+                } else {
+                    self.add_token(TokenType::Mod);
+                }
+            }
             '-' => {
                 if self.match_char('>') {
                     self.add_token(TokenType::MinusRight);
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::MinusEqual);
                 } else {
                     self.add_token(TokenType::Minus);
                 }
             }
-            '+' => self.add_token(TokenType::Plus),
+            '+' => {
+                if self.match_char('=') {
+                    self.add_token(TokenType::PlusEqual);
+                } else {
+                    self.add_token(TokenType::Plus);
+                }
+            }
             ';' => self.add_token(TokenType::Semicolon),
             ':' => {
                 if self.match_char(':') {
@@ -98,6 +117,8 @@ impl Scanner {
             '*' => {
                 if self.match_char('*') {
                     self.add_token(TokenType::StarStar);
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::StarEqual);
                 } else {
                     self.add_token(TokenType::Star);
                 }
@@ -171,6 +192,8 @@ impl Scanner {
                 } else if self.match_char('*') {
                     // Multi-line comment
                     self.block_comment();
+                } else if self.match_char('=') {
+                    self.add_token(TokenType::SlashEqual);
                 } else {
                     self.add_token(TokenType::Slash);
                 }
@@ -341,14 +364,32 @@ impl Scanner {
             while self.peek().is_ascii_digit() {
                 self.advance();
             }
+            self.number_suffix();
             let value = self.source[self.start..self.current].to_string();
             self.add_token_literal(TokenType::Float, value);
         } else {
+            self.number_suffix();
             let value = self.source[self.start..self.current].to_string();
             self.add_token_literal(TokenType::Int, value);
         }
     }
 
+    /// Consumes the type suffix of a number literal, like the `u64` of `10u64`, which stays part
+    /// of its literal.
+    fn number_suffix(&mut self) {
+        let rest = &self.source[self.current..];
+        let suffix = NUMBER_TYPES.iter().find(|suffix| {
+            rest.starts_with(**suffix)
+                && !rest[suffix.len()..]
+                    .starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_')
+        });
+        if let Some(suffix) = suffix {
+            for _ in 0..suffix.len() {
+                self.advance();
+            }
+        }
+    }
+
     fn identifier(&mut self) {
         while self.alpha_numeric(self.peek()) {
             self.advance();
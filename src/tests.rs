@@ -4,9 +4,11 @@
 //! - Scanner/Lexer: Tokenization of source code
 //! - Parser: AST construction from tokens
 //! - Type System: Type checking and inference
+//! - Type Checker: Type mismatches found before C is emitted
 //! - Transpiler: C code generation
 
 pub mod scanner_tests;
 pub mod parser_tests;
 pub mod type_env_tests;
+pub mod type_check_tests;
 pub mod transpiler_tests;
@@ -136,6 +136,28 @@ fn test_parse_assignment() {
     }
 }
 
+#[test]
+fn test_parse_compound_assignment() {
+    let expr = parse_source("v[i] -= 2;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                crate::expr::Expr::CompoundAssign(target, operation, _) => {
+                    assert!(matches!(target.as_ref(), crate::expr::Expr::Nth(..)));
+                    let crate::expr::Expr::Sub(l, r, _) = operation.as_ref() else {
+                        panic!("Expected Sub");
+                    };
+                    assert!(matches!(l.as_ref(), crate::expr::Expr::Nth(..)));
+                    assert!(matches!(r.as_ref(), crate::expr::Expr::Int(2)));
+                }
+                _ => panic!("Expected CompoundAssign"),
+            },
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== If/Else If/Else ==========
 
 #[test]
@@ -185,7 +207,7 @@ fn test_parse_while_loop() {
     let expr = parse_source("£ x < 10 { ret; }");
     match expr {
         crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
-            crate::expr::Expr::While(_, _) => {}
+            crate::expr::Expr::While(..) => {}
             _ => panic!("Expected While"),
         },
         _ => panic!("Expected StmtBlock"),
@@ -199,13 +221,54 @@ fn test_parse_for_loop() {
     let expr = parse_source("for i: 0..5 { ret; }");
     match expr {
         crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
-            crate::expr::Expr::For(_, _, _, _) => {}
+            crate::expr::Expr::For(..) => {}
             _ => panic!("Expected For"),
         },
         _ => panic!("Expected StmtBlock"),
     }
 }
 
+// ========== Break and Continue ==========
+
+#[test]
+fn test_parse_break_and_continue() {
+    let expr = parse_source("£ `t { continue; break; }");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::While(_, block, None, _) => {
+                let crate::expr::Expr::StmtBlockWithScope(stmts, _) = block.as_ref() else {
+                    panic!("Expected a block");
+                };
+                assert!(matches!(
+                    stmts[0].as_ref(),
+                    crate::expr::Expr::Discard(e) if matches!(e.as_ref(), crate::expr::Expr::Continue(None, _))
+                ));
+                assert!(matches!(
+                    stmts[1].as_ref(),
+                    crate::expr::Expr::Discard(e) if matches!(e.as_ref(), crate::expr::Expr::Break(None, _))
+                ));
+            }
+            _ => panic!("Expected While"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_labelled_loops() {
+    let expr = parse_source("outer: for i : 3 { inner: £ `t { break outer; }; }");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::For(name, _, _, Some(label), _) => {
+                assert_eq!(name, "i");
+                assert_eq!(label, "outer");
+            }
+            _ => panic!("Expected a labelled For"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Print Statements ==========
 
 #[test]
@@ -365,6 +428,103 @@ fn test_parse_lambda() {
     }
 }
 
+// ========== Classes ==========
+
+#[test]
+fn test_parse_generic_class() {
+    let expr = parse_source("cls Pair<<A, B>> { first: A, second: vec<<B>> };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Class(ty, _, members, _, _) => {
+                assert_eq!(ty.to_string(), "Pair<<A, B>>");
+                assert!(ty.generics()[0].is_conceptual());
+                assert_eq!(members.len(), 2);
+                assert!(members[0].1.is_conceptual());
+                assert!(members[1].1.contains_conceptual());
+            }
+            _ => panic!("Expected Class"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_class_members_without_commas() {
+    let expr = parse_source("cls P { a: i32 b: f64, c: bool };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Class(_, _, members, _, _) => {
+                let names: Vec<&str> = members.iter().map(|m| m.0.as_str()).collect();
+                assert_eq!(names, vec!["a", "b", "c"]);
+            }
+            _ => panic!("Expected Class"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_class_methods() {
+    let expr = parse_source(
+        "cls C { n: i32, fn get() i32 { ret `v.n; }; stc fn make() C { #c: C; ret c; }; };",
+    );
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Class(_, _, members, methods, _) => {
+                assert_eq!(members.len(), 1);
+                assert_eq!(methods.len(), 2);
+                assert!(matches!(methods[0].0, crate::expr::MethodKind::Normal));
+                assert!(matches!(methods[1].0, crate::expr::MethodKind::Static));
+            }
+            _ => panic!("Expected Class"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_method_and_static_calls() {
+    let expr = parse_source("c.get(1); C::make();");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => {
+            match statements[0].as_ref() {
+                crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                    crate::expr::Expr::CallMethod(_, method, args, _) => {
+                        assert_eq!(method, "get");
+                        assert_eq!(args.len(), 1);
+                    }
+                    _ => panic!("Expected CallMethod"),
+                },
+                _ => panic!("Expected Stmt"),
+            }
+            match statements[1].as_ref() {
+                crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                    crate::expr::Expr::CallFunc(name, _, _, _) => assert_eq!(name, "C::make"),
+                    _ => panic!("Expected CallFunc"),
+                },
+                _ => panic!("Expected Stmt"),
+            }
+        }
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_class_inheritance() {
+    let expr = parse_source("cls B : A { ovr fn f() {}; };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Class(ty, parent, _, methods, _) => {
+                assert_eq!(ty.to_string(), "B");
+                assert_eq!(parent.as_ref().unwrap().to_string(), "A");
+                assert!(matches!(methods[0].0, crate::expr::MethodKind::Override));
+            }
+            _ => panic!("Expected Class"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Binary Operators ==========
 
 #[test]
@@ -609,6 +769,61 @@ fn test_parse_or() {
     }
 }
 
+// ========== Bitwise Operators ==========
+
+#[test]
+fn test_parse_xor() {
+    let expr = parse_source("a ^ b;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => {
+                assert!(matches!(inner.as_ref(), crate::expr::Expr::Xor(..)));
+            }
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_shift_binds_looser_than_add() {
+    // x << 1 + 2 should be parsed as x << (1 + 2), not as a generic call
+    let expr = parse_source("x << 1 + 2;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                crate::expr::Expr::ShiftLeft(left, right, _) => {
+                    assert!(matches!(left.as_ref(), crate::expr::Expr::Variable(n, _) if n == "x"));
+                    assert!(matches!(right.as_ref(), crate::expr::Expr::Add(..)));
+                }
+                _ => panic!("Expected ShiftLeft"),
+            },
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_generic_call_is_not_shift() {
+    let expr = parse_source("f<<vec<<i32>>>>(x) >> 1;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                crate::expr::Expr::ShiftRight(left, _, _) => {
+                    assert!(matches!(
+                        left.as_ref(),
+                        crate::expr::Expr::CallFunc(name, gens, _, _) if name == "f" && gens.len() == 1
+                    ));
+                }
+                _ => panic!("Expected ShiftRight"),
+            },
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Unary Operators ==========
 
 #[test]
@@ -634,9 +849,27 @@ fn test_parse_unary_minus() {
     match expr {
         crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
             crate::expr::Expr::Stmt(inner) => {
-                if let crate::expr::Expr::Sub(_, _, _) = inner.as_ref() {
+                if let crate::expr::Expr::Int(-5) = inner.as_ref() {
+                } else {
+                    panic!("Expected a negative literal for unary minus");
+                }
+            }
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_negate_variable() {
+    let expr = parse_source("-x;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => {
+                if let crate::expr::Expr::Negate(operand, _) = inner.as_ref() {
+                    assert!(matches!(operand.as_ref(), crate::expr::Expr::Variable(..)));
                 } else {
-                    panic!("Expected Sub for unary minus");
+                    panic!("Expected Negate for unary minus");
                 }
             }
             _ => panic!("Expected Stmt"),
@@ -645,6 +878,45 @@ fn test_parse_unary_minus() {
     }
 }
 
+#[test]
+fn test_parse_negative_literal_power() {
+    // -2 ** 2 is -(2 ** 2), not (-2) ** 2
+    let expr = parse_source("-2 ** 2;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => {
+                if let crate::expr::Expr::Negate(operand, _) = inner.as_ref() {
+                    assert!(matches!(operand.as_ref(), crate::expr::Expr::Power(..)));
+                } else {
+                    panic!("Expected Negate of a power");
+                }
+            }
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_smallest_signed_literal() {
+    let expr = parse_source("-2147483648; -128i8;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => {
+            assert!(matches!(
+                statements[0].as_ref(),
+                crate::expr::Expr::Stmt(inner)
+                    if matches!(inner.as_ref(), crate::expr::Expr::Int(i32::MIN))
+            ));
+            assert!(matches!(
+                statements[1].as_ref(),
+                crate::expr::Expr::Stmt(inner)
+                    if matches!(inner.as_ref(), crate::expr::Expr::Number(n, _) if n == "-128")
+            ));
+        }
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Function Calls ==========
 
 #[test]
@@ -722,6 +994,28 @@ fn test_parse_array_literal() {
     }
 }
 
+#[test]
+fn test_parse_array_types() {
+    let expr = parse_source("#a: [i32]; #b: [];");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => {
+            match statements[0].as_ref() {
+                crate::expr::Expr::Declare(_, Some(var_type), _, _, _) => {
+                    assert_eq!(var_type.to_string(), "arr<<i32>>");
+                }
+                _ => panic!("Expected Declare"),
+            }
+            match statements[1].as_ref() {
+                crate::expr::Expr::Declare(_, Some(var_type), _, _, _) => {
+                    assert_eq!(var_type.to_string(), "arr");
+                }
+                _ => panic!("Expected Declare"),
+            }
+        }
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Vector Literal ==========
 
 #[test]
@@ -849,6 +1143,24 @@ fn test_parse_char() {
     }
 }
 
+#[test]
+fn test_parse_suffixed_number() {
+    let expr = parse_source("5000000000i64;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                crate::expr::Expr::Number(digits, ty) => {
+                    assert_eq!(digits, "5000000000");
+                    assert_eq!(ty.name(), "i64");
+                }
+                _ => panic!("Expected Number"),
+            },
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Parenthesized Expressions ==========
 
 #[test]
@@ -895,6 +1207,27 @@ fn test_operator_precedence() {
     }
 }
 
+#[test]
+fn test_cast_binds_tighter_than_add() {
+    // a + b -> i64 should be parsed as a + (b -> i64)
+    let expr = parse_source("a + b -> i64;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Stmt(inner) => match inner.as_ref() {
+                crate::expr::Expr::Add(_, right, _) => {
+                    assert!(matches!(
+                        right.as_ref(),
+                        crate::expr::Expr::Cast(_, ty, _) if ty.name() == "i64"
+                    ));
+                }
+                _ => panic!("Expected Add at top level"),
+            },
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Block Expressions ==========
 
 #[test]
@@ -903,10 +1236,12 @@ fn test_parse_block_expression() {
     match expr {
         crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
             crate::expr::Expr::Stmt(inner) => {
-                if let crate::expr::Expr::StmtBlockWithScope(inner_stmts, _) = inner.as_ref() {
+                if let crate::expr::Expr::Block(block) = inner.as_ref()
+                    && let crate::expr::Expr::StmtBlockWithScope(inner_stmts, _) = block.as_ref()
+                {
                     assert_eq!(inner_stmts.len(), 3);
                 } else {
-                    panic!("Expected StmtBlockWithScope");
+                    panic!("Expected Block of StmtBlockWithScope");
                 }
             }
             _ => panic!("Expected Stmt"),
@@ -915,6 +1250,153 @@ fn test_parse_block_expression() {
     }
 }
 
+// ========== Compile-time Evaluation ==========
+
+#[test]
+fn test_parse_comp_function() {
+    let expr = parse_source("comp fn sq(x: i32) i32 { x * x };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Comp(decl) => {
+                assert!(matches!(
+                    decl.as_ref(),
+                    crate::expr::Expr::DeclareFunction(name, ..) if name == "sq"
+                ));
+            }
+            _ => panic!("Expected Comp"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_comp_expression() {
+    let expr = parse_source("#x = comp 2 * 3;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Declare(_, _, Some(value), _, _) => {
+                // comp applies to the whole expression after it
+                assert!(matches!(
+                    value.as_ref(),
+                    crate::expr::Expr::Comp(inner) if matches!(inner.as_ref(), crate::expr::Expr::Mult(..))
+                ));
+            }
+            _ => panic!("Expected Declare"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+// ========== Error Values ==========
+
+#[test]
+fn test_parse_err_and_propagate() {
+    let expr = parse_source("ret err \"bad\"; f()$?;");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => {
+            match statements[0].as_ref() {
+                crate::expr::Expr::Return(value, _) => assert!(matches!(
+                    value.as_ref(),
+                    crate::expr::Expr::Err(message, _)
+                        if matches!(message.as_ref(), crate::expr::Expr::Str(s) if s == "bad")
+                )),
+                _ => panic!("Expected Return"),
+            }
+            match statements[1].as_ref() {
+                crate::expr::Expr::Stmt(inner) => assert!(matches!(
+                    inner.as_ref(),
+                    crate::expr::Expr::Propagate(call, _)
+                        if matches!(call.as_ref(), crate::expr::Expr::CallFunc(name, ..) if name == "f")
+                )),
+                _ => panic!("Expected Stmt"),
+            }
+        }
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+// ========== Enums ==========
+
+#[test]
+fn test_parse_enum() {
+    let expr = parse_source("enm Shape { Empty, Rect(f64, f64) };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Enum(ty, variants, _) => {
+                assert_eq!(ty.name(), "Shape");
+                assert_eq!(variants[0], ("Empty".to_string(), vec![]));
+                assert_eq!(variants[1].1, vec![crate::type_env::Type::simple("f64"); 2]);
+            }
+            _ => panic!("Expected Enum"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_match() {
+    let expr = parse_source("match s { Rect(w, h) -> { w }, _ -> { 0.0 } };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::Match(value, arms, is_expr, _) => {
+                assert!(matches!(value.as_ref(), crate::expr::Expr::Variable(n, _) if n == "s"));
+                assert!(!is_expr);
+                assert_eq!(arms[0].0, "Rect");
+                assert_eq!(arms[0].1, vec!["w".to_string(), "h".to_string()]);
+                assert_eq!(arms[1].0, "_");
+            }
+            _ => panic!("Expected Match"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+// ========== Macros ==========
+
+#[test]
+fn test_parse_macro_declaration() {
+    let expr = parse_source("mac inc(x) { x = x + 1; };");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[0].as_ref() {
+            crate::expr::Expr::DeclareMacro(name, params, _, _) => {
+                assert_eq!(name, "inc");
+                assert_eq!(params, &vec!["x".to_string()]);
+            }
+            _ => panic!("Expected DeclareMacro"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
+#[test]
+fn test_parse_macro_call_is_expanded_hygienically() {
+    let expr = parse_source("mac m(a) { #x = a; x }; m(x);");
+    match expr {
+        crate::expr::Expr::StmtBlock(statements, _) => match statements[1].as_ref() {
+            crate::expr::Expr::Stmt(inner) => {
+                let crate::expr::Expr::Block(block) = inner.as_ref() else {
+                    panic!("Expected Block");
+                };
+                let crate::expr::Expr::StmtBlockWithScope(body, _) = block.as_ref() else {
+                    panic!("Expected StmtBlockWithScope");
+                };
+                // the local is renamed, but the argument still refers to the caller's x
+                let crate::expr::Expr::Discard(declare) = body[0].as_ref() else {
+                    panic!("Expected Discard");
+                };
+                let crate::expr::Expr::Declare(local, _, Some(value), _, _) = declare.as_ref()
+                else {
+                    panic!("Expected Declare");
+                };
+                assert!(local.starts_with('%'));
+                assert!(matches!(value.as_ref(), crate::expr::Expr::Variable(n, _) if n == "x"));
+            }
+            _ => panic!("Expected Stmt"),
+        },
+        _ => panic!("Expected StmtBlock"),
+    }
+}
+
 // ========== Use Statement ==========
 
 #[test]
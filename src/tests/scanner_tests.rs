@@ -42,6 +42,12 @@ fn test_keyword_for() {
     assert_eq!(tokenize_first("for"), TokenType::For);
 }
 
+#[test]
+fn test_keyword_break_continue() {
+    assert_eq!(tokenize_first("break"), TokenType::Break);
+    assert_eq!(tokenize_first("continue"), TokenType::Continue);
+}
+
 #[test]
 fn test_keyword_lam() {
     assert_eq!(tokenize_first("lam"), TokenType::Lam);
@@ -72,6 +78,16 @@ fn test_keyword_mac() {
     assert_eq!(tokenize_first("mac"), TokenType::Mac);
 }
 
+#[test]
+fn test_keyword_enm() {
+    assert_eq!(tokenize_first("enm"), TokenType::Enm);
+}
+
+#[test]
+fn test_keyword_match() {
+    assert_eq!(tokenize_first("match"), TokenType::Match);
+}
+
 // ========== Operators ==========
 
 #[test]
@@ -146,6 +162,31 @@ fn test_minus_right_arrow() {
     assert_eq!(tokenize_first("->"), TokenType::MinusRight);
 }
 
+#[test]
+fn test_compound_assignment_operators() {
+    assert_eq!(
+        tokenize_all("+= -= *= /= %="),
+        vec![
+            TokenType::PlusEqual,
+            TokenType::MinusEqual,
+            TokenType::StarEqual,
+            TokenType::SlashEqual,
+            TokenType::ModEqual,
+            TokenType::EOF
+        ]
+    );
+    // ** and -> still win over a following =
+    assert_eq!(
+        tokenize_all("**= ->"),
+        vec![
+            TokenType::StarStar,
+            TokenType::Equal,
+            TokenType::MinusRight,
+            TokenType::EOF
+        ]
+    );
+}
+
 #[test]
 fn test_bang_equal() {
     assert_eq!(tokenize_first("!="), TokenType::BangEqual);
@@ -191,6 +232,14 @@ fn test_float_literal() {
     assert_eq!(tokenize_literal("3.14"), Some("3.14".to_string()));
 }
 
+#[test]
+fn test_number_literal_with_suffix() {
+    assert_eq!(tokenize_first("10u64"), TokenType::Int);
+    assert_eq!(tokenize_literal("10u64"), Some("10u64".to_string()));
+    assert_eq!(tokenize_first("2.5f32"), TokenType::Float);
+    assert_eq!(tokenize_literal("2.5f32"), Some("2.5f32".to_string()));
+}
+
 #[test]
 fn test_string_literal() {
     assert_eq!(tokenize_first(r#""hello""#), TokenType::String);
@@ -5,6 +5,7 @@ use crate::parser::Parser;
 use crate::scanner::Scanner;
 use crate::transpiler::code_gen_context::CodeGenContext;
 use crate::transpiler::compiletime_env::CompileTimeEnv;
+use crate::transpiler::type_check::type_check;
 use std::collections::HashMap;
 
 /// Helper to transpile source to C code
@@ -18,6 +19,7 @@ fn transpile_to_c(source: &str) -> String {
     let mut cte = CompileTimeEnv::new(&mut ctx);
 
     expr.pre_transpile(&mut cte, &mut ctx, &mut HashMap::new(), "");
+    type_check(&expr, &mut cte);
     expr.to_c(&mut cte, &mut ctx);
     ctx.combine(&mut cte)
 }
@@ -92,6 +94,13 @@ fn test_transpile_arithmetic_expression() {
     assert!(c.contains("v_0s_0")); // variable declaration
 }
 
+#[test]
+fn test_transpile_literal_takes_other_operand_type() {
+    let c = transpile_to_c("fn main() { #x = 1 + 2.5; };");
+    assert!(c.contains("t_2CD v_1s_0Ct_2CDD(t_2CD n1, t_2CD n2)"));
+    assert!(c.contains("t_2CD v_") && c.contains("=v_1s_0Ct_2CDD(1,2.5);"));
+}
+
 // ========== Print Statements ==========
 
 #[test]
@@ -142,12 +151,36 @@ fn test_transpile_if_else() {
     // Just verify it compiles
 }
 
+#[test]
+fn test_transpile_if_expression_ternary() {
+    let c = transpile_to_c("fn main() { #a = ? 3 > 2 { 10 } ~? 1 > 2 { 20 } ~ { 30 }; }");
+    assert!(c.contains("=((v_11s_0Ct_0CDD(3,2)) ? (10) : (((v_11s_0Ct_0CDD(1,2)) ? (20) : (30))));"));
+}
+
+#[test]
+fn test_transpile_if_expression_with_statements() {
+    let source = "fn main() { #a = ? 3 > 2 { #b = 4; b * 2 } ~ { 0 }; }";
+    let c = transpile_to_c(source);
+    // branches that hold statements assign their last value to a temporary
+    assert!(c.contains("({\nt_0CD v_"));
+    assert!(c.contains(" = v_4s_0Ct_0CDD(v_"));
+    assert!(c.contains("} else {\nv_"));
+    assert!(c.contains(" = 0;\n}\n"));
+}
+
 #[test]
 fn test_transpile_while_loop() {
     let c = transpile_to_c("fn main(args: vec<<str>>) { £ x < 10 { ret; } }");
     assert!(c.contains("while"));
 }
 
+#[test]
+fn test_transpile_empty_if_and_while_bodies() {
+    let c = transpile_to_c("fn main() { #x = 0; ? x < 1 { }; £ x > 1 { }; }");
+    assert!(c.contains("if (v_2s_0Ct_0CDD(v_46s_2,1)){\n};"));
+    assert!(c.contains("while (v_11s_0Ct_0CDD(v_46s_2,1)){"));
+}
+
 // ========== Return Statements ==========
 
 #[test]
@@ -163,6 +196,33 @@ fn test_transpile_return_value() {
     assert!(c.contains("42"));
 }
 
+#[test]
+fn test_transpile_implicit_return() {
+    let c = transpile_to_c("fn sq(x: i32) i32 { x * x }");
+    assert!(c.contains("return v_4s_0Ct_0CDD(v_"));
+}
+
+#[test]
+fn test_transpile_implicit_return_infers_type() {
+    let c = transpile_to_c("fn half(x: f64) { #y = x / 2.0; y }");
+    assert!(c.contains("t_2CD v_46s_0CD(t_2CD v_43s_1);"));
+}
+
+#[test]
+fn test_transpile_discarded_value_is_not_returned() {
+    let c = transpile_to_c("fn f(x: i32) { x * x; }");
+    assert!(c.contains("t_1CD v_44s_0CD(t_0CD v_"));
+    assert!(!c.contains("return v_4s_0Ct_0CDD"));
+}
+
+#[test]
+fn test_transpile_block_expression() {
+    let c = transpile_to_c("fn main() { #b = { #t = 3; t + 1 }; }");
+    assert!(c.contains("=({\nt_0CD v_"));
+    assert!(c.contains("v_1s_0Ct_0CDD(v_"));
+    assert!(c.contains(";\n});"));
+}
+
 // ========== Variables and Assignment ==========
 
 #[test]
@@ -179,6 +239,88 @@ fn test_transpile_variable_usage() {
     assert!(c.contains("v_")); // C variable names exist
 }
 
+#[test]
+fn test_transpile_compound_assignment_variable() {
+    let c = transpile_to_c("fn main() { #@i = 1; i += 2; i %= 2; };");
+    assert!(c.contains("=v_1s_0Ct_0CDD(v_"), "Should reuse the i32 _add");
+    assert!(
+        c.contains("=v_22s_0Ct_0CDD(v_"),
+        "Should reuse the i32 _mod"
+    );
+}
+
+#[test]
+fn test_transpile_compound_assignment_str_appends() {
+    let c = transpile_to_c("fn main() { #@s = to_str(\"a\"); s += \"b\"; };");
+    assert!(c.contains("_append_t_6CD(&"));
+}
+
+#[test]
+fn test_transpile_compound_assignment_evaluates_target_once() {
+    let c =
+        transpile_to_c("fn idx() i32 { ret 0; }; fn main() { #@v = \\{1, 2}; v[idx()] *= 3; };");
+    // the element is reached through a pointer, so idx() is only called once
+    assert_eq!(c.matches(".data[v_").count(), 1);
+    assert!(c.contains("=v_4s_0Ct_0CDD((*v_"));
+}
+
+#[test]
+fn test_transpile_compound_assignment_member() {
+    let c = transpile_to_c("cls P { x: f64 }; fn main() { #@p: P; p.x = 1.0; p.x /= 2.0; };");
+    assert!(c.contains("= &v_"));
+    assert!(c.contains("=v_6s_0Ct_2CDD((*v_"));
+}
+
+#[test]
+fn test_transpile_comp_compound_assignment() {
+    let c = transpile_to_c("fn main() { #x = comp { #@n = 3; n *= 4; n -= 2; n }; };");
+    assert!(c.contains("=10;"));
+}
+
+// ========== Immutability ==========
+
+#[test]
+fn test_transpile_write_through_reference() {
+    let c = transpile_to_c("fn reset(n: &i32) { n = 0; }; fn main() { #@x = 1; reset(&x); };");
+    assert!(c.contains("(*v_"), "Should assign through the reference");
+    assert!(c.contains("(&v_"), "Should pass the address of x");
+}
+
+#[test]
+fn test_transpile_methods_that_change_their_value() {
+    // calling a method on an immutable value finds out if the method can change it
+    let source = "cls P { x: i32,
+            fn set() { `v.x = 5; }; fn get() i32 { `v.x }; fn pass() { g(`v); }; fn twice() { `v.set(); }; };
+        cls S : P { ovr fn get() i32 { `v.x = 1; 1 }; };
+        fn g(p: &P) { };
+        fn main() { #p: P; p.set(); p.get(); p.pass(); p.twice(); };";
+    let expr = Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse();
+    let mut ctx = CodeGenContext::new();
+    let mut cte = CompileTimeEnv::new(&mut ctx);
+    expr.pre_transpile(&mut cte, &mut ctx, &mut HashMap::new(), "");
+    expr.to_c(&mut cte, &mut ctx);
+
+    assert_eq!(cte.changes_receiver("P::set"), Some(true));
+    assert_eq!(cte.changes_receiver("P::pass"), Some(true));
+    assert_eq!(cte.changes_receiver("P::twice"), Some(true));
+    assert_eq!(cte.changes_receiver("P::get"), Some(false));
+    // a subclass can override a method with one that changes its value
+    assert_eq!(cte.changes_receiver("S::get"), Some(true));
+}
+
+#[test]
+fn test_transpile_comp_push_to_immutable() {
+    // the push is refused, so the vector keeps its length
+    let c = transpile_to_c("fn main() { #n = comp { #v = \\{1}; push(v, 2); len(v) }; };");
+    assert!(c.contains("=1;"));
+}
+
+#[test]
+fn test_transpile_comp_push_to_mutable() {
+    let c = transpile_to_c("fn main() { #n = comp { #@v = \\{1}; push(v, 2); len(v) }; };");
+    assert!(c.contains("=2;"));
+}
+
 // ========== Type Handling ==========
 
 #[test]
@@ -778,3 +920,875 @@ fn main() {
     assert!(c.contains("return"), "Should have return statement");
     assert!(c.contains("int main()"), "Should have main function wrapper");
 }
+
+// ========== For Loops ==========
+
+#[test]
+fn test_transpile_for_over_i32() {
+    let c = transpile_to_c("fn main() { for i : 10 { $i; }; }");
+    // counts from 0 up to (but not including) the looper, evaluated once
+    assert!(c.contains("for (t_0CD"), "Should emit a C for loop over i32");
+    assert!(c.contains("= 0;"), "Loop variable should start at 0");
+    assert!(c.contains("++)"), "Loop variable should be incremented");
+}
+
+#[test]
+fn test_transpile_for_over_i32_looper_named_like_the_loop_variable() {
+    let c = transpile_to_c("fn main() { #n = 3; for n : n { $n; }; }");
+    // the looper is the outer n, evaluated before the loop variable hides it
+    assert!(c.contains("t_0CD v_46s_2=3;"));
+    assert!(c.contains("= v_46s_2;\nfor (t_0CD v_48s_3 = 0; v_48s_3 < v_47s_3; v_48s_3++)"));
+}
+
+#[test]
+fn test_transpile_for_over_strlit() {
+    let c = transpile_to_c("fn main() { for c : \"abc\" { $c; }; }");
+    assert!(c.contains("!= '\\0'"), "Should walk the string up to '\\0'");
+    assert!(
+        c.contains("v_0s_0Ct_4CDD("),
+        "Loop variable should be typed as char"
+    );
+}
+
+#[test]
+fn test_transpile_for_loop_variable_scoped() {
+    // the loop variable must not leak into the enclosing scope
+    let c = transpile_to_c("fn main() { for i : 3 { $i; }; #i = 1.5; $i; }");
+    assert!(c.contains("v_0s_0Ct_2CDD("), "Outer 'i' should be the f64");
+}
+
+// ========== Break and Continue ==========
+
+#[test]
+fn test_transpile_break_and_continue() {
+    let c = transpile_to_c(
+        "fn main() { #@i = 0; £ i < 10 { i = i + 1; ? i == 2 { continue; }; ? i == 5 { break; }; }; }",
+    );
+    assert!(c.contains("goto l"), "Should jump with a goto");
+    assert!(
+        c.contains("_continue:;\n}"),
+        "continue should jump to the end of the body"
+    );
+    assert!(
+        c.contains("}\nl"),
+        "break should jump to right after the loop"
+    );
+}
+
+#[test]
+fn test_transpile_break_outer_loop() {
+    let c = transpile_to_c(
+        "fn main() { outer: for a : 3 { for b : 3 { ? b == 1 { break outer; }; $b; }; }; }",
+    );
+    let jump = c.find("goto ").unwrap();
+    let label = c[jump + 5..].split(';').next().unwrap();
+    // the label is after both loops
+    let at = c.find(&format!("\n{}:;", label)).unwrap();
+    assert!(c[..at].ends_with("}\n};\n}\n}\n}"));
+}
+
+#[test]
+fn test_transpile_unused_labels_are_not_emitted() {
+    let c = transpile_to_c("fn main() { for i : 3 { $i; }; }");
+    assert!(!c.contains("_break:;"));
+    assert!(!c.contains("_continue:;"));
+}
+
+#[test]
+fn test_transpile_break_outside_loop() {
+    let c =
+        transpile_to_c("fn main() { break; }; fn f() { for i : 3 { #g = lam () { break; }; }; }");
+    assert!(!c.contains("goto"), "A break outside of a loop is an error");
+}
+
+#[test]
+fn test_transpile_comp_break() {
+    let c = transpile_to_c(
+        "comp fn f() i32 { #@n = 0; £ `t { n = n + 1; ? n < 5 { continue; }; break; }; ret n; }; fn main() { #x = f(); }",
+    );
+    assert!(c.contains("=5;"));
+}
+
+// ========== Modulo ==========
+
+#[test]
+fn test_transpile_modulo_i32() {
+    let c = transpile_to_c("#x = 7 % 3;");
+    // _mod is dispatched on the type of its left operand
+    assert!(c.contains("v_22s_0Ct_0CDD(7,3)"), "Should call _mod for i32");
+    assert!(c.contains("return n1 % n2;"), "Should define i32 modulo");
+}
+
+#[test]
+fn test_transpile_modulo_f64() {
+    let c = transpile_to_c("#x = 7.5 % 2.0;");
+    assert!(c.contains("v_22s_0Ct_2CDD("), "Should call _mod for f64");
+    assert!(c.contains("fmod(n1, n2)"), "f64 modulo should use fmod");
+}
+
+// ========== Lambdas ==========
+
+#[test]
+fn test_transpile_lambda_is_lifted() {
+    let c = transpile_to_c("fn main() { #f = lam (x: i32) i32 { ret x * 2; }; $f(21); }");
+    // the variable holds a function value of type func<<i32, i32>>, which has no environment
+    assert!(c.contains("typedef struct { t_0CD(*fn)(void*,t_0CD); void* env; } t_5Ct_0_t_0D;"));
+    assert!(c.contains("=((t_5Ct_0_t_0D){ v_"));
+    assert!(c.contains("t_5Ct_0_t_0D v_"), "Lambda should be stored as func value");
+    // the body is lifted into its own C function, declared up front
+    let lifted = c
+        .lines()
+        .find(|line| line.contains("(void* env,") && line.ends_with(";"))
+        .expect("Lifted lambda should be declared");
+    assert!(lifted.contains("CD(void* env, t_0CD v_"));
+}
+
+#[test]
+fn test_transpile_lambda_return_type_inferred() {
+    let c = transpile_to_c("fn main() { #f = lam (x: f64) { ret x * 2.0; }; }");
+    assert!(c.contains("t_5Ct_2_t_2D v_"), "Should infer func<<f64, f64>>");
+}
+
+#[test]
+fn test_transpile_lambda_captures_environment() {
+    let source = r#"
+fn make(k: i32) func<<i32, i32>> { ret lam (x: i32) i32 { ret x + k; }; };
+fn main() { #add_10 = make(10); #add_1 = make(1); $add_1(1); $add_10(1); }
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("_env {"), "Should generate an environment struct");
+    // each evaluation of the lambda gets an environment of its own
+    assert!(!c.contains("static struct v_"));
+    assert!(c.contains("return ({ struct v_") && c.contains("_env* env = malloc(sizeof(struct"));
+    assert!(c.contains("env->v_48s_1 = v_48s_1; (t_5Ct_0_t_0D){ v_"));
+    // which the lifted function reads the captured values from
+    assert!(c.contains("_env*)env)->v_48s_1;"));
+    // and is passed along when the value is called
+    assert!(c.contains("v_0s_0Ct_0CDD(v_55s_2.fn(v_55s_2.env,1));"));
+}
+
+#[test]
+fn test_transpile_function_as_value() {
+    let source = r#"
+fn apply(f: func<<i32, i32>>, x: i32) i32 { ret f(x); };
+fn inc(x: i32) i32 { ret x + 1; };
+fn main() { $apply(inc, 5); };
+"#;
+    let c = transpile_to_c(source);
+    // parameters holding functions are called with their environment
+    assert!(c.contains("return v_49s_1.fn(v_49s_1.env,v_50s_1);"));
+    // a function is passed as a wrapper taking an environment
+    assert!(c.contains("t_0CD v_47s_0CD_value(void* env, t_0CD a0) { // inc as a value"));
+    assert!(c.contains("    return v_47s_0CD(a0);"));
+    assert!(c.contains("((t_5Ct_0_t_0D){ v_47s_0CD_value, NULL }),5)"));
+}
+
+// ========== Vectors ==========
+
+#[test]
+fn test_transpile_vector_literal() {
+    let c = transpile_to_c("fn main() { #v = \\{1, 2, 3}; };");
+    assert!(
+        c.contains("typedef struct { t_0CD* data; t_0CD len; t_0CD cap; } t_8Ct_0D;"),
+        "Should generate a struct for vec<<i32>>"
+    );
+    assert!(c.contains("t_8Ct_0D_from((t_0CD[]){1,2,3}, 3)"));
+}
+
+#[test]
+fn test_transpile_vector_builtins() {
+    let source = r#"
+fn main() {
+    #@v: vec<<f64>> = \{};
+    push(v, 1.5);
+    #x = pop(v);
+    $len(v);
+};
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("={0}"), "Empty literal should be zero-initialised");
+    // push takes a &vec<<T>>, so the vector's address is passed
+    assert!(c.contains("v_23s_0Ct_8Ct_2DCDD(&v_"), "Should call push for vec<<f64>>");
+    // pop returns the element type
+    assert!(c.contains("t_2CD v_") && c.contains("=v_24s_0Ct_8Ct_2DCDD(&v_"));
+    assert!(c.contains("v_25s_0Ct_8Ct_2DCDD(v_"), "Should call len for vec<<f64>>");
+}
+
+#[test]
+fn test_transpile_vector_indexing() {
+    let c = transpile_to_c("fn main() { #@v = \\{1, 2}; v[0] = 3; #x = v[1] + 1; };");
+    assert!(c.contains(".data[0]=3"), "Should assign through the data pointer");
+    assert!(c.contains("t_0CD v_"), "Indexing should have the element type");
+}
+
+#[test]
+fn test_transpile_len_strlit() {
+    let c = transpile_to_c("#x = len(\"abc\");");
+    assert!(c.contains("v_25s_0Ct_6CDD("), "Should call len for strlit");
+}
+
+// ========== Arrays ==========
+
+#[test]
+fn test_transpile_array_literal() {
+    let c = transpile_to_c("fn main() { #a = [1.0, 2.0]; $len(a); };");
+    assert!(
+        c.contains("typedef struct { t_2CD* data; t_0CD len; } t_1Ct_2D;"),
+        "Should generate a length-carrying struct for [f64]"
+    );
+    assert!(
+        c.contains("((t_1Ct_2D){ (t_2CD[]){1.0,2.0}, 2 })"),
+        "An array that stays in its block should keep its elements on the stack"
+    );
+    assert!(c.contains("v_25s_0Ct_1Ct_2DCDD(v_"), "Should call len for [f64]");
+}
+
+#[test]
+fn test_transpile_array_literal_that_outlives_its_block() {
+    let c = transpile_to_c("fn make() [i32] { #a = [1, 2]; a }; fn main() { $make()[0]; };");
+    assert!(
+        c.contains("t_1Ct_0D_from((t_0CD[]){1,2}, 2)"),
+        "A returned array should copy its elements to the heap"
+    );
+}
+
+#[test]
+fn test_transpile_array_indexing_typed_by_element() {
+    let c = transpile_to_c("fn main() { #a = [1.5, 2.5]; #x = a[0]; };");
+    assert!(c.contains(".data[0]"), "Should index through the data pointer");
+    assert!(c.contains("t_2CD v_"), "Element should be typed as f64");
+}
+
+#[test]
+fn test_transpile_array_bounds_check_in_debug() {
+    let c = transpile_to_c("#DEBUG = `t; fn main() { #a = [1, 2]; #x = a[1]; };");
+    assert!(c.contains("t_1Ct_0D_at("), "Should index through the checked helper");
+    assert!(c.contains("out of bounds"), "Checked helper should report out of bounds");
+}
+
+// ========== Strings ==========
+
+#[test]
+fn test_transpile_str_type() {
+    let c = transpile_to_c("fn main() { #s = to_str(\"abc\"); $len(s); };");
+    assert!(
+        c.contains("typedef struct { t_4CD* data; t_0CD len; t_0CD cap; } t_9CD;"),
+        "Should generate the str struct"
+    );
+    assert!(c.contains("t_9CD v_"), "to_str should return a str");
+    assert!(c.contains("v_25s_0Ct_9CDD(v_"), "Should call len for str");
+}
+
+#[test]
+fn test_transpile_str_concatenation() {
+    let c = transpile_to_c("fn main() { #s = to_str(\"a\"); #t = s + \"b\"; #u = s + 'c'; };");
+    assert!(c.contains("v_1s_0Ct_9Ct_6CDD("), "Should call add for str+strlit");
+    assert!(c.contains("v_1s_0Ct_9Ct_4CDD("), "Should call add for str+char");
+}
+
+#[test]
+fn test_transpile_str_appends_in_place() {
+    let c = transpile_to_c("fn main() { #@s = to_str(\"a\"); s = s + \"b\"; s[0] = 'c'; };");
+    assert!(c.contains("t_9CD_append_t_6CD(&v_"), "s = s + x should append in place");
+    assert!(c.contains(".data[0]='c'"), "Should assign through the data pointer");
+}
+
+#[test]
+fn test_transpile_shared_str_is_not_appended_in_place() {
+    let in_place = |source: &str| transpile_to_c(source).contains("t_9CD_append_t_6CD(&v_");
+    // growing s would free the chars t still points to
+    assert!(!in_place(
+        "fn main() { #@s = to_str(\"a\"); #t = s; s = s + \"b\"; $t; };"
+    ));
+    assert!(!in_place(
+        "fn main() { #u = to_str(\"a\"); #@s = to_str(\"\"); s = u; s = s + \"b\"; };"
+    ));
+    // the chars of a parameter belong to the caller
+    assert!(!in_place("fn f(@s: str) { s = s + \"b\"; };"));
+    // a copy made after the last append is fine
+    assert!(in_place(
+        "fn main() { #@s = to_str(\"a\"); £ len(s) < 3 { s = s + \"b\"; }; #t = s; };"
+    ));
+}
+
+#[test]
+fn test_transpile_str_drop() {
+    let c = transpile_to_c("fn main() { #s = to_str(\"a\"); drop(s); };");
+    assert!(c.contains("t_1CD v_30s_0CD(t_9CD s) { // drop\n    free(s.data);"));
+    assert!(c.contains("v_30s_0CD(v_"));
+}
+
+
+// ========== Generic Functions ==========
+
+#[test]
+fn test_transpile_generic_function_instance_per_type() {
+    let source = r#"
+fn <<T>> id(x: T) T { ret x; };
+fn main() { $id(3); $id(2.5); $id<<i32>>(4); };
+"#;
+    let c = transpile_to_c(source);
+    // one instance per binding, named with the generic types
+    assert!(c.contains("t_0CD v_43s_0Ct_0CDD(t_0CD v_"), "Should instantiate id for i32");
+    assert!(c.contains("t_2CD v_43s_0Ct_2CDD(t_2CD v_"), "Should instantiate id for f64");
+    assert_eq!(
+        c.matches("t_0CD v_43s_0Ct_0CDD(t_0CD v_").count(),
+        2,
+        "An instance should only be declared and defined once"
+    );
+    assert!(!c.contains("v_43s_0CD("), "The generic function itself is not emitted");
+}
+
+#[test]
+fn test_transpile_generic_function_inferred_from_nested_type() {
+    let source = r#"
+fn <<T>> first(v: vec<<T>>) { ret v[0]; };
+fn main() { #v = \{1.5, 2.5}; #x = first(v); };
+"#;
+    let c = transpile_to_c(source);
+    // T is bound to f64 through vec<<T>>, which also gives the inferred return type
+    assert!(c.contains("t_2CD v_43s_0Ct_2CDD(t_8Ct_2D v_"));
+    assert!(c.contains("t_2CD v_") && c.contains("=v_43s_0Ct_2CDD(v_"));
+}
+
+#[test]
+fn test_transpile_generic_function_typed_locals() {
+    let source = r#"
+fn <<A, B>> second(a: A, b: B) B { #y: B = b; ret y; };
+fn main() { $second(1, 'c'); };
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("t_4CD v_43s_0Ct_0Ct_4CDD(t_0CD v_"));
+    assert!(c.contains("t_4CD v_"), "Locals typed with a generic should be substituted");
+}
+
+#[test]
+fn test_transpile_generic_function_only_sees_globals() {
+    let source = r#"
+#scale = 10;
+fn <<T>> f(x: T) T { ret y; };
+fn <<T>> g(x: T) i32 { ret scale; };
+fn main() { #y = 1; $$f(2); $$g(3); };
+"#;
+    let c = transpile_to_c(source);
+    // y is a local of main, which the instance cannot see however it is called
+    let before_y = c.split("s_2=1;").next().unwrap();
+    let y = before_y.rsplit(' ').next().unwrap();
+    assert!(y.starts_with("v_"));
+    assert!(!c.contains(&format!("return {}s_2;", y)));
+    assert!(c.contains("return v_43s_0;"), "Globals are still visible");
+}
+
+
+// ========== Classes ==========
+
+#[test]
+fn test_transpile_class_members_are_distinct() {
+    let c = transpile_to_c("cls P { a: i32, b: f64 }; fn main() { #@p: P; p.b = 1.5; };");
+    assert!(c.contains("t_0CD m_0;\nt_2CD m_1;"), "Each member should get its own name");
+    assert!(c.contains(".m_1=1.5"));
+}
+
+#[test]
+fn test_transpile_class_member_ids_count_up() {
+    // every member used to be declared as m_0, so all of them shared one C field
+    let c = transpile_to_c(
+        "cls P { a: i32, b: f64, c: bool }; fn main() { #@p: P; p.a = 1; p.c = `t; };",
+    );
+    assert!(c.contains("t_0CD m_0;\nt_2CD m_1;\nt_3CD m_2;\n};"));
+    assert!(c.contains(".m_0=1"));
+    assert!(c.contains(".m_2=true"));
+}
+
+#[test]
+fn test_transpile_generic_class_instances() {
+    let source = r#"
+cls Pair<<A, B>> { first: A, second: B };
+fn main() {
+    #@p: Pair<<i32, f64>>;
+    #@q: Pair<<char, i32>>;
+    p.second = 2.5;
+};
+"#;
+    let c = transpile_to_c(source);
+    // one struct per concrete instance, with the member types substituted
+    assert!(c.contains("typedef struct t_36Ct_0_t_2D t_36Ct_0_t_2D;"));
+    assert!(c.contains("struct t_36Ct_0_t_2D {\nt_0CD m_0;\nt_2CD m_1;\n};"));
+    assert!(c.contains("struct t_36Ct_4_t_0D {\nt_4CD m_0;\nt_0CD m_1;\n};"));
+    assert!(c.contains(".m_1=2.5"));
+}
+
+#[test]
+fn test_transpile_class_struct_before_generic_instance_holding_it() {
+    let source = r#"
+cls Pair<<A, B>> { first: A, second: B };
+cls Foo { n: i32 };
+fn show(p: Pair<<i32, Foo>>) { $7; };
+fn main() { #@p: Pair<<i32, Foo>>; show(p); };
+"#;
+    let c = transpile_to_c(source);
+    // the instance is first needed by the parameter, before the body of main is emitted
+    let foo = c
+        .find("struct t_37CD {")
+        .expect("Should emit the struct of Foo");
+    let pair = c
+        .find("struct t_36Ct_0_t_37D {")
+        .expect("Should emit the instance holding Foo");
+    assert!(
+        foo < pair,
+        "Foo should be complete before the instance holding it"
+    );
+}
+
+#[test]
+fn test_transpile_method_call() {
+    let source = r#"
+cls Counter {
+    count: i32,
+    fn bump() { `v.count = `v.count + 1; };
+};
+fn main() {
+    #@c: Counter;
+    c.bump();
+};
+"#;
+    let c = transpile_to_c(source);
+    // methods take a reference to their class as `this`, and access members through it
+    assert!(c.contains("t_1CD v_44s_0CD(t_7Ct_36D v_43s_1);"));
+    assert!(c.contains(" v_47s_1 ->m_0=v_1s_0Ct_0CDD( v_47s_1 ->m_0,1)"));
+    assert!(c.contains("v_44s_0CD(&v_"));
+}
+
+#[test]
+fn test_transpile_static_method_call() {
+    let source = r#"
+cls Counter {
+    count: i32,
+    stc fn zero() Counter { #@c: Counter; c.count = 0; ret c; };
+};
+fn main() {
+    #c = Counter::zero();
+};
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("t_36CD v_44s_0CD();"));
+    assert!(c.contains("=v_44s_0CD()"));
+}
+
+#[test]
+fn test_transpile_class_inheritance() {
+    let source = r#"
+cls Entity { x: i32 };
+cls Player : Entity { lives: i32 };
+fn show(e: &Entity) { $e.x; };
+fn main() {
+    #@p: Player;
+    p.lives = 3;
+    show(&p);
+};
+"#;
+    let c = transpile_to_c(source);
+    // the child starts with the members of its parent, and is passed as one
+    assert!(c.contains("struct t_37CD {\nt_0CD m_0;\nt_0CD m_1;\n};"));
+    assert!(c.contains(".m_1=3"));
+    assert!(c.contains("((t_7Ct_36D)&v_"));
+}
+
+#[test]
+fn test_transpile_override_uses_vtable() {
+    let source = r#"
+cls Entity { ovr fn name() strlit { ret "entity"; }; };
+cls Player : Entity { ovr fn name() strlit { ret "player"; }; };
+fn show(e: &Entity) { $e.name(); };
+fn main() {
+    #@p: Player;
+    show(&p);
+};
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("struct t_39CD {\nobs_vslot* vt;\n"));
+    assert!(c.contains("obs_vslot t_39CD_vt[] = {(obs_vslot)v_"));
+    // values start out pointing to the vtable of their class, and calls go through it
+    assert!(c.contains("t_39CD v_"));
+    assert!(c.contains("={.vt=t_39CD_vt};"));
+    // the receiver is evaluated once, and the slot is cast to a pointer to the method
+    let call = c
+        .lines()
+        .find(|line| line.contains("->vt[0]"))
+        .expect("Should call through the vtable");
+    let receiver = call.split("({ t_7Ct_36D ").nth(1).unwrap();
+    let receiver = receiver.split(' ').next().unwrap();
+    assert!(call.contains(&format!(
+        "((t_6CD (*)(t_7Ct_36D))({r}->vt[0]))({r}); }})",
+        r = receiver
+    )));
+}
+
+#[test]
+fn test_transpile_override_in_member_uses_vtable() {
+    let source = r#"
+cls Entity { ovr fn name() strlit { ret "entity"; }; };
+cls Holder { n: i32, e: Entity, };
+cls Outer { h: Holder, };
+#g: Holder;
+fn main() {
+    #o: Outer;
+    #h = o.h;
+    #e = h.e;
+    $$ e.name();
+};
+"#;
+    let c = transpile_to_c(source);
+    // members of a class with `ovr` methods point to its vtable too, however deep they are
+    assert!(c.contains("t_39CD v_45s_0={.m_1={.vt=t_36CD_vt}};"));
+    assert!(c.contains("={.m_0={.m_1={.vt=t_36CD_vt}}};"));
+}
+
+// ========== Globals ==========
+
+#[test]
+fn test_transpile_constant_global() {
+    let c = transpile_to_c("#PI = 3.14; fn main() { $PI; };");
+    assert!(c.contains("t_2CD v_43s_0=3.14;"));
+    assert!(c.contains("v_0s_0Ct_2CDD(v_43s_0)"));
+    assert!(!c.contains("obs_init_globals"));
+}
+
+#[test]
+fn test_transpile_global_initialised_before_main() {
+    let c = transpile_to_c("#PI = 3.14; #TAU = 2.0 * PI; fn main() { $TAU; };");
+    assert!(c.contains("t_2CD v_44s_0;\n"));
+    assert!(c.contains("t_1CD obs_init_globals() {\nv_44s_0=v_4s_0Ct_2CDD(2.0,v_43s_0);\n}"));
+    assert!(c.contains("int main() {\n    obs_init_globals();\n    v_"));
+}
+
+#[test]
+fn test_transpile_mutable_global() {
+    let c = transpile_to_c("#@count = 0; fn bump() { count = count + 1; }; fn main() { bump(); };");
+    assert!(c.contains("t_0CD v_43s_0=0;"));
+    assert!(c.contains("v_43s_0=v_1s_0Ct_0CDD(v_43s_0,1)"));
+}
+
+#[test]
+fn test_transpile_global_function_value() {
+    let c = transpile_to_c("#twice = lam (x: i32) i32 { x * 2 }; fn main() { $twice(3); };");
+    // a global holding a function is called through the variable, not as a function
+    assert!(c.contains("v_0s_0Ct_0CDD(v_43s_0.fn(v_43s_0.env,3))"));
+}
+
+// ========== Compile-time Evaluation ==========
+
+#[test]
+fn test_transpile_comp_global() {
+    let c = transpile_to_c("#PI = 3.5; #TAU = comp 2.0 * PI; fn main() { $TAU; };");
+    assert!(c.contains("t_2CD v_44s_0=7.0;"));
+    assert!(!c.contains("obs_init_globals"));
+}
+
+#[test]
+fn test_transpile_comp_function_call_is_inlined() {
+    let source = r#"
+comp fn fib(n: i32) i32 { ? n < 2 { n } ~ { fib(n - 1) + fib(n - 2) } };
+fn main() { $fib(10); };
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("v_0s_0Ct_0CDD(55)"));
+    // comp fns are never emitted
+    assert!(!c.contains("v_43s_0CD"));
+}
+
+#[test]
+fn test_transpile_comp_lookup_table() {
+    let source = r#"
+comp fn squares(n: i32) vec<<i32>> {
+    #@out: vec<<i32>> = \{};
+    for i : n { push(out, i * i); };
+    out
+};
+#SQUARES = comp squares(4);
+fn main() { $SQUARES[3]; };
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("=t_8Ct_0D_from((t_0CD[]){0,1,4,9}, 4);"));
+}
+
+#[test]
+fn test_transpile_comp_expression() {
+    let c = transpile_to_c("fn main() { #x = comp { #@a = 1; for i : 4 { a = a * 2; }; a }; };");
+    assert!(c.contains("s_2=16;"));
+}
+
+// ========== Macros ==========
+
+#[test]
+fn test_transpile_macro_expands_to_block() {
+    let source = "mac twice(x) { x * 2 }; fn main() { #a = twice(4); };";
+    let c = transpile_to_c(source);
+    assert!(c.contains("=({\nv_4s_0Ct_0CDD(4,2);\n});"));
+}
+
+#[test]
+fn test_transpile_macro_locals_do_not_capture_arguments() {
+    let source = r#"
+mac swap(a, b) { #tmp = a; a = b; b = tmp; };
+fn main() { #@tmp = 1; #@x = 2; swap(tmp, x); };
+"#;
+    let c = transpile_to_c(source);
+    // the macro's tmp is a different variable to the caller's
+    assert!(c.contains("t_0CD v_48s_2=1;"));
+    assert!(c.contains("t_0CD v_50s_3=v_48s_2;"));
+    assert!(c.contains("v_49s_2=v_50s_3;"));
+}
+
+// ========== Error Values ==========
+
+#[test]
+fn test_transpile_res_type() {
+    let c = transpile_to_c("fn half(n: i32) res<<i32>> { n / 2 };");
+    assert!(c.contains("typedef struct { t_3CD ok; t_0CD value; t_6CD err; } t_10Ct_0D;"));
+    // a plain value returned from the function is its success
+    assert!(c.contains("return (t_10Ct_0D){ .ok = true, .value = v_6s_0Ct_0CDD(v_45s_1,2) };"));
+}
+
+#[test]
+fn test_transpile_err() {
+    let c = transpile_to_c("fn f(n: i32) res<<i32>> { ? n < 0 { ret err \"negative\"; }; n };");
+    assert!(c.contains("return (t_10Ct_0D){ .ok = false, .err = \"negative\" };"));
+}
+
+#[test]
+fn test_transpile_propagate() {
+    let source = r#"
+fn half(n: i32) res<<i32>> { n / 2 };
+fn quarter(n: i32) res<<i32>> { half(half(n)$?) };
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains(
+        "if (!v_49s_2.ok) return (t_10Ct_0D){ .ok = false, .err = v_49s_2.err }; v_49s_2.value; })"
+    ));
+}
+
+#[test]
+fn test_transpile_empty_res_succeeds_at_end() {
+    let c = transpile_to_c("fn check(n: i32) res<<[]>> { ? n > 9 { ret err \"too big\"; }; };");
+    assert!(c.contains("typedef struct { t_3CD ok; t_6CD err; } t_10Ct_1D;"));
+    assert!(c.contains("return (t_10Ct_1D){ .ok = true };"));
+}
+
+#[test]
+fn test_transpile_fallible_input() {
+    let c = transpile_to_c("fn main() { #n = unwrap(try_intput()); };");
+    assert!(c.contains("t_10Ct_0D v_34s_0CD() { // try_intput"));
+    assert!(c.contains("t_0CD v_46s_2=v_32s_0Ct_10Ct_0DCDD(v_34s_0CD());"));
+}
+
+// ========== Enums ==========
+
+#[test]
+fn test_transpile_enum_is_tagged_union() {
+    let c = transpile_to_c("enm State { Idle, Running(i32, f64) }; fn main() { #s = State::Idle; };");
+    assert!(c.contains("t_0CD tag;\nunion {\nstruct { t_0CD m_0; t_2CD m_1; } p1;\n} data;\n};"));
+    // variants without a payload are constants, the others have a constructor
+    assert!(c.contains(" = { .tag = 0 };"));
+    assert!(c.contains("e.data.p1.m_0 = m_0;\ne.data.p1.m_1 = m_1;\nreturn e;"));
+}
+
+#[test]
+fn test_transpile_match_statement() {
+    let source = r#"
+enm State { Idle, Running(i32) };
+fn main() {
+    #s = State::Running(1);
+    match s {
+        Running(n) -> { $ n; },
+        _ -> {},
+    };
+};
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains(".tag) {\ncase 1: {\n"));
+    assert!(c.contains(".data.p1.m_0;\n{\nv_0s_0Ct_0CDD("));
+    assert!(c.contains("default: {\n"));
+}
+
+#[test]
+fn test_transpile_match_expression() {
+    let source = r#"
+enm State { Idle, Running(i32) };
+fn count(s: State) i32 {
+    match s {
+        Idle -> { 0 },
+        Running(n) -> { n },
+    }
+};
+"#;
+    let c = transpile_to_c(source);
+    assert!(c.contains("return ({\nt_0CD "));
+    assert!(c.contains("case 0: {\n{\nv_"));
+    assert!(c.contains(" = 0;\n}\nbreak;\n}\ncase 1: {"));
+}
+
+// ========== Number Types ==========
+
+#[test]
+fn test_transpile_sized_literal() {
+    let c = transpile_to_c("fn main() { #x: u64 = 18446744073709551615u64; };");
+    assert!(c.contains("typedef uint64_t t_17CD; // u64"));
+    assert!(c.contains("((t_17CD)18446744073709551615ULL)"));
+}
+
+#[test]
+fn test_transpile_sized_operators_are_emitted() {
+    let c = transpile_to_c("fn main() { #x: i64 = 2i64; $ x * 3i64; };");
+    assert!(c.contains("t_13CD v_4s_0Ct_13CDD(t_13CD n1, t_13CD n2) { // mult i64"));
+    assert!(c.contains("printf(\"%lld\", (long long)n);"));
+    // types that are not used get no operators
+    assert!(!c.contains("// mult u8"));
+}
+
+#[test]
+fn test_transpile_cast() {
+    let c = transpile_to_c("fn main() { #x = 7; #y = x -> f32; };");
+    assert!(c.contains("((t_18CD)(v_"));
+}
+
+#[test]
+fn test_transpile_comp_wraps_sized_integers() {
+    let c = transpile_to_c("fn main() { #x = comp (250u8 + 10u8); };");
+    assert!(c.contains("((t_14CD)4)"));
+}
+
+// ========== Negation ==========
+
+#[test]
+fn test_transpile_negative_literal() {
+    let c = transpile_to_c("fn main() { #x = -5; #y = 1.5 - -2.5; };");
+    assert!(c.contains("=(-5);"));
+    assert!(c.contains("(-2.5)"));
+}
+
+#[test]
+fn test_transpile_negate_argument() {
+    let c = transpile_to_c("fn f(n: i32) i32 { ret n; }; fn main() { #y = 3; #z = f(-y); };");
+    assert!(c.contains("((-v_"));
+    assert!(!c.contains("v_3s_0Ct_0CDD(,"));
+}
+
+#[test]
+fn test_transpile_negate_sized() {
+    let c = transpile_to_c("fn main() { #x = -9223372036854775808i64; #y = -x; };");
+    assert!(c.contains("((t_13CD)(-9223372036854775807LL - 1))"));
+}
+
+#[test]
+fn test_transpile_comp_negate() {
+    let c = transpile_to_c("fn main() { #x = comp (-(3 * 2)); };");
+    assert!(c.contains("=(-6);"));
+}
+
+// ========== Logical Operators ==========
+
+#[test]
+fn test_transpile_logical_operators_short_circuit() {
+    let c = transpile_to_c("fn main() { #a = `t; #b = a & `f | a; };");
+    assert!(c.contains(" && false) || v_"));
+    assert!(!c.contains("v_16s_0CD(v_"));
+}
+
+#[test]
+fn test_transpile_comp_and_short_circuits() {
+    // the division by zero on the right is never evaluated
+    let c = transpile_to_c("fn main() { #b = comp (`f & (1 / 0 == 0)); };");
+    assert!(c.contains("=false;"));
+}
+
+// ========== Bitwise Operators ==========
+
+#[test]
+fn test_transpile_bitwise_on_integers() {
+    let c = transpile_to_c("fn main() { #x = 6; #y = x & 3 | 8; #b = `t & `f; };");
+    assert!(c.contains("v_38s_0Ct_0CDD(v_37s_0Ct_0CDD(v_"));
+    // on bools & stays logical
+    assert!(c.contains("(true && false)"));
+}
+
+#[test]
+fn test_transpile_shifts_and_not() {
+    let c = transpile_to_c("fn main() { #x = 1 << 4; #y = x >> 1; #z = !x; };");
+    assert!(c.contains("v_40s_0Ct_0CDD(1,4)"));
+    assert!(c.contains("v_41s_0Ct_0CDD(v_"));
+    assert!(c.contains("v_42s_0Ct_0CDD(v_"));
+}
+
+#[test]
+fn test_transpile_bitwise_calls_the_defined_instances() {
+    let c = transpile_to_c(
+        "fn main() { #x = 12; #y = 10; #a = x & y; #b = x | y; #c = x ^ y; #d = x << 2; #e = x >> 1; #f = !x; #g = `t ^ `t; };",
+    );
+    for operator in [
+        "bit_and i32",
+        "bit_or i32",
+        "xor i32",
+        "shl i32",
+        "shr i32",
+        "bit_not i32",
+        "xor bool",
+    ] {
+        let definition = c
+            .lines()
+            .find(|line| line.ends_with(&format!("{{ // {}", operator)))
+            .unwrap_or_else(|| panic!("Should define {}", operator));
+        let name = definition.split([' ', '(']).nth(1).unwrap();
+        assert!(
+            c.contains(&format!("={}(", name)),
+            "Should call {} as it is defined, {}",
+            operator,
+            name
+        );
+    }
+}
+
+#[test]
+fn test_transpile_bitwise_literal_takes_other_operand_type() {
+    let c = transpile_to_c("fn main() { #x = 1i64 & 2; #y = 3 | 4i64; #z: u8 = 1; #w = z << 3; };");
+    assert!(c.contains("=v_37s_0Ct_13CDD(((t_13CD)1LL),2);"));
+    assert!(c.contains("=v_38s_0Ct_13CDD(3,((t_13CD)4LL));"));
+    assert!(c.contains("=v_40s_0Ct_14CDD(v_"));
+}
+
+#[test]
+fn test_transpile_bitwise_on_sized_integers() {
+    let c = transpile_to_c("fn main() { #x: u64 = 1u64 << 40u64; };");
+    assert!(c.contains("t_17CD v_40s_0Ct_17CDD(t_17CD n1, t_17CD n2) { // shl u64"));
+    assert!(c.contains("t_17CD v_42s_0Ct_17CDD(t_17CD n) { // bit_not u64"));
+}
+
+#[test]
+fn test_transpile_comp_loop_that_never_ends() {
+    // the loop is stopped once it runs out of fuel, and reported at the loop
+    crate::take_test_errors();
+    transpile_to_c("fn main() { #n = comp { #@i = 0; £ `t { i = i + 1; }; i }; };");
+    let errors = crate::take_test_errors();
+    assert!(errors.iter().any(|(span, message)| span.line == 1
+        && message.starts_with("Evaluating this at compile time takes too long")));
+}
+
+#[test]
+fn test_transpile_comp_fn_recursing_forever() {
+    let source = r#"
+comp fn down(n: i32) i32 { down(n + 1) + 1 };
+fn main() { $down(0); };
+"#;
+    // the compiler runs on the main thread, whose stack is larger than a test thread's
+    let errors = std::thread::Builder::new()
+        .stack_size(8 << 20)
+        .spawn(|| {
+            transpile_to_c(source);
+            crate::take_test_errors()
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+    assert!(errors.iter().any(|(span, message)| span.line == 2
+        && message.starts_with("Calling 'down' at compile time goes more than")));
+}
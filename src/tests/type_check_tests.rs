@@ -0,0 +1,322 @@
+//! Type checker tests
+//! Tests the type mismatches reported before any C is emitted
+
+use crate::expr::Expr;
+use crate::parser::Parser;
+use crate::scanner::Scanner;
+use crate::transpiler::code_gen_context::CodeGenContext;
+use crate::transpiler::compiletime_env::CompileTimeEnv;
+use crate::transpiler::type_check::type_check;
+use crate::type_env::Type;
+use std::collections::HashMap;
+
+/// Helper to type check source, returning (line, message) for every mismatch
+fn type_errors(source: &str) -> Vec<(usize, String)> {
+    let mut scanner = Scanner::new(source.to_string());
+    let tokens = scanner.scan_tokens();
+    let mut parser = Parser::new(tokens);
+    let expr = parser.parse();
+
+    let mut ctx = CodeGenContext::new();
+    let mut cte = CompileTimeEnv::new(&mut ctx);
+
+    expr.pre_transpile(&mut cte, &mut ctx, &mut HashMap::new(), "");
+    type_check(&expr, &mut cte)
+        .into_iter()
+        .map(|e| (e.span.line, e.message))
+        .collect()
+}
+
+fn assert_mismatch(source: &str, expected: &str) {
+    let errors = type_errors(source);
+    assert!(
+        errors.iter().any(|(_, message)| message == expected),
+        "Expected '{}', found {:?}",
+        expected,
+        errors
+    );
+}
+
+// ========== Well Typed Programs ==========
+
+#[test]
+fn test_check_well_typed_program() {
+    let source = "fn add(a: i32, b: i32) i32 { ret a + b; };
+        fn main() { #@x = add(1, 2); x = 3; ? x > 2 { $$ x; }; £ x < 5 { x += 1; }; };";
+    assert!(type_errors(source).is_empty());
+}
+
+#[test]
+fn test_check_literals_take_the_expected_type() {
+    let source = "fn half(x: f32) f32 { ret x / 2; };
+        fn main() { #small: u8 = 200; #big: i64 = -5; #f: f64 = 1; $$ half(3); };";
+    assert!(type_errors(source).is_empty());
+}
+
+#[test]
+fn test_check_subclass_reference_as_parent() {
+    let source = "cls Entity { x: i32, }; cls Player : Entity { lives: i32, };
+        fn describe(e: &Entity) i32 { ret e.x; };
+        fn main() { #@p: Player; $$ describe(&p); };";
+    assert!(type_errors(source).is_empty());
+}
+
+#[test]
+fn test_check_res_values() {
+    let source = "fn half(n: i32) res<<i32>> { ? n % 2 != 0 { ret err \"odd\"; }; n / 2 };
+        fn check(n: i32) res<<[]>> { ? n < 0 { ret err \"negative\"; }; };";
+    assert!(type_errors(source).is_empty());
+}
+
+#[test]
+fn test_check_methods_and_lambdas() {
+    let source = "cls Counter { count: i32, fn bump(by: i32) { `v.count = `v.count + by; }; };
+        fn apply(f: func<<i32, i32>>, v: i32) i32 { f(v) };
+        fn main() { #@c: Counter; c.bump(2); $$ apply(lam (x: i32) i32 { x * 2 }, 3); };";
+    assert!(type_errors(source).is_empty());
+}
+
+// ========== Arguments ==========
+
+#[test]
+fn test_check_argument_type() {
+    assert_mismatch(
+        "fn add(a: i32, b: i32) i32 { ret a + b; }; fn main() { $$ add(1, `t); };",
+        "Expected an argument of type 'i32' for 'add', found 'bool'",
+    );
+}
+
+#[test]
+fn test_check_argument_count() {
+    assert_mismatch(
+        "fn add(a: i32, b: i32) i32 { ret a + b; }; fn main() { $$ add(1); };",
+        "Function 'add' takes 2 arguments but was given 1",
+    );
+}
+
+#[test]
+fn test_check_value_where_reference_expected() {
+    assert_mismatch(
+        "fn reset(n: &i32) { n = 0; }; fn main() { #@x = 1; reset(x); };",
+        "Expected an argument of type 'ref<<i32>>' for 'reset', found 'i32'",
+    );
+}
+
+#[test]
+fn test_check_method_argument() {
+    assert_mismatch(
+        "cls Counter { count: i32, fn bump(by: i32) { `v.count = `v.count + by; }; };
+        fn main() { #@c: Counter; c.bump(\"two\"); };",
+        "Expected an argument of type 'i32' for 'Counter::bump', found 'strlit'",
+    );
+}
+
+// ========== Declarations and Assignments ==========
+
+#[test]
+fn test_check_declared_type() {
+    assert_mismatch(
+        "fn main() { #flag: bool = 1; };",
+        "Expected a value of type 'bool' for 'flag', found 'i32'",
+    );
+}
+
+#[test]
+fn test_check_other_number_type_needs_cast() {
+    assert_mismatch(
+        "fn main() { #x = 1; #y: i64 = x; };",
+        "Expected a value of type 'i64' for 'y', found 'i32'",
+    );
+    assert!(type_errors("fn main() { #x = 1; #y: i64 = x -> i64; };").is_empty());
+}
+
+#[test]
+fn test_check_value_of_call_that_returns_nothing() {
+    assert_mismatch(
+        "fn nothing() { $$ 1; }; fn main() { #c: i32 = nothing(); };",
+        "Expected a value of type 'i32' for 'c', found 'nothing'",
+    );
+}
+
+#[test]
+fn test_check_assignment() {
+    assert_mismatch(
+        "fn main() { #@x = 1; x = \"s\"; };",
+        "Cannot assign a value of type 'strlit' to a place of type 'i32'",
+    );
+}
+
+#[test]
+fn test_check_assignment_to_member_and_element() {
+    let source = "cls Point { x: i32, };
+        fn main() { #@p: Point; p.x = 1.5; #@v = \\{1}; v[0] = 'c'; };";
+    let errors = type_errors(source);
+    assert_eq!(errors.len(), 2, "{:?}", errors);
+}
+
+#[test]
+fn test_check_assignment_through_reference() {
+    assert!(type_errors("fn reset(n: &i32) { n = 0; };").is_empty());
+    assert_mismatch(
+        "fn reset(n: &i32) { n = `f; };",
+        "Cannot assign a value of type 'bool' to a place of type 'i32'",
+    );
+}
+
+// ========== Returns ==========
+
+#[test]
+fn test_check_return_type() {
+    assert_mismatch(
+        "fn name() strlit { ret 5; };",
+        "Expected to return a value of type 'strlit', found 'i32'",
+    );
+}
+
+#[test]
+fn test_check_trailing_value() {
+    assert_mismatch(
+        "fn sq(x: i32) i32 { x > 0 };",
+        "Expected to return a value of type 'i32', found 'bool'",
+    );
+}
+
+#[test]
+fn test_check_lambda_return_type() {
+    assert_mismatch(
+        "fn main() { #f = lam (x: i32) i32 { ret `t; }; };",
+        "Expected to return a value of type 'i32', found 'bool'",
+    );
+}
+
+// ========== Conditions ==========
+
+#[test]
+fn test_check_if_condition() {
+    assert_mismatch(
+        "fn main() { #x = 1; ? x { $$ x; }; };",
+        "Expected a condition of type 'bool', found 'i32'",
+    );
+}
+
+#[test]
+fn test_check_while_condition() {
+    assert_mismatch(
+        "fn main() { £ \"forever\" { }; };",
+        "Expected a condition of type 'bool', found 'strlit'",
+    );
+}
+
+// ========== Operators ==========
+
+#[test]
+fn test_check_operands_of_different_types() {
+    assert_mismatch(
+        "fn main() { #x = 1; #a = x + 2.5; };",
+        "Cannot apply '+' to values of type 'i32' and 'f64'",
+    );
+    assert_mismatch(
+        "fn main() { #b = 3 & `t; };",
+        "Cannot apply '&' to values of type 'i32' and 'bool'",
+    );
+}
+
+#[test]
+fn test_check_added_to_string() {
+    let source = "fn main() { #s = to_str(\"a\"); #t = s + \"b\" + 'c'; #u = \"x\" + 'y'; };";
+    assert!(type_errors(source).is_empty());
+    assert_mismatch(
+        "fn f() strlit { \"x\" }; fn main() { #k = f() + 1; };",
+        "Cannot apply '+' to values of type 'strlit' and 'i32'",
+    );
+}
+
+#[test]
+fn test_check_literal_operand_takes_other_type() {
+    let source = "fn main() { #a = 1 + 2.5; #@z: u8 = 1; z = z << 3; #b = 1i64 & 2; #c = z < 9; };";
+    assert!(type_errors(source).is_empty());
+}
+
+#[test]
+fn test_check_literal_out_of_range() {
+    assert_mismatch(
+        "fn main() { #d: u8 = 300; };",
+        "The integer '300' does not fit in 'u8'",
+    );
+    assert_mismatch(
+        "fn main() { #g: i8 = -129; };",
+        "The integer '-129' does not fit in 'i8'",
+    );
+    assert_mismatch(
+        "fn main() { #@z: u8 = 1; z = z + 256; };",
+        "The integer '256' does not fit in 'u8'",
+    );
+}
+
+// ========== Reporting ==========
+
+#[test]
+fn test_check_reports_every_mismatch_with_its_line() {
+    let source = "fn main() {
+        #a: i32 = `t;
+        #@b = 1;
+        b = \"s\";
+        ? b { };
+    };";
+    let lines: Vec<usize> = type_errors(source).iter().map(|e| e.0).collect();
+    assert_eq!(lines, vec![2, 4, 5]);
+}
+
+#[test]
+fn test_check_skips_generic_functions() {
+    // generic functions are checked per instance, when they are emitted
+    assert!(type_errors("fn<<T>> id(x: T) T { x };").is_empty());
+}
+
+#[test]
+fn test_check_leaves_c_names_unchanged() {
+    let source = "fn sq(x: i32) i32 { #y = x * x; y };
+        fn main() { #@v = \\{1, 2}; for e : v { $$ sq(e); }; #p = &v; };";
+    let transpile = |check: bool| {
+        let mut scanner = Scanner::new(source.to_string());
+        let tokens = scanner.scan_tokens();
+        let expr = Parser::new(tokens).parse();
+        let mut ctx = CodeGenContext::new();
+        let mut cte = CompileTimeEnv::new(&mut ctx);
+        expr.pre_transpile(&mut cte, &mut ctx, &mut HashMap::new(), "");
+        if check {
+            assert!(type_check(&expr, &mut cte).is_empty());
+        }
+        expr.to_c(&mut cte, &mut ctx);
+        ctx.combine(&mut cte)
+    };
+    assert_eq!(transpile(true), transpile(false));
+}
+
+/// Returns the span of the declaration of `name` in a program
+fn declaration_span(expr: &Expr, name: &str) -> Option<crate::span::Span> {
+    match expr {
+        Expr::Declare(declared, .., span) if declared == name => Some(*span),
+        _ => expr
+            .children()
+            .into_iter()
+            .find_map(|child| declaration_span(child, name)),
+    }
+}
+
+#[test]
+fn test_check_records_types_of_locals() {
+    // the types found for locals declared without one are the ones they are emitted with
+    let source = "fn main() { #x = 1 + 0.5; #y: f32 = 2; };";
+    let expr = Parser::new(Scanner::new(source.to_string()).scan_tokens()).parse();
+    let mut ctx = CodeGenContext::new();
+    let mut cte = CompileTimeEnv::new(&mut ctx);
+    expr.pre_transpile(&mut cte, &mut ctx, &mut HashMap::new(), "");
+    assert!(type_check(&expr, &mut cte).is_empty());
+
+    let x = declaration_span(&expr, "x").unwrap();
+    assert_eq!(cte.checked_type("x", x), Some(Type::simple("f64")));
+    // declared types are already known
+    let y = declaration_span(&expr, "y").unwrap();
+    assert_eq!(cte.checked_type("y", y), None);
+}
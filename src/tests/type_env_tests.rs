@@ -127,6 +127,17 @@ fn test_generics_not_empty() {
     assert!(!ty.generics().is_empty());
 }
 
+#[test]
+fn test_number_types() {
+    assert!(Type::simple("u8").is_integer());
+    assert!(!Type::simple("f32").is_integer());
+    assert!(Type::simple("f32").is_float());
+    assert!(!Type::simple("char").is_float());
+    assert_eq!(Type::simple("i64").integer_bits(), Some((64, true)));
+    assert_eq!(Type::simple("u16").integer_bits(), Some((16, false)));
+    assert_eq!(Type::simple("f64").integer_bits(), None);
+}
+
 // ========== Type Equality ==========
 
 #[test]
@@ -476,3 +487,14 @@ fn test_complex_unification_failure() {
 
     assert!(!unify(&pattern, &actual, &mut bindings));
 }
+
+#[test]
+fn test_with_conceptuals() {
+    // vec<<T>> as written in a generic function's signature
+    let written = Type::with_generics("vec", vec![Type::simple("T"), Type::simple("i32")]);
+    let ty = written.with_conceptuals(&["T".to_string()]);
+
+    assert!(ty.contains_conceptual());
+    assert_eq!(ty.generics()[0], Type::conceptual("T"));
+    assert_eq!(ty.generics()[1], Type::simple("i32"));
+}
@@ -48,6 +48,11 @@ pub enum TokenType {
     DoubleColon,
     Minus,
     MinusRight,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    ModEqual,
 
     // Literals
     Ident,
@@ -60,6 +65,8 @@ pub enum TokenType {
 
     // Keywords
     Cls,
+    Enm,
+    Match,
     Ret,
     Comp,
     Stc,
@@ -68,6 +75,8 @@ pub enum TokenType {
     Del,
     Use,
     For,
+    Break,
+    Continue,
     Fn,
     Mac,
     Lam,
@@ -126,6 +135,11 @@ impl fmt::Display for TokenType {
             TokenType::DoubleColon => "DoubleColon",
             TokenType::Minus => "Minus",
             TokenType::MinusRight => "MinusRight",
+            TokenType::PlusEqual => "PlusEqual",
+            TokenType::MinusEqual => "MinusEqual",
+            TokenType::StarEqual => "StarEqual",
+            TokenType::SlashEqual => "SlashEqual",
+            TokenType::ModEqual => "ModEqual",
 
             // Literals
             TokenType::Ident => "Ident",
@@ -138,6 +152,8 @@ impl fmt::Display for TokenType {
 
             // Keywords
             TokenType::Cls => "Cls",
+            TokenType::Enm => "Enm",
+            TokenType::Match => "Match",
             TokenType::Ret => "Ret",
             TokenType::Comp => "Comp",
             TokenType::Stc => "Stc",
@@ -146,6 +162,8 @@ impl fmt::Display for TokenType {
             TokenType::Del => "Del",
             TokenType::Use => "Use",
             TokenType::For => "For",
+            TokenType::Break => "Break",
+            TokenType::Continue => "Continue",
             TokenType::Fn => "Fn",
             TokenType::Mac => "Mac",
             TokenType::Lam => "Lam",
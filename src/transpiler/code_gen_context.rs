@@ -7,6 +7,8 @@ pub struct CodeGenContext {
     pub declarations: String,
     pub unnamed: String,
     pub body: String,
+    /// Assignments of the globals with non-constant initialisers, run before `main`
+    pub init: String,
 }
 
 impl CodeGenContext {
@@ -22,6 +24,7 @@ impl CodeGenContext {
             body: String::new(),
             declarations: String::new(),
             unnamed: String::new(),
+            init: String::new(),
         }
     }
 
@@ -45,9 +48,22 @@ typedef void t_1CD; // [] (arr)
 typedef double t_2CD; // f64
 typedef bool t_3CD; // bool
 typedef char t_4CD; // char
-// typedef func t_5; // func - commented out as func is not a C type
+// typedef struct t_5; // func - generated per signature
 typedef char* t_6CD; // strlit
 // typedef *t_7; // ref - commented out as refs have to be "of" another type
+// typedef struct t_8; // vec - generated per element type
+// typedef struct t_9; // str - generated when used
+// typedef struct t_10; // res - generated per value type
+typedef int8_t t_11CD; // i8
+typedef int16_t t_12CD; // i16
+typedef int64_t t_13CD; // i64
+typedef uint8_t t_14CD; // u8
+typedef uint16_t t_15CD; // u16
+typedef uint32_t t_16CD; // u32
+typedef uint64_t t_17CD; // u64
+typedef float t_18CD; // f32
+
+typedef void (*obs_vslot)(void); // a vtable slot, cast to the type of its method when called
 
 "#;
 
@@ -70,6 +86,11 @@ t_1CD v_0s_0Ct_3CDD(t_3CD n) { // print bool
     fflush(stdout);
 }
 
+t_1CD v_0s_0Ct_4CDD(t_4CD c) { // print char
+    printf("%c", c);
+    fflush(stdout);
+}
+
 t_1CD v_0s_0Ct_6CDD(t_6CD s) { // print strlit
     printf("%s", s);
     fflush(stdout);
@@ -154,28 +175,31 @@ t_2CD v_6s_0Ct_2CDD(t_2CD n1, t_2CD n2) { // div f64
 
 t_0CD v_7s_0CD() { // intput
     t_0CD output;
-    scanf("%d", &output);
+    if (scanf("%d", &output) != 1) {
+        fprintf(stderr, "Runtime error: intput expected an integer\n");
+        exit(1);
+    }
     return output;
 }
 
 t_2CD v_8s_0CD() { // fput
     t_2CD output;
-    scanf("%f", &output);
+    if (scanf("%lf", &output) != 1) {
+        fprintf(stderr, "Runtime error: fput expected a number\n");
+        exit(1);
+    }
     return output;
 }
 
 t_6CD v_9s_0CD() { // strput
     char* buffer = malloc(256 * sizeof(char));
-    if (buffer == NULL) return NULL; // Handle allocation failure
-
-    if (fgets(buffer, 256, stdin)) {
-        // Optional: Remove trailing newline
-        buffer[strcspn(buffer, "\n")] = 0;
-        return buffer;
+    if (buffer == NULL || !fgets(buffer, 256, stdin)) {
+        fprintf(stderr, "Runtime error: strput reached the end of input\n");
+        exit(1);
     }
-
-    free(buffer);
-    return NULL;
+    // Remove trailing newline
+    buffer[strcspn(buffer, "\n")] = 0;
+    return buffer;
 }
 
 t_3CD v_10s_0Ct_0CDD(t_0CD n1, t_0CD n2) { // equal i32
@@ -280,14 +304,35 @@ t_1CD v_21s_0CD(t_0CD time) { // sleep
     usleep(time);
 }
 
+t_0CD v_22s_0Ct_0CDD(t_0CD n1, t_0CD n2) { // mod i32
+    return n1 % n2;
+}
+
+t_2CD v_22s_0Ct_2CDD(t_2CD n1, t_2CD n2) { // mod f64
+    return fmod(n1, n2);
+}
+
+t_0CD v_25s_0Ct_6CDD(t_6CD s) { // len strlit
+    return strlen(s);
+}
+
 "#;
 
         let include = base_include.to_string() + base_body;
 
+        if !self.init.is_empty() {
+            self.body.push_str("\nt_1CD obs_init_globals() {\n");
+            self.body.push_str(&self.init);
+            self.body.push('}');
+        }
+
         self.body.push_str(
             "
 int main() {\n    ",
         );
+        if !self.init.is_empty() {
+            self.body.push_str("obs_init_globals();\n    ");
+        }
         self.body
             .push_str(&cte.c_func_instance_name("main", &[], Span::empty()));
         self.body.push_str("();\n}");
@@ -1,26 +1,109 @@
 use crate::error;
 use crate::expr::Expr;
+use crate::get_current_file;
+use crate::runtime_env::RuntimeEnvironment;
 use crate::span::Span;
 use crate::transpiler::code_gen_context::CodeGenContext;
-use crate::type_env::{nil_type, Type};
+use crate::type_env::{nil_type, substitute, unify, Type};
 use std::collections::{HashMap, HashSet};
 
+/// The bodies of the fallible input builtins, returning a RES of what they read
+const TRY_INTPUT: &str = r#"    t_0CD value;
+    int n = scanf("%d", &value);
+    if (n == EOF) return (RES){ .ok = false, .err = "end of input" };
+    if (n != 1) {
+        for (int c = getchar(); c != '\n' && c != EOF; c = getchar()); // skip the rest of the line
+        return (RES){ .ok = false, .err = "expected an integer" };
+    }
+    return (RES){ .ok = true, .value = value };
+"#;
+
+const TRY_FPUT: &str = r#"    t_2CD value;
+    int n = scanf("%lf", &value);
+    if (n == EOF) return (RES){ .ok = false, .err = "end of input" };
+    if (n != 1) {
+        for (int c = getchar(); c != '\n' && c != EOF; c = getchar()); // skip the rest of the line
+        return (RES){ .ok = false, .err = "expected a number" };
+    }
+    return (RES){ .ok = true, .value = value };
+"#;
+
+const TRY_STRPUT: &str = r#"    char* buffer = malloc(256 * sizeof(char));
+    if (buffer == NULL) return (RES){ .ok = false, .err = "out of memory" };
+    if (!fgets(buffer, 256, stdin)) {
+        free(buffer);
+        return (RES){ .ok = false, .err = "end of input" };
+    }
+    buffer[strcspn(buffer, "\n")] = 0;
+    return (RES){ .ok = true, .value = buffer };
+"#;
+
+/// A loop being emitted, which `break` and `continue` jump out of with a C `goto`
+struct Loop {
+    label: Option<String>,
+    /// makes the C labels of the loop unique
+    id: usize,
+    /// whether a `break` or `continue` jumped to it, so its C label has to be emitted
+    broken: bool,
+    continued: bool,
+}
+
 pub struct CompileTimeEnv {
     all_types: Vec<Type>,
     scopes: Vec<HashMap<String, (usize, bool, Type)>>, // variable: id, is_mutable, type
     current_scope: usize,
 
-    these: Vec<String>,
-
     /// HashMap<class name, (Vec<(member name, type)>, next member id)>
     members: HashMap<Type, (Vec<(String, Type)>, usize)>,
 
     next_var_id: usize,
     next_type_id: usize,
 
-    generic_functions: Vec<(Type, Expr)>,
-
-    /// Set of ref<T> type names that have already been emitted as typedefs
+    /// HashMap<function name, declaration with its generic parameters as conceptual types>
+    generic_functions: HashMap<String, Expr>,
+    /// C names of the generic function instances that have already been emitted
+    generic_instances: HashSet<String>,
+    /// C names of the functions used as values, whose wrappers taking an environment have
+    /// already been emitted
+    function_values: HashSet<String>,
+    /// HashMap<class name, (class type with conceptual generics, members)> of generic classes
+    generic_classes: HashMap<String, (Type, Vec<(String, Type)>)>,
+    /// HashMap<enum, Vec<(variant, payload types)>>, in declaration order, which is the order of
+    /// their tags
+    enums: HashMap<Type, Vec<(String, Vec<Type>)>>,
+    /// HashMap<class, parent class> of classes declared as `cls Child : Parent`
+    parents: HashMap<Type, Type>,
+    /// HashMap<class, Vec<(method name, function implementing it)>>, the vtable slots of every
+    /// class that has (or inherits) `ovr` methods
+    vtables: HashMap<Type, Vec<(String, String)>>,
+    /// HashMap<method function, (class, body)> of the methods that take `this`
+    methods: HashMap<String, (Type, Expr)>,
+    /// HashMap<method function, whether it can change the value it is called on>, filled in as
+    /// it is found out
+    changing_methods: HashMap<String, bool>,
+    /// Ids of the str variables that `s = s + x` appends to in place, as no other value shares
+    /// their chars while it does
+    unshared_strs: HashSet<usize>,
+    /// Whether the next array literal emitted can keep its elements on the stack, as its value
+    /// does not outlive the C block it is in
+    array_on_stack: bool,
+    /// Names of the variables declared at the top level of a program, which are C globals
+    globals: HashSet<String>,
+    /// Names of the `comp fn`s, which are evaluated by the compiler instead of being emitted
+    comp_functions: HashSet<String>,
+    /// The values of the globals and `comp fn`s known at compile time, for evaluating `comp`
+    comp_env: RuntimeEnvironment,
+    /// HashMap<(file, variable, span of its declaration), type> of the local variables declared
+    /// without a type, as the type checker found them
+    checked_types: HashMap<(Option<String>, String, Span), Type>,
+    /// The return types of the functions being emitted, innermost last
+    return_types: Vec<Type>,
+    /// The loops being emitted in each function, innermost last. A loop outside a function can
+    /// never be left from inside it.
+    loops: Vec<Vec<Loop>>,
+
+    /// Set of ref<T>, vec<T>, arr<T>, func and generic class instance type names that have already
+    /// been emitted as typedefs
     ref_typedefs_emitted: HashSet<String>,
 }
 
@@ -31,14 +114,28 @@ impl CompileTimeEnv {
             scopes: vec![HashMap::new()],
             current_scope: 0,
 
-            these: Vec::new(),
-
             members: HashMap::new(),
 
             next_var_id: 0,
             next_type_id: 0,
 
-            generic_functions: Vec::new(),
+            generic_functions: HashMap::new(),
+            generic_instances: HashSet::new(),
+            function_values: HashSet::new(),
+            generic_classes: HashMap::new(),
+            enums: HashMap::new(),
+            parents: HashMap::new(),
+            vtables: HashMap::new(),
+            methods: HashMap::new(),
+            changing_methods: HashMap::new(),
+            unshared_strs: HashSet::new(),
+            array_on_stack: false,
+            globals: HashSet::new(),
+            comp_functions: HashSet::new(),
+            comp_env: RuntimeEnvironment::new(),
+            checked_types: HashMap::new(),
+            return_types: Vec::new(),
+            loops: vec![vec![]],
 
             ref_typedefs_emitted: HashSet::new(),
         };
@@ -51,6 +148,12 @@ impl CompileTimeEnv {
         this.register_type(Type::simple("func"));
         this.register_type(Type::simple("strlit"));
         this.register_type(Type::simple("ref"));
+        this.register_type(Type::simple("vec"));
+        this.register_type(Type::simple("str"));
+        this.register_type(Type::simple("res"));
+        for number in ["i8", "i16", "i64", "u8", "u16", "u32", "u64", "f32"] {
+            this.register_type(Type::simple(number));
+        }
 
         // Declare and register _print: func(i32) -> arr
         this.declare_global_var(
@@ -472,6 +575,192 @@ impl CompileTimeEnv {
 	);
 	this.add_func_type(nil_type(), vec![], ctx, Span::empty());
 
+        // Declare and register _mod: func(arg1, arg2, return)
+        this.declare_global_var(
+            "_mod".to_string(),
+            false,
+            Type::with_generics(
+                "func",
+                vec![
+                    Type::simple("i32"),
+                    Type::simple("i32"),
+                    Type::simple("i32"),
+                ],
+            ),
+        );
+        this.add_func_type(
+            Type::simple("i32"),
+            vec![Type::simple("i32"), Type::simple("i32")],
+            ctx,
+            Span::empty(),
+        );
+        this.add_func_type(
+            Type::simple("f64"),
+            vec![Type::simple("f64"), Type::simple("f64")],
+            ctx,
+            Span::empty(),
+        );
+
+        // The vec builtins are generic over the element type T. Their C instances are emitted
+        // together with each vec type (see emit_vec_type) and keyed by the first argument type.
+        let elem = Type::conceptual("T");
+        let vec = Type::with_generics("vec", vec![elem.clone()]);
+        let vec_ref = Type::with_generics("ref", vec![vec.clone()]);
+
+        // Declare push: func(&vec<T>, T) -> arr
+        this.declare_global_var(
+            "push".to_string(),
+            false,
+            Type::with_generics("func", vec![vec_ref.clone(), elem.clone(), nil_type()]),
+        );
+
+        // Declare pop: func(&vec<T>) -> T
+        this.declare_global_var(
+            "pop".to_string(),
+            false,
+            Type::with_generics("func", vec![vec_ref.clone(), elem.clone()]),
+        );
+
+        // Declare len: func(T) -> i32, implemented for vec<T> and strlit
+        this.declare_global_var(
+            "len".to_string(),
+            false,
+            Type::with_generics("func", vec![elem.clone(), Type::simple("i32")]),
+        );
+
+        // Declare get: func(vec<T>, i32) -> T
+        this.declare_global_var(
+            "get".to_string(),
+            false,
+            Type::with_generics("func", vec![vec, Type::simple("i32"), elem.clone()]),
+        );
+
+        // Declare set: func(&vec<T>, i32, T) -> arr
+        this.declare_global_var(
+            "set".to_string(),
+            false,
+            Type::with_generics(
+                "func",
+                vec![vec_ref, Type::simple("i32"), elem, nil_type()],
+            ),
+        );
+
+        // The str builtins (and the str overloads of the operators, len and print) are emitted
+        // together with the str type, see emit_str_type.
+        let str_type = Type::simple("str");
+
+        // Declare to_str: func(strlit) -> str
+        this.declare_global_var(
+            "to_str".to_string(),
+            false,
+            Type::with_generics("func", vec![Type::simple("strlit"), str_type.clone()]),
+        );
+
+        // Declare slice: func(str, i32, i32) -> str
+        this.declare_global_var(
+            "slice".to_string(),
+            false,
+            Type::with_generics(
+                "func",
+                vec![
+                    str_type.clone(),
+                    Type::simple("i32"),
+                    Type::simple("i32"),
+                    str_type.clone(),
+                ],
+            ),
+        );
+
+        // Declare drop: func(str) -> arr
+        this.declare_global_var(
+            "drop".to_string(),
+            false,
+            Type::with_generics("func", vec![str_type, nil_type()]),
+        );
+
+        // The res builtins are generic over the success type T, and emitted together with each
+        // res type (see emit_res_type).
+        let res = Type::with_generics("res", vec![Type::conceptual("T")]);
+
+        // Declare is_ok: func(res<T>) -> bool
+        this.declare_global_var(
+            "is_ok".to_string(),
+            false,
+            Type::with_generics("func", vec![res.clone(), Type::simple("bool")]),
+        );
+
+        // Declare unwrap: func(res<T>) -> T
+        this.declare_global_var(
+            "unwrap".to_string(),
+            false,
+            Type::with_generics("func", vec![res.clone(), Type::conceptual("T")]),
+        );
+
+        // Declare err_msg: func(res<T>) -> strlit
+        this.declare_global_var(
+            "err_msg".to_string(),
+            false,
+            Type::with_generics("func", vec![res, Type::simple("strlit")]),
+        );
+
+        // The fallible versions of the input builtins, emitted with res<i32>, res<f64> and
+        // res<strlit>
+        for (name, ty) in [
+            ("try_intput", "i32"),
+            ("try_fput", "f64"),
+            ("try_strput", "strlit"),
+        ] {
+            let res = Type::with_generics("res", vec![Type::simple(ty)]);
+            this.declare_global_var(
+                name.to_string(),
+                false,
+                Type::with_generics("func", vec![res]),
+            );
+        }
+
+        // Declare and register the bitwise operators: func(arg1, arg2, return), implemented for
+        // the integers (and _xor for bool). The instances of the integers other than i32 are
+        // emitted with their type, see emit_number_type.
+        for name in ["_bit_and", "_bit_or", "_xor", "_shl", "_shr"] {
+            this.declare_global_var(
+                name.to_string(),
+                false,
+                Type::with_generics(
+                    "func",
+                    vec![
+                        Type::simple("i32"),
+                        Type::simple("i32"),
+                        Type::simple("i32"),
+                    ],
+                ),
+            );
+        }
+
+        // Declare and register _bit_not: func(arg, return)
+        this.declare_global_var(
+            "_bit_not".to_string(),
+            false,
+            Type::with_generics("func", vec![Type::simple("i32"), Type::simple("i32")]),
+        );
+        this.add_func_type(
+            Type::simple("i32"),
+            vec![Type::simple("i32")],
+            ctx,
+            Span::empty(),
+        );
+
+        // The i32 and bool instances of the bitwise operators, under the names calls to them use
+        let (mut declarations, mut bodies) =
+            this.bitwise_instances(&Type::simple("i32"), "t_0CD", Span::empty());
+        let xor = this.c_func_instance_name("_xor", &[Type::simple("bool")], Span::empty());
+        declarations.push_str(&format!("t_3CD {}(t_3CD b1, t_3CD b2);\n", xor));
+        bodies.push_str(&format!(
+            "\nt_3CD {}(t_3CD b1, t_3CD b2) {{ // xor bool\n    return b1 != b2;\n}}\n",
+            xor
+        ));
+        ctx.declarations.push_str(&declarations);
+        ctx.unnamed.push_str(&bodies);
+
         this
     }
 
@@ -487,23 +776,39 @@ impl CompileTimeEnv {
         self.current_scope -= 1;
     }
 
+    /// Runs `f` with only the global scope visible, like in the body of a function declared at
+    /// the top level, wherever it is called from. The scopes `f` pushes must be popped by it.
+    pub fn with_only_globals<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let locals = self.scopes.split_off(1);
+        let current_scope = std::mem::replace(&mut self.current_scope, 0);
+        let result = f(self);
+        self.scopes.extend(locals);
+        self.current_scope = current_scope;
+        result
+    }
+
     // Class handling
 
     pub fn declare_member(&mut self, name: String, t: Type, class_type: Type) -> usize {
         let class = self.members.get_mut(&class_type).unwrap();
         let id = class.1;
+        class.1 += 1;
 
         class.0.push((name, t));
 
         id
     }
 
+    /// Returns the C member name of a class member.\
+    /// Format: m_{id}, where id is the position of the member in its class
     pub fn c_member_name(&mut self, ty: &Type, name: &str, span: Span) -> String {
+        self.register_class_instance(ty);
         let class = self.members.get(ty);
 
         match class {
             Some(class) => {
-                format!("m_{}", class.1)
+                let id = class.0.iter().position(|m| m.0 == name).unwrap_or(class.1);
+                format!("m_{}", id)
             }
             None => {
                 error(
@@ -519,7 +824,8 @@ impl CompileTimeEnv {
         }
     }
 
-    pub fn get_member_type(&self, ty: &Type, name: &str) -> Option<Type> {
+    pub fn get_member_type(&mut self, ty: &Type, name: &str) -> Option<Type> {
+        self.register_class_instance(ty);
         let class = self.members.get(ty);
         match class {
             Some(class) => {
@@ -541,6 +847,148 @@ impl CompileTimeEnv {
         self.register_type(ty.clone())
     }
 
+    /// Declares a generic class, like Pair<<A, B>>. Its instances are registered as classes of
+    /// their own the first time they are used.
+    pub fn declare_generic_class(&mut self, ty: Type, members: Vec<(String, Type)>) {
+        // instances are mangled with the ID of the bare class name
+        self.register_type(Type::simple(ty.name()));
+        self.generic_classes
+            .insert(ty.name().to_string(), (ty, members));
+    }
+
+    /// Makes `child` a subclass of `parent`. It starts with all the members of its parent, in the
+    /// same order, so a reference to the child can be used as a reference to the parent.
+    pub fn inherit(&mut self, child: &Type, parent: &Type) {
+        let members = self.class_members(parent);
+        for (name, member_type) in members {
+            self.declare_member(name, member_type, child.clone());
+        }
+        self.parents.insert(child.clone(), parent.clone());
+    }
+
+    /// Declares an enum. Its variants are tagged with their position.
+    pub fn declare_enum(&mut self, ty: Type, variants: Vec<(String, Vec<Type>)>) -> usize {
+        self.enums.insert(ty.clone(), variants);
+        self.register_type(ty)
+    }
+
+    /// Returns the variants of an enum, or None if the type is not one.
+    pub fn enum_variants(&self, ty: &Type) -> Option<&Vec<(String, Vec<Type>)>> {
+        self.enums.get(ty)
+    }
+
+    pub fn parent_of(&self, ty: &Type) -> Option<&Type> {
+        self.parents.get(ty)
+    }
+
+    /// Returns true if `ty` is `ancestor` or inherits from it, directly or not
+    pub fn is_subclass(&self, ty: &Type, ancestor: &Type) -> bool {
+        let mut current = Some(ty);
+        while let Some(class) = current {
+            if class == ancestor {
+                return true;
+            }
+            current = self.parents.get(class);
+        }
+        false
+    }
+
+    /// Returns the members of a class (inherited ones first), in the order they are laid out
+    pub fn class_members(&self, ty: &Type) -> Vec<(String, Type)> {
+        self.members
+            .get(ty)
+            .map(|class| class.0.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn set_vtable(&mut self, ty: Type, slots: Vec<(String, String)>) {
+        self.vtables.insert(ty, slots);
+    }
+
+    pub fn vtable(&self, ty: &Type) -> Option<&Vec<(String, String)>> {
+        self.vtables.get(ty)
+    }
+
+    /// Declares the body of a method that takes `this`, by the name of the function it is emitted
+    /// as.
+    pub fn declare_method(&mut self, function: String, class: Type, body: Expr) {
+        self.methods.insert(function, (class, body));
+    }
+
+    /// Returns the class and body of a method that takes `this`, by the name of its function.
+    pub fn method(&self, function: &str) -> Option<&(Type, Expr)> {
+        self.methods.get(function)
+    }
+
+    /// Returns the functions of the methods named `method` of the subclasses of `class`, which
+    /// can override it.
+    pub fn subclass_methods(&self, class: &Type, method: &str) -> Vec<String> {
+        self.methods
+            .iter()
+            .filter(|(function, (owner, _))| {
+                owner != class
+                    && self.is_subclass(owner, class)
+                    && **function == format!("{}::{}", owner.name(), method)
+            })
+            .map(|(function, _)| function.clone())
+            .collect()
+    }
+
+    /// Returns whether a method can change the value it is called on, if that is known yet.
+    pub fn changes_receiver(&self, function: &str) -> Option<bool> {
+        self.changing_methods.get(function).copied()
+    }
+
+    pub fn set_changes_receiver(&mut self, function: &str, changes: bool) {
+        self.changing_methods.insert(function.to_string(), changes);
+    }
+
+    /// Marks the str variable as one that no other value shares the chars of when it is appended
+    /// to, so that appending grows it in place.
+    pub fn set_unshared_str(&mut self, name: &str) {
+        if let Some((id, _)) = self.resolve_var(name) {
+            self.unshared_strs.insert(id);
+        }
+    }
+
+    pub fn is_unshared_str(&self, name: &str) -> bool {
+        self.resolve_var(name)
+            .is_some_and(|(id, _)| self.unshared_strs.contains(&id))
+    }
+
+    /// Decides whether the next array literal emitted keeps its elements on the stack instead of
+    /// in a buffer of its own on the heap.
+    pub fn set_array_on_stack(&mut self, on_stack: bool) {
+        self.array_on_stack = on_stack;
+    }
+
+    /// Returns whether the array literal being emitted keeps its elements on the stack, which
+    /// only holds for that one literal.
+    pub fn take_array_on_stack(&mut self) -> bool {
+        std::mem::take(&mut self.array_on_stack)
+    }
+
+    /// Registers an instance of a generic class (like Pair<<i32, f64>>) and its members, with the
+    /// generic types substituted, unless that has already been done.
+    fn register_class_instance(&mut self, ty: &Type) {
+        if !ty.has_generics() || ty.contains_conceptual() || self.members.contains_key(ty) {
+            return;
+        }
+        let Some((pattern, members)) = self.generic_classes.get(ty.name()).cloned() else {
+            return;
+        };
+        let mut bindings = HashMap::new();
+        if !unify(&pattern, ty, &mut bindings) {
+            return;
+        }
+
+        self.register_class(ty.clone());
+        for (name, member_type) in members {
+            let member_type = substitute(&member_type, &bindings, Span::empty());
+            self.declare_member(name, member_type, ty.clone());
+        }
+    }
+
     // Variable Handling
 
     pub fn declare_var(&mut self, name: String, is_mutable: bool, var_type: Type) -> usize {
@@ -553,10 +1001,47 @@ impl CompileTimeEnv {
 
         let scope = self.scopes.last_mut().unwrap();
         scope.insert(name, (id, is_mutable, var_type));
+        // the id can be given out again after without_new_ids
+        self.unshared_strs.remove(&id);
 
         id
     }
 
+    /// Declares a compiler-generated variable in the current scope and returns its C name.\
+    /// The Obstruct-side name can never be written in source, so it never shadows user variables.
+    pub fn declare_temp(&mut self, var_type: Type) -> String {
+        let name = format!("%tmp{}", self.next_var_id);
+        self.declare_var_inner(name.clone(), false, var_type);
+        self.c_var_name(&name, Span::empty())
+    }
+
+    /// Runs `f`, then forgets the ids of the variables and types it declared, so that a pass which
+    /// only reads the program (like the type checker) leaves the C names it is emitted with as
+    /// they would have been without it. The scopes `f` pushes must be popped by it.
+    pub fn without_new_ids<R>(&mut self, f: impl FnOnce(&mut Self) -> R) -> R {
+        let (next_var_id, next_type_id) = (self.next_var_id, self.next_type_id);
+        let type_count = self.all_types.len();
+        let result = f(self);
+        self.next_var_id = next_var_id;
+        self.next_type_id = next_type_id;
+        self.all_types.truncate(type_count);
+        result
+    }
+
+    /// Records the type the type checker found for a local variable declared without one.
+    pub fn record_checked_type(&mut self, name: &str, span: Span, var_type: Type) {
+        self.checked_types
+            .insert((get_current_file(), name.to_string(), span), var_type);
+    }
+
+    /// Returns the type the type checker found for the local variable declared at `span`, or None
+    /// if it was not checked, like the variables of generic functions.
+    pub fn checked_type(&self, name: &str, span: Span) -> Option<Type> {
+        self.checked_types
+            .get(&(get_current_file(), name.to_string(), span))
+            .cloned()
+    }
+
     pub fn declare_global_var(&mut self, name: String, is_mutable: bool, var_type: Type) -> usize {
         let id = self.next_var_id;
         self.next_var_id += 1;
@@ -567,6 +1052,93 @@ impl CompileTimeEnv {
         id
     }
 
+    /// Declares a variable at the top level of a program, which is emitted as a C global.\
+    /// Unlike functions, its C name has no generics suffix, even if it holds a function value.
+    pub fn declare_global_value(&mut self, name: String, is_mutable: bool, var_type: Type) -> usize {
+        self.globals.insert(name.clone());
+        self.declare_global_var(name, is_mutable, var_type)
+    }
+
+    /// Declares a `comp fn`. It has no C name, calls to it are evaluated by the compiler.
+    pub fn declare_comp_function(&mut self, name: String, func_type: Type) {
+        self.comp_functions.insert(name.clone());
+        self.declare_global_var(name, false, func_type);
+    }
+
+    /// Returns true if the name refers to a `comp fn`.
+    pub fn is_comp_function(&self, name: &str) -> bool {
+        self.is_function(name) && self.comp_functions.contains(name)
+    }
+
+    /// The environment `comp` expressions are evaluated in.
+    pub fn comp_env(&mut self) -> &mut RuntimeEnvironment {
+        &mut self.comp_env
+    }
+
+    /// Marks the start of the body of a function that returns `return_type`, which `ret`, `err`
+    /// and `$?` refer to.
+    pub fn enter_function(&mut self, return_type: Type) {
+        self.return_types.push(return_type);
+        self.loops.push(vec![]);
+    }
+
+    pub fn exit_function(&mut self) {
+        self.return_types.pop();
+        self.loops.pop();
+    }
+
+    /// Marks the start of a loop, which `break` and `continue` refer to.
+    pub fn enter_loop(&mut self, label: Option<String>) {
+        let id = self.next_var_id;
+        self.next_var_id += 1;
+        self.loops.last_mut().unwrap().push(Loop {
+            label,
+            id,
+            broken: false,
+            continued: false,
+        });
+    }
+
+    /// Marks the end of the body of the innermost loop. Returns the C labels `break` and
+    /// `continue` jumped to: the one of `continue` goes at the end of the body, the one of
+    /// `break` after the loop.
+    pub fn exit_loop(&mut self) -> (Option<String>, Option<String>) {
+        let lp = self.loops.last_mut().unwrap().pop().unwrap();
+        (
+            lp.broken.then(|| format!("l{}_break", lp.id)),
+            lp.continued.then(|| format!("l{}_continue", lp.id)),
+        )
+    }
+
+    /// Returns the C label a `break` (or `continue`) jumps to, for the loop with the label or
+    /// the innermost one. None if there is no such loop in the function being emitted.
+    pub fn loop_jump(&mut self, label: Option<&str>, is_break: bool) -> Option<String> {
+        let lp = self
+            .loops
+            .last_mut()
+            .unwrap()
+            .iter_mut()
+            .rev()
+            .find(|lp| label.is_none() || lp.label.as_deref() == label)?;
+        if is_break {
+            lp.broken = true;
+            Some(format!("l{}_break", lp.id))
+        } else {
+            lp.continued = true;
+            Some(format!("l{}_continue", lp.id))
+        }
+    }
+
+    /// Returns the return type of the innermost function being emitted.
+    pub fn return_type(&self) -> Option<&Type> {
+        self.return_types.last()
+    }
+
+    /// Returns true outside of any function, where declarations are globals.
+    pub fn is_global_scope(&self) -> bool {
+        self.current_scope == 0
+    }
+
     fn resolve_var(&self, name: &str) -> Option<(usize, usize)> {
         for (idx, scope) in self.scopes.iter().enumerate().rev() {
             if let Some(id) = scope.get(name) {
@@ -576,6 +1148,56 @@ impl CompileTimeEnv {
         None
     }
 
+    /// Returns true if the name refers to a declared function (rather than a variable that
+    /// happens to hold a function value).
+    pub fn is_function(&self, name: &str) -> bool {
+        match (self.resolve_var(name), self.get_var(name)) {
+            (Some((_, scope)), Some((_, ty))) => {
+                scope == 0 && ty.name() == "func" && !self.globals.contains(name)
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns true if the name refers to a variable declared inside a function (not a global).
+    pub fn is_local(&self, name: &str) -> bool {
+        matches!(self.resolve_var(name), Some((_, scope)) if scope > 0)
+    }
+
+    /// Declares the hidden global function a lambda is lifted into and returns its C name.
+    pub fn declare_lambda(&mut self, func_type: Type) -> String {
+        let name = format!("%lam{}", self.next_var_id);
+        self.declare_global_var(name.clone(), false, func_type);
+        self.c_func_instance_name(&name, &[], Span::empty())
+    }
+
+    /// Declares a generic function. No C code is emitted for it until it is instantiated.
+    pub fn declare_generic_function(&mut self, name: String, func_type: Type, decl: Expr) {
+        self.declare_global_var(name.clone(), false, func_type);
+        self.generic_functions.insert(name, decl);
+    }
+
+    /// Returns the declaration of a generic function, if the name refers to one.
+    pub fn get_generic_function(&self, name: &str) -> Option<&Expr> {
+        if self.is_function(name) {
+            self.generic_functions.get(name)
+        } else {
+            None
+        }
+    }
+
+    /// Marks the instance of a generic function as emitted.\
+    /// Returns false if it already was.
+    pub fn add_generic_instance(&mut self, c_name: &str) -> bool {
+        self.generic_instances.insert(c_name.to_string())
+    }
+
+    /// Marks a function as used as a value, so that its wrapper is emitted.\
+    /// Returns false if it already was.
+    pub fn add_function_value(&mut self, c_name: &str) -> bool {
+        self.function_values.insert(c_name.to_string())
+    }
+
     pub fn get_var(&self, name: &str) -> Option<(bool, Type)> {
         for scope in self.scopes.iter().rev() {
             if let Some((_id, is_mutable, var_type)) = scope.get(name) {
@@ -767,20 +1389,24 @@ impl CompileTimeEnv {
 
         // For function types, the raw name already ends with D, so don't add CD
         if ty.name() == "func" {
+            // Make sure the function pointer typedef exists before it is used
+            if let Some((ret_type, arg_types)) = ty.generics().split_last() {
+                self.add_func_type(ret_type.clone(), arg_types.to_vec(), ctx, span);
+            }
             name
         } else if ty.generics().is_empty() {
             let mut result = name;
             result.push_str("CD");
+            if ty.name() == "str" {
+                self.emit_str_type(&result, ctx, span);
+            } else if (ty.is_integer() || ty.is_float()) && !matches!(ty.name(), "i32" | "f64") {
+                self.emit_number_type(ty, &result, ctx, span);
+            }
             result
         } else if ty.name() == "ref" {
             // Ensure a typedef like: typedef t_15CD* t_7Ct_15CD; exists for ref<SomeType>
             let inner_type = ty.generics()[0].clone();
-            let inner_raw = self.c_type_name_raw(&inner_type, span);
-            let inner_c_type = if inner_type.name() == "func" {
-                inner_raw
-            } else {
-                inner_raw + "CD"
-            };
+            let inner_c_type = self.c_type_name(&inner_type, ctx, span);
             let typedef_name = name.clone();
             let typedef_key = format!("ref_{}", inner_c_type);
             if !self.ref_typedefs_emitted.contains(&typedef_key) {
@@ -789,11 +1415,550 @@ impl CompileTimeEnv {
                     .push_str(&format!("typedef {}* {};\n", inner_c_type, typedef_name));
             }
             name
+        } else if ty.name() == "vec" {
+            self.emit_vec_type(ty, &name, ctx, span);
+            name
+        } else if ty.name() == "arr" {
+            self.emit_arr_type(ty, &name, ctx, span);
+            name
+        } else if ty.name() == "res" {
+            self.emit_res_type(ty, &name, ctx, span);
+            name
+        } else if self.generic_classes.contains_key(ty.name()) {
+            self.emit_class_instance(ty, &name, ctx, span);
+            name
+        } else {
+            name
+        }
+    }
+
+    /// Emits the struct typedef of a vec<T> along with the C instances of the vec builtins.\
+    /// Format: typedef struct { T* data; t_0CD len; t_0CD cap; } t_8C{T}D;
+    fn emit_vec_type(&mut self, ty: &Type, name: &str, ctx: &mut CodeGenContext, span: Span) {
+        if !self.ref_typedefs_emitted.insert(name.to_string()) {
+            return;
+        }
+
+        let elem_type = ty.generics()[0].clone();
+        let elem = self.c_type_name(&elem_type, ctx, span);
+        ctx.types.push_str(&format!(
+            "typedef struct {{ {}* data; t_0CD len; t_0CD cap; }} {};\n",
+            elem, name
+        ));
+
+        // Structs are passed by pointer, like they are to any other function
+        let (elem_param, elem_value) = if self.is_class(&elem_type) {
+            (format!("{}*", elem), "*x")
         } else {
+            (elem.clone(), "x")
+        };
+
+        let push = self.c_func_instance_name("push", std::slice::from_ref(ty), span);
+        let pop = self.c_func_instance_name("pop", std::slice::from_ref(ty), span);
+        let len = self.c_func_instance_name("len", std::slice::from_ref(ty), span);
+        let get = self.c_func_instance_name("get", std::slice::from_ref(ty), span);
+        let set = self.c_func_instance_name("set", std::slice::from_ref(ty), span);
+
+        ctx.declarations.push_str(&format!(
+            "{v} {v}_from({e}* src, t_0CD n);\n\
+             t_1CD {push}({v}* v, {p} x);\n\
+             {e} {pop}({v}* v);\n\
+             t_0CD {len}({v} v);\n\
+             {e} {get}({v} v, t_0CD i);\n\
+             t_1CD {set}({v}* v, t_0CD i, {p} x);\n",
+            v = name,
+            e = elem,
+            p = elem_param,
+        ));
+
+        ctx.unnamed.push_str(&format!(
+            r#"
+{v} {v}_from({e}* src, t_0CD n) {{ // vec literal
+    {v} v = {{ malloc(sizeof({e}) * n), n, n }};
+    memcpy(v.data, src, sizeof({e}) * n);
+    return v;
+}}
+
+t_1CD {push}({v}* v, {p} x) {{ // push
+    if (v->len == v->cap) {{
+        v->cap = v->cap ? v->cap * 2 : 4;
+        v->data = realloc(v->data, sizeof({e}) * v->cap);
+    }}
+    v->data[v->len++] = {x};
+}}
+
+{e} {pop}({v}* v) {{ // pop
+    if (v->len == 0) {{
+        fprintf(stderr, "Runtime error: pop from an empty vec\n");
+        exit(1);
+    }}
+    return v->data[--v->len];
+}}
+
+t_0CD {len}({v} v) {{ // len
+    return v.len;
+}}
+
+{e} {get}({v} v, t_0CD i) {{ // get
+    if (i < 0 || i >= v.len) {{
+        fprintf(stderr, "Runtime error: index %d out of bounds for vec of len %d\n", i, v.len);
+        exit(1);
+    }}
+    return v.data[i];
+}}
+
+t_1CD {set}({v}* v, t_0CD i, {p} x) {{ // set
+    if (i < 0 || i >= v->len) {{
+        fprintf(stderr, "Runtime error: index %d out of bounds for vec of len %d\n", i, v->len);
+        exit(1);
+    }}
+    v->data[i] = {x};
+}}
+"#,
+            v = name,
+            e = elem,
+            p = elem_param,
+            x = elem_value,
+        ));
+
+        self.emit_checked_index(name, &elem, ctx);
+    }
+
+    /// Emits the C instances of the operators and print for a number type. Those of i32 and f64
+    /// are always part of the program, so this is only needed for the other sizes.
+    fn emit_number_type(&mut self, ty: &Type, name: &str, ctx: &mut CodeGenContext, span: Span) {
+        if !self.ref_typedefs_emitted.insert(name.to_string()) {
+            return;
+        }
+
+        let print_args = match ty.name() {
+            "i8" | "i16" => "\"%d\", n",
+            "u8" | "u16" | "u32" => "\"%u\", n",
+            "i64" => "\"%lld\", (long long)n",
+            "u64" => "\"%llu\", (unsigned long long)n",
+            _ => "\"%.6f\", n",
+        };
+        let print = self.c_func_instance_name("_print", std::slice::from_ref(ty), span);
+        let mut declarations = format!("t_1CD {}({} n);\n", print, name);
+        let mut bodies = format!(
+            "\nt_1CD {}({} n) {{ // print {}\n    printf({});\n    fflush(stdout);\n}}\n",
+            print, name, ty, print_args
+        );
+
+        let returning = |value: &str| format!("    return {};\n", value);
+        let pow = if ty.is_float() {
+            returning("powf(n1, n2)")
+        } else {
+            // by squaring, as going through a double would lose the precision of 64-bit integers
+            format!(
+                "    {} result = 1;\n    for (; n2 > 0; n2 /= 2) {{\n        if (n2 % 2) result *= n1;\n        n1 *= n1;\n    }}\n    return result;\n",
+                name
+            )
+        };
+        let modulo = if ty.is_float() {
+            "fmodf(n1, n2)"
+        } else {
+            "n1 % n2"
+        };
+        let operators = [
+            ("_add", name, returning("n1 + n2")),
+            ("_sub", name, returning("n1 - n2")),
+            ("_mult", name, returning("n1 * n2")),
+            ("_div", name, returning("n1 / n2")),
+            ("_mod", name, returning(modulo)),
+            ("_pow", name, pow),
+            ("_equal", "t_3CD", returning("n1 == n2")),
+            ("_bang_equal", "t_3CD", returning("n1 != n2")),
+            ("_less", "t_3CD", returning("n1 < n2")),
+            ("_less_equal", "t_3CD", returning("n1 <= n2")),
+            ("_greater", "t_3CD", returning("n1 > n2")),
+            ("_greater_equal", "t_3CD", returning("n1 >= n2")),
+        ];
+        for (operator, return_type, body) in operators.iter() {
+            let func = self.c_func_instance_name(operator, std::slice::from_ref(ty), span);
+            let signature = format!("{} {}({t} n1, {t} n2)", return_type, func, t = name);
+            declarations.push_str(&format!("{};\n", signature));
+            bodies.push_str(&format!(
+                "\n{} {{ // {} {}\n{}}}\n",
+                signature,
+                &operator[1..],
+                ty,
+                body
+            ));
+        }
+
+        if ty.is_integer() {
+            let (bitwise_declarations, bitwise_bodies) = self.bitwise_instances(ty, name, span);
+            declarations.push_str(&bitwise_declarations);
+            bodies.push_str(&bitwise_bodies);
+        }
+
+        ctx.declarations.push_str(&declarations);
+        ctx.unnamed.push_str(&bodies);
+    }
+
+    /// Returns the declarations and bodies of the C instances of the bitwise operators for an
+    /// integer type.
+    fn bitwise_instances(&mut self, ty: &Type, name: &str, span: Span) -> (String, String) {
+        // through uint64_t, as shifting a negative number left is undefined in C
+        let shl = format!("({})((uint64_t)n1 << n2)", name);
+        let operators = [
+            ("_bit_and", "n1 & n2"),
+            ("_bit_or", "n1 | n2"),
+            ("_xor", "n1 ^ n2"),
+            ("_shl", shl.as_str()),
+            ("_shr", "n1 >> n2"),
+        ];
+        let mut declarations = String::new();
+        let mut bodies = String::new();
+        for (operator, value) in operators {
+            let func = self.c_func_instance_name(operator, std::slice::from_ref(ty), span);
+            let signature = format!("{t} {}({t} n1, {t} n2)", func, t = name);
+            declarations.push_str(&format!("{};\n", signature));
+            bodies.push_str(&format!(
+                "\n{} {{ // {} {}\n    return {};\n}}\n",
+                signature,
+                &operator[1..],
+                ty,
+                value
+            ));
+        }
+
+        let bit_not = self.c_func_instance_name("_bit_not", std::slice::from_ref(ty), span);
+        declarations.push_str(&format!("{t} {}({t} n);\n", bit_not, t = name));
+        bodies.push_str(&format!(
+            "\n{t} {}({t} n) {{ // bit_not {}\n    return ~n;\n}}\n",
+            bit_not,
+            ty,
+            t = name
+        ));
+
+        (declarations, bodies)
+    }
+
+    /// Emits the struct typedef of str along with the str builtins and overloads.\
+    /// Format: typedef struct { t_4CD* data; t_0CD len; t_0CD cap; } t_9CD;\
+    /// Like a vec, a str owns a heap buffer that grows in place when appended to. It is not
+    /// '\0'-terminated.
+    fn emit_str_type(&mut self, name: &str, ctx: &mut CodeGenContext, span: Span) {
+        if !self.ref_typedefs_emitted.insert(name.to_string()) {
+            return;
+        }
+
+        ctx.types.push_str(&format!(
+            "typedef struct {{ t_4CD* data; t_0CD len; t_0CD cap; }} {};\n",
             name
+        ));
+
+        let str_type = Type::simple("str");
+        let with_str = |rest: &str| vec![str_type.clone(), Type::simple(rest)];
+        let to_str = self.c_func_instance_name("to_str", &[], span);
+        let slice = self.c_func_instance_name("slice", &[], span);
+        let drop = self.c_func_instance_name("drop", &[], span);
+        let print = self.c_func_instance_name("_print", std::slice::from_ref(&str_type), span);
+        let len = self.c_func_instance_name("len", std::slice::from_ref(&str_type), span);
+        let add = self.c_func_instance_name("_add", std::slice::from_ref(&str_type), span);
+        let add_strlit = self.c_func_instance_name("_add", &with_str("strlit"), span);
+        let add_char = self.c_func_instance_name("_add", &with_str("char"), span);
+        let equal = self.c_func_instance_name("_equal", std::slice::from_ref(&str_type), span);
+        let bang_equal =
+            self.c_func_instance_name("_bang_equal", std::slice::from_ref(&str_type), span);
+
+        ctx.declarations.push_str(&format!(
+            "{s} {s}_from(const char* src, t_0CD n);\n\
+             t_1CD {s}_append_{s}({s}* s, {s} x);\n\
+             t_1CD {s}_append_t_6CD({s}* s, t_6CD x);\n\
+             t_1CD {s}_append_t_4CD({s}* s, t_4CD x);\n\
+             {s} {to_str}(t_6CD s);\n\
+             {s} {slice}({s} s, t_0CD start, t_0CD n);\n\
+             t_1CD {drop}({s} s);\n\
+             t_1CD {print}({s} s);\n\
+             t_0CD {len}({s} s);\n\
+             {s} {add}({s} a, {s} b);\n\
+             {s} {add_strlit}({s} a, t_6CD b);\n\
+             {s} {add_char}({s} a, t_4CD b);\n\
+             t_3CD {equal}({s} a, {s} b);\n\
+             t_3CD {bang_equal}({s} a, {s} b);\n",
+            s = name,
+        ));
+
+        ctx.unnamed.push_str(&format!(
+            r#"
+{s} {s}_from(const char* src, t_0CD n) {{ // str from chars
+    {s} s = {{ malloc(n ? n : 1), n, n }};
+    memcpy(s.data, src, n);
+    return s;
+}}
+
+static t_1CD {s}_reserve({s}* s, t_0CD extra) {{ // make room for extra chars
+    if (s->len + extra > s->cap) {{
+        s->cap = s->cap * 2 > s->len + extra ? s->cap * 2 : s->len + extra;
+        s->data = realloc(s->data, s->cap);
+    }}
+}}
+
+t_1CD {s}_append_{s}({s}* s, {s} x) {{ // append str
+    t_0CD same = x.data == s->data;
+    {s}_reserve(s, x.len);
+    memcpy(s->data + s->len, same ? s->data : x.data, x.len);
+    s->len += x.len;
+}}
+
+t_1CD {s}_append_t_6CD({s}* s, t_6CD x) {{ // append strlit
+    t_0CD n = strlen(x);
+    {s}_reserve(s, n);
+    memcpy(s->data + s->len, x, n);
+    s->len += n;
+}}
+
+t_1CD {s}_append_t_4CD({s}* s, t_4CD x) {{ // append char
+    {s}_reserve(s, 1);
+    s->data[s->len++] = x;
+}}
+
+{s} {to_str}(t_6CD s) {{ // to_str
+    return {s}_from(s, strlen(s));
+}}
+
+{s} {slice}({s} s, t_0CD start, t_0CD n) {{ // slice
+    if (start < 0 || n < 0 || start + n > s.len) {{
+        fprintf(stderr, "Runtime error: slice of %d chars at %d out of bounds for str of len %d\n", n, start, s.len);
+        exit(1);
+    }}
+    return {s}_from(s.data + start, n);
+}}
+
+t_1CD {drop}({s} s) {{ // drop
+    free(s.data);
+}}
+
+t_1CD {print}({s} s) {{ // print str
+    printf("%.*s", s.len, s.data);
+    fflush(stdout);
+}}
+
+t_0CD {len}({s} s) {{ // len str
+    return s.len;
+}}
+
+{s} {add}({s} a, {s} b) {{ // add str
+    {s} r = {s}_from(a.data, a.len);
+    {s}_append_{s}(&r, b);
+    return r;
+}}
+
+{s} {add_strlit}({s} a, t_6CD b) {{ // add str+strlit
+    {s} r = {s}_from(a.data, a.len);
+    {s}_append_t_6CD(&r, b);
+    return r;
+}}
+
+{s} {add_char}({s} a, t_4CD b) {{ // add str+char
+    {s} r = {s}_from(a.data, a.len);
+    {s}_append_t_4CD(&r, b);
+    return r;
+}}
+
+t_3CD {equal}({s} a, {s} b) {{ // equal str
+    return a.len == b.len && memcmp(a.data, b.data, a.len) == 0;
+}}
+
+t_3CD {bang_equal}({s} a, {s} b) {{ // bang_equal str
+    return !{equal}(a, b);
+}}
+"#,
+            s = name,
+        ));
+
+        self.emit_checked_index(name, "t_4CD", ctx);
+    }
+
+    /// Emits the struct typedef of a fixed-size arr<T> along with its len instance.\
+    /// Format: typedef struct { T* data; t_0CD len; } t_1C{T}D;
+    fn emit_arr_type(&mut self, ty: &Type, name: &str, ctx: &mut CodeGenContext, span: Span) {
+        if !self.ref_typedefs_emitted.insert(name.to_string()) {
+            return;
         }
+
+        let elem = self.c_type_name(&ty.generics()[0], ctx, span);
+        ctx.types.push_str(&format!(
+            "typedef struct {{ {}* data; t_0CD len; }} {};\n",
+            elem, name
+        ));
+
+        let len = self.c_func_instance_name("len", std::slice::from_ref(ty), span);
+
+        ctx.declarations.push_str(&format!(
+            "{a} {a}_from({e}* src, t_0CD n);\n\
+             t_0CD {len}({a} a);\n",
+            a = name,
+            e = elem,
+        ));
+
+        ctx.unnamed.push_str(&format!(
+            r#"
+{a} {a}_from({e}* src, t_0CD n) {{ // array literal
+    {a} a = {{ malloc(sizeof({e}) * n), n }};
+    memcpy(a.data, src, sizeof({e}) * n);
+    return a;
+}}
+
+t_0CD {len}({a} a) {{ // len
+    return a.len;
+}}
+"#,
+            a = name,
+            e = elem,
+        ));
+
+        self.emit_checked_index(name, &elem, ctx);
     }
 
+    /// Emits the struct typedef of a res<T> along with the C instances of the res builtins.\
+    /// Format: typedef struct { t_3CD ok; T value; t_6CD err; } t_10C{T}D;\
+    /// `err` is only set when `ok` is false. A res<[]> has no value.
+    fn emit_res_type(&mut self, ty: &Type, name: &str, ctx: &mut CodeGenContext, span: Span) {
+        if !self.ref_typedefs_emitted.insert(name.to_string()) {
+            return;
+        }
+
+        let elem_type = ty.generics()[0].clone();
+        let elem = self.c_type_name(&elem_type, ctx, span);
+        let has_value = elem_type != nil_type();
+        let value_member = if has_value {
+            format!("{} value; ", elem)
+        } else {
+            String::new()
+        };
+        ctx.types.push_str(&format!(
+            "typedef struct {{ t_3CD ok; {}t_6CD err; }} {};\n",
+            value_member, name
+        ));
+
+        let is_ok = self.c_func_instance_name("is_ok", std::slice::from_ref(ty), span);
+        let unwrap = self.c_func_instance_name("unwrap", std::slice::from_ref(ty), span);
+        let err_msg = self.c_func_instance_name("err_msg", std::slice::from_ref(ty), span);
+
+        ctx.declarations.push_str(&format!(
+            "t_3CD {is_ok}({r} r);\n\
+             {e} {unwrap}({r} r);\n\
+             t_6CD {err_msg}({r} r);\n",
+            r = name,
+            e = elem,
+        ));
+
+        ctx.unnamed.push_str(&format!(
+            r#"
+t_3CD {is_ok}({r} r) {{ // is_ok
+    return r.ok;
+}}
+
+{e} {unwrap}({r} r) {{ // unwrap
+    if (!r.ok) {{
+        fprintf(stderr, "Runtime error: unwrapped an err: %s\n", r.err);
+        exit(1);
+    }}
+    {ret}
+}}
+
+t_6CD {err_msg}({r} r) {{ // err_msg
+    return r.ok ? "" : r.err;
+}}
+"#,
+            r = name,
+            e = elem,
+            ret = if has_value { "return r.value;" } else { "return;" },
+        ));
+
+        // the fallible input builtins are emitted with the res of what they read
+        let (input, read) = match elem_type.to_string().as_str() {
+            "i32" => ("try_intput", TRY_INTPUT),
+            "f64" => ("try_fput", TRY_FPUT),
+            "strlit" => ("try_strput", TRY_STRPUT),
+            _ => return,
+        };
+        let input_name = self.c_func_instance_name(input, &[], span);
+        ctx.declarations
+            .push_str(&format!("{} {}();\n", name, input_name));
+        ctx.unnamed.push_str(&format!(
+            "\n{r} {f}() {{ // {input}\n{body}}}\n",
+            r = name,
+            f = input_name,
+            input = input,
+            body = read.replace("RES", name),
+        ));
+    }
+
+    /// Emits the struct of an instance of a generic class, with its generic types substituted.\
+    /// Format: struct t_{id}C{generic types}D { ... };
+    fn emit_class_instance(&mut self, ty: &Type, name: &str, ctx: &mut CodeGenContext, span: Span) {
+        if !self.ref_typedefs_emitted.insert(name.to_string()) {
+            return;
+        }
+
+        if !self.is_class(ty) {
+            let expected = self.generic_classes[ty.name()].0.generics().len();
+            error(
+                span,
+                &format!(
+                    "Class '{}' takes {} generic types, found '{}'",
+                    ty.name(),
+                    expected,
+                    ty
+                ),
+                "transpiling",
+            );
+            return;
+        }
+
+        ctx.include.push_str(&format!("struct {};\n", name));
+        ctx.types
+            .push_str(&format!("\ntypedef struct {} {};\n", name, name));
+
+        let members = self.members[ty].0.clone();
+        let mut def = format!("struct {} {{\n", name);
+        for (member, member_type) in members.iter() {
+            let member_type_name = self.c_type_name(member_type, ctx, span);
+            let member_name = self.c_member_name(ty, member, span);
+            def.push_str(&format!("{} {};\n", member_type_name, member_name));
+        }
+        def.push_str("};\n");
+        ctx.types.push_str(&def);
+    }
+
+    /// Emits `{seq}_at`, which indexing goes through while the DEBUG constant is true so that
+    /// out of bounds accesses report where they happened. Nothing is emitted without DEBUG.
+    fn emit_checked_index(&mut self, name: &str, elem: &str, ctx: &mut CodeGenContext) {
+        if !self.var_exists("DEBUG") {
+            return;
+        }
+        let debug = self.c_var_name("DEBUG", Span::empty());
+
+        ctx.declarations.push_str(&format!(
+            "{e}* {s}_at({s} s, t_0CD i, const char* file, t_0CD line);\n",
+            s = name,
+            e = elem,
+        ));
+
+        ctx.unnamed.push_str(&format!(
+            r#"
+{e}* {s}_at({s} s, t_0CD i, const char* file, t_0CD line) {{ // checked index
+    if ({debug} && (i < 0 || i >= s.len)) {{
+        fprintf(stderr, "Runtime error: index %d out of bounds for length %d (%s:%d)\n", i, s.len, file, line);
+        exit(1);
+    }}
+    return &s.data[i];
+}}
+"#,
+            s = name,
+            e = elem,
+        ));
+    }
+
+    /// Registers a function type and emits its typedef.\
+    /// Format: typedef struct { R (*fn)(void*, A...); void* env; } t_5C{A..._R}D;\
+    /// A function value is a C function along with the environment it is called with, which is
+    /// NULL unless it is a lambda that captures variables.
     pub fn add_func_type(
         &mut self,
         ret_type: Type,
@@ -807,41 +1972,29 @@ impl CompileTimeEnv {
         gens.push(ret_type.clone());
         let func_type = Type::with_generics("func", gens);
 
-        if self.get_type_id(&func_type).is_some() {
+        // Registering another function type also registers this one if it is nested in it,
+        // so check the emitted typedefs rather than the registered types.
+        let func_type_name = self.c_type_name_raw(&func_type, span);
+        if !self.ref_typedefs_emitted.insert(func_type_name.clone()) {
             return;
         }
 
         self.register_type(func_type.clone());
 
-        let func_type_name = self.c_type_name(&func_type, ctx, span);
         let ret_type_name = self.c_type_name(&ret_type, ctx, span);
 
         // Generate the typedef for the function type
-        let mut buf = String::from("typedef ");
+        let mut buf = String::from("typedef struct { ");
         buf.push_str(&ret_type_name);
-        buf.push_str(&format!("(*{})", func_type_name));
-        buf.push('(');
+        buf.push_str("(*fn)(void*");
         for ty in arg_types.iter() {
-            buf.push_str(&self.c_type_name(ty, ctx, span));
             buf.push(',');
+            buf.push_str(&self.c_type_name(ty, ctx, span));
         }
-        if arg_types.len() >= 1 {
-            buf.pop();
-        }
-        buf.push_str(");\n");
+        buf.push_str(&format!("); void* env; }} {};\n", func_type_name));
         ctx.types.push_str(&buf);
     }
 
-    pub fn push_this(&mut self, this: &str) {
-        self.these.push(this.to_string());
-    }
-    pub fn pop_this(&mut self) {
-        self.these.pop();
-    }
-    pub fn this(&self) -> &str {
-        &self.these.last().unwrap()
-    }
-
     pub fn del_var(&mut self, name: &str) {
         for scope in self.scopes.iter_mut().rev() {
             if let Some(_id) = scope.get(name) {
@@ -851,7 +2004,8 @@ impl CompileTimeEnv {
     }
 
     /// Returns true if the type is a registered class (struct type)
-    pub fn is_class(&self, ty: &Type) -> bool {
+    pub fn is_class(&mut self, ty: &Type) -> bool {
+        self.register_class_instance(ty);
         self.members.contains_key(ty)
     }
 
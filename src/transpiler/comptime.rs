@@ -0,0 +1,776 @@
+use crate::error;
+use crate::expr::Expr;
+use crate::runtime_env::RuntimeEnvironment;
+use crate::span::Span;
+use crate::type_env::{Type, nil_type};
+use crate::value::{Func, Value, literal_val, nil, seq_val};
+use std::ops::ControlFlow;
+
+/// The most loop iterations and `comp fn` calls one compile-time evaluation can make, so that one
+/// that never ends is stopped with an error
+const FUEL: usize = 1_000_000;
+/// The most `comp fn` calls that can be evaluated one inside the other
+const MAX_CALL_DEPTH: usize = 100;
+
+impl Expr {
+    /// Evaluates a `comp` expression or a call to a `comp fn` inside the compiler, see evaluate.
+    /// It is stopped with an error if it takes too long or recurses too deeply.
+    pub fn evaluate_comp(&self, env: &mut RuntimeEnvironment) -> Value {
+        env.fuel = FUEL;
+        env.call_depth = 0;
+        self.evaluate(env)
+    }
+
+    /// Evaluates an expression inside the compiler, for `comp`.\
+    /// Only literals, operators, control flow, `len`/`push`, and the variables and `comp fn`s
+    /// known at compile time can be evaluated.
+    /// The larger cases are in functions of their own, which keeps the stack frame of this
+    /// recursive function small.
+    pub fn evaluate(&self, env: &mut RuntimeEnvironment) -> Value {
+        match self {
+            Expr::Int(n) => literal_val("i32".into(), n.to_string()),
+            Expr::Float(n) => literal_val("f64".into(), n.to_string()),
+            Expr::Number(digits, ty) => literal_val(ty.clone(), digits.clone()),
+            Expr::Bool(b) => bool_val(*b),
+            Expr::Str(s) => literal_val("strlit".into(), s.clone()),
+            Expr::Char(c) => literal_val("char".into(), c.clone()),
+            Expr::Vector(exprs) | Expr::Array(exprs) => {
+                sequence(env, exprs, matches!(self, Expr::Vector(_)))
+            }
+
+            Expr::Add(l, r, span)
+            | Expr::Sub(l, r, span)
+            | Expr::Mult(l, r, span)
+            | Expr::Div(l, r, span)
+            | Expr::Mod(l, r, span)
+            | Expr::Power(l, r, span)
+            | Expr::Less(l, r, span)
+            | Expr::LessEqual(l, r, span)
+            | Expr::Greater(l, r, span)
+            | Expr::GreaterEqual(l, r, span)
+            | Expr::ShiftLeft(l, r, span)
+            | Expr::ShiftRight(l, r, span) => {
+                let (l, r) = (l.evaluate(env), r.evaluate(env));
+                binary(self, l, r, *span)
+            }
+            Expr::EqualEqual(l, r, _) => bool_val(values_equal(&l.evaluate(env), &r.evaluate(env))),
+            Expr::BangEqual(l, r, _) => bool_val(!values_equal(&l.evaluate(env), &r.evaluate(env))),
+            Expr::And(l, r, span) | Expr::Or(l, r, span) | Expr::Xor(l, r, span) => {
+                logical(self, env, l, r, *span)
+            }
+            Expr::Negate(expr, span) => negate(env, expr, *span),
+            Expr::Not(expr, span) => not(env, expr, *span),
+            // values known at compile time have no address, a reference is the value itself
+            Expr::Ref(expr, _) | Expr::Deref(expr, _) => expr.evaluate(env),
+            Expr::Cast(expr, ty, span) => cast(expr.evaluate(env), ty, *span),
+
+            Expr::Nth(seq, index, span) => nth(env, seq, index, *span),
+
+            Expr::Variable(name, span) => variable(env, name, *span),
+            Expr::Declare(name, var_type, expr, is_mutable, _) => {
+                declare(env, name, var_type, expr, *is_mutable)
+            }
+            // the target of a compound assignment has no side effects at compile time
+            Expr::CompoundAssign(target, value, span) | Expr::Assign(target, value, span) => {
+                assign(env, target, value, *span)
+            }
+
+            Expr::Discard(expr) => {
+                let value = expr.evaluate(env);
+                if value.is_return || value.loop_jump.is_some() {
+                    value
+                } else {
+                    nil()
+                }
+            }
+            Expr::Stmt(expr) | Expr::Block(expr) | Expr::Comp(expr) => expr.evaluate(env),
+            Expr::StmtBlock(exprs, _) => evaluate_block(exprs, env),
+            Expr::StmtBlockWithScope(exprs, _) => {
+                env.push_scope();
+                let value = evaluate_block(exprs, env);
+                env.pop_scope();
+                value
+            }
+            Expr::Return(expr, _) => {
+                let mut value = expr.evaluate(env);
+                value.is_return = true;
+                value
+            }
+
+            Expr::If(cond, block, else_block, _) => {
+                if cond.evaluate(env).is_true() {
+                    block.evaluate(env)
+                } else if let Some(else_block) = else_block {
+                    else_block.evaluate(env)
+                } else {
+                    nil()
+                }
+            }
+            Expr::While(cond, block, label, span) => {
+                while cond.evaluate(env).is_true() && use_fuel(env, *span) {
+                    if let ControlFlow::Break(value) = next_iteration(block.evaluate(env), label) {
+                        return value;
+                    }
+                }
+                nil()
+            }
+            Expr::Break(..) | Expr::Continue(..) => {
+                let mut value = nil();
+                value.loop_jump = Some(Box::new(self.clone()));
+                value
+            }
+            Expr::For(name, looper, block, label, span) => {
+                for_loop(env, name, looper, block, label, *span)
+            }
+
+            Expr::CallFunc(name, _, args, span) => call_func(env, name, args, *span),
+
+            _ => {
+                error(
+                    self.get_span(),
+                    "This expression cannot be evaluated at compile time",
+                    "interpreting",
+                );
+                nil()
+            }
+        }
+    }
+}
+
+/// Evaluates `seq[index]`.
+fn nth(env: &mut RuntimeEnvironment, seq: &Expr, index: &Expr, span: Span) -> Value {
+    let seq = seq.evaluate(env);
+    let index = as_i32(&index.evaluate(env), span);
+    let items = items(&seq, span);
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| items.get(i).cloned())
+        .unwrap_or_else(|| {
+            error(
+                span,
+                &format!("Index {} out of bounds for length {}", index, items.len()),
+                "interpreting",
+            );
+            nil()
+        })
+}
+
+/// Declares a variable known at compile time.
+fn declare(
+    env: &mut RuntimeEnvironment,
+    name: &str,
+    var_type: &Option<Type>,
+    expr: &Option<Box<Expr>>,
+    is_mutable: bool,
+) -> Value {
+    let mut value = match (expr, var_type) {
+        (Some(expr), _) => expr.evaluate(env),
+        (None, Some(var_type)) => default_value(var_type),
+        (None, None) => nil(),
+    };
+    // an empty literal takes its element type from the declared type
+    if let Some(var_type) = var_type {
+        value.value_type = var_type.clone();
+    }
+    env.declare(name.to_string(), value, is_mutable);
+    nil()
+}
+
+/// Assigns to a variable known at compile time, or to one of its elements.
+fn assign(env: &mut RuntimeEnvironment, target: &Expr, value: &Expr, span: Span) -> Value {
+    let value = value.evaluate(env);
+    match target {
+        Expr::Variable(name, _) => env.assign(name, value, span),
+        Expr::Nth(seq, index, _) => {
+            let index = as_i32(&index.evaluate(env), span);
+            let Some(seq) = variable_mut(env, seq, span) else {
+                return nil();
+            };
+            let len = seq.value_vec.as_ref().map_or(0, |items| items.len());
+            match (seq.value_vec.as_mut(), usize::try_from(index)) {
+                (Some(items), Ok(i)) if i < len => items[i] = value,
+                _ => error(
+                    span,
+                    &format!("Index {} out of bounds for length {}", index, len),
+                    "interpreting",
+                ),
+            }
+        }
+        _ => error(
+            span,
+            "Only variables and their elements can be assigned at compile time",
+            "interpreting",
+        ),
+    }
+    nil()
+}
+
+/// Evaluates a `for` loop over a number or over the items of a sequence.
+fn for_loop(
+    env: &mut RuntimeEnvironment,
+    name: &str,
+    looper: &Expr,
+    block: &Expr,
+    label: &Option<String>,
+    span: Span,
+) -> Value {
+    let looper = looper.evaluate(env);
+    let items = if looper.value_type.is_integer() {
+        (0..as_integer(&looper, span))
+            .map(|i| literal_val(looper.value_type.clone(), i.to_string()))
+            .collect()
+    } else {
+        items(&looper, span)
+    };
+    for item in items {
+        if !use_fuel(env, span) {
+            break;
+        }
+        env.push_scope();
+        env.declare(name.to_string(), item, false);
+        let value = block.evaluate(env);
+        env.pop_scope();
+        if let ControlFlow::Break(value) = next_iteration(value, label) {
+            return value;
+        }
+    }
+    nil()
+}
+
+/// Evaluates a call to `len`, `push` or a `comp fn`.
+fn call_func(env: &mut RuntimeEnvironment, name: &str, args: &[Box<Expr>], span: Span) -> Value {
+    match name {
+        "len" if args.len() == 1 => {
+            let seq = args[0].evaluate(env);
+            literal_val("i32".into(), items(&seq, span).len().to_string())
+        }
+        "push" if args.len() == 2 => {
+            let item = args[1].evaluate(env);
+            if let Some(seq) = variable_mut(env, &args[0], span)
+                && let Some(items) = seq.value_vec.as_mut()
+            {
+                items.push(item);
+            }
+            nil()
+        }
+        _ => match is_known(env, name).then(|| env.get(name, span).value.body) {
+            Some(Some(func)) => {
+                let args = args.iter().map(|a| a.evaluate(env)).collect();
+                call(env, name, func, args, span)
+            }
+            _ => {
+                error(
+                    span,
+                    &format!(
+                        "'{}' is not a 'comp fn', so it cannot be called at compile time",
+                        name
+                    ),
+                    "interpreting",
+                );
+                nil()
+            }
+        },
+    }
+}
+
+/// Evaluates a vector or array literal.
+fn sequence(env: &mut RuntimeEnvironment, exprs: &[Expr], is_vector: bool) -> Value {
+    let items: Vec<Value> = exprs.iter().map(|e| e.evaluate(env)).collect();
+    let elem_type = items
+        .first()
+        .map_or_else(nil_type, |v| v.value_type.clone());
+    let seq = if is_vector { "vec" } else { "arr" };
+    seq_val(Type::with_generics(seq, vec![elem_type]), items)
+}
+
+/// Evaluates `&&`, `||` or `^`, which are bitwise on integers.
+fn logical(expr: &Expr, env: &mut RuntimeEnvironment, l: &Expr, r: &Expr, span: Span) -> Value {
+    let l = l.evaluate(env);
+    if l.value_type.is_integer() {
+        return binary(expr, l, r.evaluate(env), span);
+    }
+    // like at runtime, the right side is only evaluated if it decides the result
+    bool_val(match expr {
+        Expr::And(..) => l.is_true() && r.evaluate(env).is_true(),
+        Expr::Or(..) => l.is_true() || r.evaluate(env).is_true(),
+        _ => l.is_true() != r.evaluate(env).is_true(),
+    })
+}
+
+/// Evaluates `-expr`.
+fn negate(env: &mut RuntimeEnvironment, expr: &Expr, span: Span) -> Value {
+    let value = expr.evaluate(env);
+    let ty = value.value_type.clone();
+    if ty.is_float() {
+        return float_val(&ty, -as_f64(&value, span));
+    }
+    let n = wrap_integer(-as_integer(&value, span), &ty);
+    literal_val(ty, n.to_string())
+}
+
+/// Evaluates `!expr`, which is bitwise on integers.
+fn not(env: &mut RuntimeEnvironment, expr: &Expr, span: Span) -> Value {
+    let value = expr.evaluate(env);
+    if value.value_type.is_integer() {
+        let n = !as_integer(&value, span);
+        return literal_val(
+            value.value_type.clone(),
+            wrap_integer(n, &value.value_type).to_string(),
+        );
+    }
+    bool_val(value.is_false())
+}
+
+/// Evaluates a variable known at compile time.
+fn variable(env: &mut RuntimeEnvironment, name: &str, span: Span) -> Value {
+    if !is_known(env, name) {
+        error(
+            span,
+            &format!("'{}' is not known at compile time", name),
+            "interpreting",
+        );
+        return nil();
+    }
+    env.get(name, span).value
+}
+
+/// Converts a value computed at compile time into the literal that is emitted for it.
+pub fn value_to_expr(value: &Value, span: Span) -> Expr {
+    let items = || {
+        value
+            .value_vec
+            .iter()
+            .flatten()
+            .map(|v| value_to_expr(v, span))
+            .collect()
+    };
+    match value.value_type.name() {
+        "i32" => Expr::Int(value.value.parse().unwrap_or(0)),
+        "f64" => Expr::Float(value.value.parse().unwrap_or(0.0)),
+        _ if value.value_type.is_integer() || value.value_type.is_float() => {
+            Expr::Number(value.value.clone(), value.value_type.clone())
+        }
+        "bool" => Expr::Bool(value.is_true()),
+        "char" => Expr::Char(value.value.clone()),
+        "strlit" => Expr::Str(value.value.clone()),
+        "vec" => Expr::Vector(items()),
+        "arr" => Expr::Array(items()),
+        _ => {
+            error(
+                span,
+                &format!(
+                    "A value of type '{}' cannot be computed at compile time",
+                    value.value_type
+                ),
+                "interpreting",
+            );
+            Expr::Nothing()
+        }
+    }
+}
+
+/// Evaluates the statements of a block. Its value is that of its last statement, unless a `ret`,
+/// `break` or `continue` stops it early.
+fn evaluate_block(exprs: &[Box<Expr>], env: &mut RuntimeEnvironment) -> Value {
+    let mut value = nil();
+    for expr in exprs {
+        value = expr.evaluate(env);
+        if value.is_return || value.loop_jump.is_some() {
+            break;
+        }
+    }
+    value
+}
+
+/// Decides whether a loop goes on after its body evaluated to `value`, or stops with the value
+/// it evaluates to: nothing after a `break` of its own, and the value itself for a `ret` or a
+/// jump out to an outer loop.
+fn next_iteration(value: Value, label: &Option<String>) -> ControlFlow<Value> {
+    let is_own = |jump_label: &Option<String>| jump_label.is_none() || jump_label == label;
+    match value.loop_jump.as_deref() {
+        _ if value.is_return => ControlFlow::Break(value),
+        None => ControlFlow::Continue(()),
+        Some(Expr::Continue(jump_label, _)) if is_own(jump_label) => ControlFlow::Continue(()),
+        Some(Expr::Break(jump_label, _)) if is_own(jump_label) => ControlFlow::Break(nil()),
+        Some(_) => ControlFlow::Break(value),
+    }
+}
+
+/// Calls a `comp fn`. Its body only sees the globals and its own parameters, not the locals of
+/// its caller.
+fn call(
+    env: &mut RuntimeEnvironment,
+    name: &str,
+    func: Func,
+    args: Vec<Value>,
+    span: Span,
+) -> Value {
+    if args.len() != func.args.len() {
+        error(
+            span,
+            &format!(
+                "'{}' takes {} arguments but was given {}",
+                name,
+                func.args.len(),
+                args.len()
+            ),
+            "interpreting",
+        );
+        return nil();
+    }
+    if !use_fuel(env, span) {
+        return nil();
+    }
+    if env.call_depth == MAX_CALL_DEPTH {
+        error(
+            span,
+            &format!(
+                "Calling '{}' at compile time goes more than {} calls deep",
+                name, MAX_CALL_DEPTH
+            ),
+            "interpreting",
+        );
+        // the rest of the evaluation is given up on, like when it runs out of fuel
+        env.fuel = 0;
+        return nil();
+    }
+
+    let caller_scopes = env.scopes.split_off(1);
+    env.push_scope();
+    for ((param, _, is_mutable), arg) in func.args.iter().zip(args) {
+        env.declare(param.clone(), arg, *is_mutable);
+    }
+    env.call_depth += 1;
+    let mut value = func.body.evaluate(env);
+    env.call_depth -= 1;
+    env.pop_scope();
+    env.scopes.extend(caller_scopes);
+
+    value.is_return = false;
+    if let Some(jump) = value.loop_jump.take() {
+        error(
+            jump.get_span(),
+            "'break' and 'continue' can only be used inside a loop",
+            "interpreting",
+        );
+    }
+    if func.return_type != nil_type() && value.value_type != func.return_type {
+        error(
+            span,
+            &format!(
+                "'{}' should return '{}' but returned '{}'",
+                name, func.return_type, value.value_type
+            ),
+            "interpreting",
+        );
+    }
+    value
+}
+
+/// Uses up one loop iteration or call of a compile-time evaluation. Returns false, after reporting
+/// it once, if there was none left, so that every loop and call stops.
+fn use_fuel(env: &mut RuntimeEnvironment, span: Span) -> bool {
+    match env.fuel {
+        0 => false,
+        1 => {
+            error(
+                span,
+                &format!(
+                    "Evaluating this at compile time takes too long, it was stopped after {} loop iterations and calls",
+                    FUEL
+                ),
+                "interpreting",
+            );
+            env.fuel = 0;
+            false
+        }
+        _ => {
+            env.fuel -= 1;
+            true
+        }
+    }
+}
+
+/// Applies an arithmetic, bitwise or comparison operator to two values of the same type.
+fn binary(op: &Expr, l: Value, r: Value, span: Span) -> Value {
+    let is_bitwise = matches!(
+        op,
+        Expr::And(..) | Expr::Or(..) | Expr::Xor(..) | Expr::ShiftLeft(..) | Expr::ShiftRight(..)
+    );
+    match (l.value_type.name(), r.value_type.name()) {
+        ("i32", "i32") if !is_bitwise => {
+            let (a, b) = (as_i32(&l, span), as_i32(&r, span));
+            if matches!(op, Expr::Div(..) | Expr::Mod(..)) && b == 0 {
+                error(span, "Division by zero", "interpreting");
+                return nil();
+            }
+            match op {
+                Expr::Add(..) => literal_val("i32".into(), a.wrapping_add(b).to_string()),
+                Expr::Sub(..) => literal_val("i32".into(), a.wrapping_sub(b).to_string()),
+                Expr::Mult(..) => literal_val("i32".into(), a.wrapping_mul(b).to_string()),
+                Expr::Div(..) => literal_val("i32".into(), a.wrapping_div(b).to_string()),
+                Expr::Mod(..) => literal_val("i32".into(), a.wrapping_rem(b).to_string()),
+                Expr::Power(..) => {
+                    literal_val("i32".into(), ((a as f64).powf(b as f64) as i32).to_string())
+                }
+                _ => compare(op, a.cmp(&b)),
+            }
+        }
+        ("f64", "f64") | ("f32", "f32") if !is_bitwise => {
+            let ty = &l.value_type;
+            let (a, b) = (as_f64(&l, span), as_f64(&r, span));
+            match op {
+                Expr::Add(..) => float_val(ty, a + b),
+                Expr::Sub(..) => float_val(ty, a - b),
+                Expr::Mult(..) => float_val(ty, a * b),
+                Expr::Div(..) => float_val(ty, a / b),
+                Expr::Mod(..) => float_val(ty, a % b),
+                Expr::Power(..) => float_val(ty, a.powf(b)),
+                _ => compare(op, a.total_cmp(&b)),
+            }
+        }
+        (left, right) if left == right && l.value_type.is_integer() => {
+            let ty = &l.value_type;
+            let (a, b) = (as_integer(&l, span), as_integer(&r, span));
+            if matches!(op, Expr::Div(..) | Expr::Mod(..)) && b == 0 {
+                error(span, "Division by zero", "interpreting");
+                return nil();
+            }
+            let n = match op {
+                Expr::Add(..) => a + b,
+                Expr::Sub(..) => a - b,
+                Expr::Mult(..) => a.wrapping_mul(b),
+                Expr::Div(..) => a / b,
+                Expr::Mod(..) => a % b,
+                Expr::Power(..) => (0..b).fold(1, |n: i128, _| wrap_integer(n.wrapping_mul(a), ty)),
+                Expr::And(..) => a & b,
+                Expr::Or(..) => a | b,
+                Expr::Xor(..) => a ^ b,
+                Expr::ShiftLeft(..) => {
+                    u32::try_from(b).map_or(0, |b| a.checked_shl(b).unwrap_or(0))
+                }
+                Expr::ShiftRight(..) => a >> b.clamp(0, 127),
+                _ => return compare(op, a.cmp(&b)),
+            };
+            literal_val(ty.clone(), wrap_integer(n, ty).to_string())
+        }
+        ("strlit", "strlit" | "char") if matches!(op, Expr::Add(..)) => {
+            literal_val("strlit".into(), l.value + &r.value)
+        }
+        (left, right) => {
+            error(
+                span,
+                &format!(
+                    "Cannot apply this operator to '{}' and '{}' at compile time",
+                    left, right
+                ),
+                "interpreting",
+            );
+            nil()
+        }
+    }
+}
+
+fn compare(op: &Expr, ordering: std::cmp::Ordering) -> Value {
+    bool_val(match op {
+        Expr::Less(..) => ordering.is_lt(),
+        Expr::LessEqual(..) => ordering.is_le(),
+        Expr::Greater(..) => ordering.is_gt(),
+        _ => ordering.is_ge(),
+    })
+}
+
+fn values_equal(a: &Value, b: &Value) -> bool {
+    a.value_type == b.value_type
+        && a.value == b.value
+        && match (&a.value_vec, &b.value_vec) {
+            (Some(a), Some(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| values_equal(a, b))
+            }
+            (a, b) => a.is_none() && b.is_none(),
+        }
+}
+
+fn bool_val(b: bool) -> Value {
+    literal_val("bool".into(), if b { "`t" } else { "`f" }.to_string())
+}
+
+/// The value a variable declared with only a type starts out with, like in C.
+fn default_value(var_type: &Type) -> Value {
+    match var_type.name() {
+        _ if var_type.is_integer() || var_type.is_float() => {
+            literal_val(var_type.clone(), "0".to_string())
+        }
+        "bool" => bool_val(false),
+        "char" => literal_val(var_type.clone(), "\0".to_string()),
+        "strlit" => literal_val(var_type.clone(), String::new()),
+        _ => seq_val(var_type.clone(), vec![]),
+    }
+}
+
+fn as_i32(value: &Value, span: Span) -> i32 {
+    value.value.parse().unwrap_or_else(|_| {
+        error(
+            span,
+            &format!("Expected 'i32' but got '{}'", value.value_type),
+            "interpreting",
+        );
+        0
+    })
+}
+
+fn as_f64(value: &Value, span: Span) -> f64 {
+    value.value.parse().unwrap_or_else(|_| {
+        error(
+            span,
+            &format!("Expected 'f64' but got '{}'", value.value_type),
+            "interpreting",
+        );
+        0.0
+    })
+}
+
+fn as_integer(value: &Value, span: Span) -> i128 {
+    value.value.parse().unwrap_or_else(|_| {
+        error(
+            span,
+            &format!("Expected an integer but got '{}'", value.value_type),
+            "interpreting",
+        );
+        0
+    })
+}
+
+/// Wraps an integer around to the range of its type, like it would overflow in C.
+fn wrap_integer(n: i128, ty: &Type) -> i128 {
+    let Some((bits, signed)) = ty.integer_bits() else {
+        return n;
+    };
+    let shift = 128 - bits;
+    if signed {
+        (n << shift) >> shift
+    } else {
+        ((n as u128) << shift >> shift) as i128
+    }
+}
+
+fn float_val(ty: &Type, n: f64) -> Value {
+    let value = if ty.name() == "f32" {
+        (n as f32).to_string()
+    } else {
+        n.to_string()
+    };
+    literal_val(ty.clone(), value)
+}
+
+/// Converts a number, char or bool to a number or char type, for `->`.
+fn cast(value: Value, ty: &Type, span: Span) -> Value {
+    let from = &value.value_type;
+    let (int, float) = if from.is_float() {
+        let n = as_f64(&value, span);
+        (n as i128, n)
+    } else {
+        let n = match from.name() {
+            "char" => value.value.chars().next().map_or(0, |c| c as i128),
+            "bool" => value.is_true() as i128,
+            _ if from.is_integer() => as_integer(&value, span),
+            _ => {
+                error(
+                    span,
+                    &format!("Cannot cast '{}' to '{}'", from, ty),
+                    "interpreting",
+                );
+                return nil();
+            }
+        };
+        (n, n as f64)
+    };
+
+    if ty.is_integer() {
+        literal_val(ty.clone(), wrap_integer(int, ty).to_string())
+    } else if ty.is_float() {
+        float_val(ty, float)
+    } else if *ty == Type::simple("char") {
+        let byte = wrap_integer(int, &Type::simple("u8")) as u8;
+        literal_val(ty.clone(), char::from(byte).to_string())
+    } else {
+        error(
+            span,
+            &format!("Cannot cast '{}' to '{}'", from, ty),
+            "interpreting",
+        );
+        nil()
+    }
+}
+
+/// Returns the elements of a vec, arr or strlit value.
+fn items(value: &Value, span: Span) -> Vec<Value> {
+    match value.value_type.name() {
+        "strlit" => value
+            .value
+            .chars()
+            .map(|c| literal_val("char".into(), c.to_string()))
+            .collect(),
+        "vec" | "arr" => value.value_vec.clone().unwrap_or_default(),
+        _ => {
+            error(
+                span,
+                &format!("Cannot take the elements of a '{}'", value.value_type),
+                "interpreting",
+            );
+            vec![]
+        }
+    }
+}
+
+fn is_known(env: &RuntimeEnvironment, name: &str) -> bool {
+    env.scopes.iter().any(|scope| scope.contains_key(name))
+}
+
+/// Returns the value of the variable an expression (like `v` or `&v`) names, to change it. None
+/// if it is immutable.
+fn variable_mut<'a>(
+    env: &'a mut RuntimeEnvironment,
+    expr: &Expr,
+    span: Span,
+) -> Option<&'a mut Value> {
+    let name = match expr {
+        Expr::Variable(name, _) => name,
+        Expr::Ref(expr, _) => return variable_mut(env, expr, span),
+        _ => {
+            error(
+                span,
+                "Only variables can be changed at compile time",
+                "interpreting",
+            );
+            return None;
+        }
+    };
+    let id = env
+        .scopes
+        .iter()
+        .rev()
+        .find_map(|scope| scope.get(name).copied());
+    match id {
+        Some(id) if !env.get_ptr(id).is_mutable => {
+            error(
+                span,
+                &format!(
+                    "Cannot change '{}', it is immutable. Declare it with '#@{}'",
+                    name, name
+                ),
+                "interpreting",
+            );
+            None
+        }
+        Some(id) => Some(&mut env.get_ptr(id).value),
+        None => {
+            error(
+                span,
+                &format!("'{}' is not known at compile time", name),
+                "interpreting",
+            );
+            None
+        }
+    }
+}
@@ -1,39 +1,47 @@
-use crate::expr::{Expr, UseKind};
+use crate::expr::{Expr, MethodKind, UseKind};
 use crate::span::Span;
 use crate::transpiler::code_gen_context::CodeGenContext;
 use crate::transpiler::compiletime_env::CompileTimeEnv;
-use crate::type_env::{nil_type, Type};
+use crate::transpiler::comptime::value_to_expr;
+use crate::type_env::{nil_type, substitute, unify, Type};
 use crate::{error, STD_PATH};
 use std::collections::HashMap;
 use std::path::Path;
 
+/// The name methods declare their `this` parameter with. It can never be written in source.
+const THIS: &str = "%this";
+/// The name of the pointer a compound assignment reaches its target through.
+const TARGET: &str = "%target";
+
 /// Register local variable declarations from a block so they are visible to
 /// returned_type() / get_type() lookups. This only declares variables (no
 /// C code is generated).
-fn declare_locals_from_block(cte: &mut CompileTimeEnv, block: &Expr) {
+pub fn declare_locals_from_block(cte: &mut CompileTimeEnv, block: &Expr) {
     match block {
         Expr::StmtBlock(exprs, _) | Expr::StmtBlockWithScope(exprs, _) => {
             for expr in exprs {
                 // Handle both bare Declare and Declare wrapped in Discard
                 match expr.as_ref() {
-                    Expr::Declare(name, var_type, expr_opt, is_mutable, _) => {
+                    Expr::Declare(name, var_type, expr_opt, is_mutable, span) => {
                         let ty = if let Some(vt) = var_type {
                             vt.clone()
                         } else if let Some(e) = expr_opt {
-                            e.get_type(cte)
+                            cte.checked_type(name, *span)
+                                .unwrap_or_else(|| e.get_type(cte))
                         } else {
                             nil_type()
                         };
                         cte.declare_var(name.clone(), *is_mutable, ty);
                     }
                     Expr::Discard(inner) => {
-                        if let Expr::Declare(name, var_type, expr_opt, is_mutable, _) =
+                        if let Expr::Declare(name, var_type, expr_opt, is_mutable, span) =
                             inner.as_ref()
                         {
                             let ty = if let Some(vt) = var_type {
                                 vt.clone()
                             } else if let Some(e) = expr_opt {
-                                e.get_type(cte)
+                                cte.checked_type(name, *span)
+                                    .unwrap_or_else(|| e.get_type(cte))
                             } else {
                                 nil_type()
                             };
@@ -51,24 +59,1447 @@ fn declare_locals_from_block(cte: &mut CompileTimeEnv, block: &Expr) {
     }
 }
 
+/// Returns the value a block ends with: its last statement, if that has no `;` and is not a
+/// statement in its own right. A trailing if statement with values in each branch counts.
+pub fn trailing_value(block: &Expr) -> Option<Expr> {
+    let (Expr::StmtBlock(exprs, _) | Expr::StmtBlockWithScope(exprs, _)) = block else {
+        return None;
+    };
+    match exprs.last()?.as_ref() {
+        Expr::Stmt(value)
+            if !matches!(value.as_ref(), Expr::Assign(..) | Expr::CompoundAssign(..)) =>
+        {
+            Some(value.as_ref().clone())
+        }
+        if_stmt @ Expr::If(..) => if_expression(if_stmt),
+        match_stmt @ Expr::Match(..) => match_expression(match_stmt),
+        _ => None,
+    }
+}
+
+/// Turns a match statement into a match expression, if every arm ends with a value.
+fn match_expression(match_stmt: &Expr) -> Option<Expr> {
+    let Expr::Match(value, arms, _, span) = match_stmt else {
+        return None;
+    };
+    let arms = arms
+        .iter()
+        .map(|(variant, bindings, block)| {
+            let value = trailing_value(block)?;
+            let Expr::StmtBlockWithScope(exprs, block_span) = block else {
+                return None;
+            };
+            let mut exprs = exprs.clone();
+            **exprs.last_mut().unwrap() = Expr::Stmt(Box::new(value));
+            Some((
+                variant.clone(),
+                bindings.clone(),
+                Expr::StmtBlockWithScope(exprs, *block_span),
+            ))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(Expr::Match(value.clone(), arms, true, *span))
+}
+
+/// Turns an if statement into an if expression, if every branch of its `~?` chain has a `~`.
+fn if_expression(if_stmt: &Expr) -> Option<Expr> {
+    let Expr::If(cond, block, Some(else_block), _) = if_stmt else {
+        return None;
+    };
+    let else_block = match else_block.as_ref() {
+        Expr::If(..) => if_expression(else_block)?,
+        _ => else_block.as_ref().clone(),
+    };
+    Some(Expr::If(
+        cond.clone(),
+        block.clone(),
+        Some(Box::new(else_block)),
+        true,
+    ))
+}
+
+/// Returns a function body that returns its trailing value, so `fn sq(x: i32) i32 { x * x }`
+/// needs no `ret`.
+fn with_implicit_return(cte: &mut CompileTimeEnv, block: &Expr) -> Expr {
+    let (Expr::StmtBlockWithScope(exprs, span), Some(value)) = (block, trailing_value(block))
+    else {
+        return block.clone();
+    };
+    cte.push_scope();
+    declare_locals_from_block(cte, block);
+    let value_type = value.get_type(cte);
+    cte.pop_scope();
+    if value_type == nil_type() {
+        return block.clone();
+    }
+
+    let mut exprs = exprs.clone();
+    **exprs.last_mut().unwrap() = Expr::Return(Box::new(value), *span);
+    Expr::StmtBlockWithScope(exprs, *span)
+}
+
+/// Returns a function body that ends by returning success, for functions that return a res<<[]>>
+/// and so have no value to end with.
+fn with_success_at_end(block: Expr, return_type: &Type) -> Expr {
+    let Expr::StmtBlockWithScope(mut exprs, span) = block else {
+        return block;
+    };
+    let ends_with_return = matches!(exprs.last().map(|e| e.as_ref()), Some(Expr::Return(..)));
+    if *return_type != Type::with_generics("res", vec![nil_type()]) || ends_with_return {
+        return Expr::StmtBlockWithScope(exprs, span);
+    }
+    exprs.push(Box::new(Expr::Return(Box::new(Expr::Nothing()), span)));
+    Expr::StmtBlockWithScope(exprs, span)
+}
+
+/// Emits a value where a value of type `target` is expected. Where that is a res, an `err`
+/// becomes its failure and a value of its success type (or nothing, for a res<<[]>>) becomes its
+/// success.
+fn res_value_to_c(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    expr: &Expr,
+    target: &Type,
+    span: Span,
+) -> bool {
+    if target.name() != "res" {
+        return expr.to_c(cte, ctx);
+    }
+    if let Expr::Err(message, span) = expr {
+        return err_to_c(cte, ctx, message, target, *span);
+    }
+    let value_type = &target.generics()[0];
+    let is_success = if matches!(expr, Expr::Nothing()) {
+        *value_type == nil_type()
+    } else {
+        expr.get_type(cte) == *value_type
+    };
+    if !is_success {
+        return expr.to_c(cte, ctx);
+    }
+
+    let res_name = cte.c_type_name(target, ctx, span);
+    ctx.body.push_str(&format!("({}){{ .ok = true", res_name));
+    if !matches!(expr, Expr::Nothing()) {
+        ctx.body.push_str(", .value = ");
+        expr.to_c(cte, ctx);
+    }
+    ctx.body.push_str(" }");
+    false
+}
+
+/// Emits a failed `res_type` with the given message.
+fn err_to_c(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    message: &Expr,
+    res_type: &Type,
+    span: Span,
+) -> bool {
+    let message_type = message.get_type(cte);
+    if message_type != Type::simple("strlit") {
+        error(
+            span,
+            &format!("The message of an 'err' must be a strlit, found '{}'", message_type),
+            "transpiling",
+        );
+    }
+    let res_name = cte.c_type_name(res_type, ctx, span);
+    ctx.body
+        .push_str(&format!("({}){{ .ok = false, .err = ", res_name));
+    message.to_c(cte, ctx);
+    ctx.body.push_str(" }");
+    false
+}
+
+/// Turns `ret ? c { a } ~ { b }` into `? c { ret a } ~ { ret b }`, so that each branch can be
+/// made into a res on its own.
+fn returning_branches(if_expr: &Expr, span: Span) -> Expr {
+    let Expr::If(cond, block, else_block, _) = if_expr else {
+        return Expr::Return(Box::new(if_expr.clone()), span);
+    };
+    let returning = |branch: &Expr| match branch {
+        Expr::If(..) => returning_branches(branch, span),
+        Expr::StmtBlockWithScope(exprs, block_span) => {
+            let mut exprs = exprs.clone();
+            if let Some(last) = exprs.last_mut()
+                && !matches!(last.as_ref(), Expr::Return(..))
+            {
+                **last = Expr::Return(last.clone(), span);
+            }
+            Expr::StmtBlockWithScope(exprs, *block_span)
+        }
+        _ => branch.clone(),
+    };
+    Expr::If(
+        cond.clone(),
+        Box::new(returning(block)),
+        else_block.as_ref().map(|b| Box::new(returning(b))),
+        false,
+    )
+}
+
+/// Declares the names a match arm binds to the payload of its variant, and returns the C
+/// declarations that read them out of `value`.
+fn declare_arm_bindings(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    variants: &[(String, Vec<Type>)],
+    arm: &(String, Vec<String>, Expr),
+    value: &str,
+    span: Span,
+) -> String {
+    let Some(tag) = variants.iter().position(|v| v.0 == arm.0) else {
+        return String::new();
+    };
+    let mut declarations = String::new();
+    for (i, (binding, ty)) in arm.1.iter().zip(&variants[tag].1).enumerate() {
+        cte.declare_var(binding.clone(), false, ty.clone());
+        let ty_name = cte.c_type_name(ty, ctx, span);
+        let var = cte.c_var_name(binding, span);
+        declarations.push_str(&format!(
+            "{} {} = {}.data.p{}.m_{};\n",
+            ty_name, var, value, tag, i
+        ));
+    }
+    declarations
+}
+
+/// Returns the type a match arm yields, with the names it binds declared.
+fn arm_type(cte: &mut CompileTimeEnv, enum_type: &Type, arm: &(String, Vec<String>, Expr)) -> Type {
+    let payload = cte
+        .enum_variants(enum_type)
+        .and_then(|variants| variants.iter().find(|v| v.0 == arm.0))
+        .map(|v| v.1.clone())
+        .unwrap_or_default();
+    cte.push_scope();
+    for (binding, ty) in arm.1.iter().zip(payload) {
+        cte.declare_var(binding.clone(), false, ty);
+    }
+    let ty = block_type(cte, &arm.2);
+    cte.pop_scope();
+    ty
+}
+
+/// Checks that the arms of a match name variants of the enum (binding all of their payload), and
+/// that every variant is covered, by its own arm or by a `_` arm.
+fn check_match_arms(
+    enum_type: &Type,
+    variants: &[(String, Vec<Type>)],
+    arms: &[(String, Vec<String>, Expr)],
+    span: Span,
+) {
+    let mut covered: Vec<&str> = vec![];
+    for (variant, bindings, _) in arms {
+        if covered.contains(&variant.as_str()) {
+            error(
+                span,
+                &format!("'{}' is matched more than once", variant),
+                "transpiling",
+            );
+        }
+        covered.push(variant);
+        if variant == "_" {
+            if !bindings.is_empty() {
+                error(span, "'_' cannot bind a payload", "transpiling");
+            }
+            continue;
+        }
+        match variants.iter().find(|v| &v.0 == variant) {
+            Some((_, payload)) if payload.len() != bindings.len() => error(
+                span,
+                &format!(
+                    "'{}::{}' has {} values in its payload, but {} were bound",
+                    enum_type,
+                    variant,
+                    payload.len(),
+                    bindings.len()
+                ),
+                "transpiling",
+            ),
+            Some(_) => {}
+            None => error(
+                span,
+                &format!("'{}' is not a variant of '{}'", variant, enum_type),
+                "transpiling",
+            ),
+        }
+    }
+
+    let missing: Vec<&str> = variants
+        .iter()
+        .map(|v| v.0.as_str())
+        .filter(|v| !covered.contains(v))
+        .collect();
+    if !covered.contains(&"_") && !missing.is_empty() {
+        error(
+            span,
+            &format!(
+                "match on '{}' does not cover {}, add their arms or a '_' arm",
+                enum_type,
+                missing.join(", ")
+            ),
+            "transpiling",
+        );
+    }
+}
+
+/// Returns the immutable variable a place (an assignment target, or what a reference is taken
+/// to) is part of. None if it is mutable, or reached through a reference: `*r`, `` `v ``, or a
+/// variable holding a reference.
+fn immutable_root<'a>(cte: &CompileTimeEnv, place: &'a Expr) -> Option<&'a str> {
+    let mut root = place;
+    while let Expr::Member(base, ..) | Expr::Nth(base, ..) = root {
+        root = base;
+    }
+    let Expr::Variable(name, _) = root else {
+        return None;
+    };
+    match cte.get_var(name) {
+        Some((false, ty)) if ty.name() != "ref" => Some(name),
+        _ => None,
+    }
+}
+
+/// Reports an error for an assignment to a variable declared without `@`, or to a member or
+/// element of one.
+fn check_assignable(cte: &CompileTimeEnv, target: &Expr, span: Span) {
+    let Some(name) = immutable_root(cte, target) else {
+        return;
+    };
+    let part = match target {
+        Expr::Member(..) => "a member of ",
+        Expr::Nth(..) => "an element of ",
+        _ => "",
+    };
+    error(
+        span,
+        &format!(
+            "Cannot assign to {}'{}', it is immutable. Declare it with '#@{}' (or '@{}' for a parameter)",
+            part, name, name, name
+        ),
+        "transpiling",
+    );
+}
+
+/// Reports an error for a reference to a variable declared without `@`, which could be written
+/// through.
+fn check_referenceable(cte: &CompileTimeEnv, value: &Expr, span: Span) {
+    if let Some(name) = immutable_root(cte, value) {
+        error(
+            span,
+            &format!(
+                "Cannot take a reference to '{}', it is immutable. Declare it with '#@{}' (or '@{}' for a parameter)",
+                name, name, name
+            ),
+            "transpiling",
+        );
+    }
+}
+
+/// Emits the reference to the value a method is called on, which is passed as `this`. It can be
+/// to an immutable variable, unless the method changes it.
+fn receiver_to_c(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    function: &str,
+    this: &Expr,
+    span: Span,
+) {
+    let Expr::Ref(value, _) = this else {
+        this.to_c(cte, ctx);
+        return;
+    };
+    let method = function.rsplit("::").next().unwrap_or(function);
+    let class = value.get_type(cte);
+    if let Some(name) = immutable_root(cte, value)
+        && call_changes_receiver(cte, &class, method)
+    {
+        error(
+            span,
+            &format!(
+                "Cannot call '{}' on '{}', it is immutable and '{}' changes it. Declare it with '#@{}' (or '@{}' for a parameter)",
+                method, name, method, name, name
+            ),
+            "transpiling",
+        );
+    }
+    ctx.body.push('&');
+    value.to_c(cte, ctx);
+}
+
+/// Returns true if calling `method` on a value of `class` can change the value: if the method
+/// (or a method of a subclass overriding it) can.
+fn call_changes_receiver(cte: &mut CompileTimeEnv, class: &Type, method: &str) -> bool {
+    let owner = method_owner(cte, class, method);
+    let mut functions = vec![format!("{}::{}", owner.name(), method)];
+    functions.extend(cte.subclass_methods(class, method));
+    functions
+        .iter()
+        .any(|function| method_changes_receiver(cte, function))
+}
+
+/// Returns true if a method can change the value it is called on: if it assigns to a part of
+/// `` `v ``, takes a reference to one, calls a method that changes one, or passes `` `v `` on.
+fn method_changes_receiver(cte: &mut CompileTimeEnv, function: &str) -> bool {
+    if let Some(changes) = cte.changes_receiver(function) {
+        return changes;
+    }
+    let Some((class, body)) = cte.method(function).cloned() else {
+        return false;
+    };
+    // a method calling itself does not change its value by doing so
+    cte.set_changes_receiver(function, false);
+    let changes = changes_this(cte, &class, &body);
+    cte.set_changes_receiver(function, changes);
+    changes
+}
+
+/// Returns true if an expression in a method of `class` can change `` `v ``.
+fn changes_this(cte: &mut CompileTimeEnv, class: &Type, expr: &Expr) -> bool {
+    match expr {
+        Expr::Assign(place, ..) | Expr::CompoundAssign(place, ..) | Expr::Ref(place, _)
+            if is_part_of_this(place) =>
+        {
+            true
+        }
+        Expr::CallMethod(value, method, args, _) if is_part_of_this(value) => {
+            // a part of `v whose type is not known is assumed to be changed
+            let changes = match part_of_this_type(cte, class, value) {
+                Some(ty) => call_changes_receiver(cte, &member_access(ty).0, method),
+                None => true,
+            };
+            changes || args.iter().any(|arg| changes_this(cte, class, arg))
+        }
+        // reading a part of `v
+        Expr::Member(..) if is_part_of_this(expr) => false,
+        Expr::Nth(value, index, _) if is_part_of_this(value) => changes_this(cte, class, index),
+        // `v is passed on or stored, so it can be written through
+        Expr::This(_) => true,
+        _ => expr
+            .children()
+            .into_iter()
+            .any(|child| changes_this(cte, class, child)),
+    }
+}
+
+/// Returns true if a place is `` `v `` or a member or element of it.
+fn is_part_of_this(place: &Expr) -> bool {
+    let mut root = place;
+    while let Expr::Member(base, ..) | Expr::Nth(base, ..) = root {
+        root = base;
+    }
+    matches!(root, Expr::This(_))
+}
+
+/// Returns the type of a part of `` `v `` in a method of `class`, if it can be found without the
+/// scope of the method.
+fn part_of_this_type(cte: &mut CompileTimeEnv, class: &Type, place: &Expr) -> Option<Type> {
+    match place {
+        Expr::This(_) => Some(class.clone()),
+        Expr::Member(base, name, _) => {
+            let (base_type, _) = member_access(part_of_this_type(cte, class, base)?);
+            cte.get_member_type(&base_type, name)
+        }
+        Expr::Nth(base, ..) => part_of_this_type(cte, class, base)?.element_type(),
+        _ => None,
+    }
+}
+
+/// Marks the str variable a statement declares as unshared if `s = s + x` can append to it in
+/// place: if it starts out with chars of its own, and none of the statements after it up to the
+/// last one appending to it lets another value share them. Growing it would free the chars
+/// that value still points to.
+fn mark_unshared_str(cte: &mut CompileTimeEnv, stmt: &Expr, rest: &[Box<Expr>]) {
+    let stmt = match stmt {
+        Expr::Discard(inner) => inner.as_ref(),
+        _ => stmt,
+    };
+    let Expr::Declare(name, _, value, ..) = stmt else {
+        return;
+    };
+    if cte.is_global_scope()
+        || cte.get_var(name).is_none_or(|(_, ty)| ty.name() != "str")
+        || !value.as_deref().is_none_or(has_own_chars)
+    {
+        return;
+    }
+    let Some(last) = rest.iter().rposition(|stmt| appends_to(name, stmt)) else {
+        return;
+    };
+    if !rest[..=last].iter().any(|stmt| shares_str(name, stmt)) {
+        cte.set_unshared_str(name);
+    }
+}
+
+/// Returns true if a str value is made by the expression, rather than shared with another.
+fn has_own_chars(value: &Expr) -> bool {
+    match value {
+        Expr::Add(..) => true,
+        Expr::CallFunc(name, ..) => matches!(name.as_str(), "to_str" | "slice"),
+        _ => false,
+    }
+}
+
+/// Returns true if an expression contains `s = s + x` (or `s += x`) for the variable `name`.
+fn appends_to(name: &str, expr: &Expr) -> bool {
+    match expr {
+        Expr::Assign(place, value, _) | Expr::CompoundAssign(place, value, _)
+            if matches!(place.as_ref(), Expr::Variable(n, _) if n == name)
+                && matches!(value.as_ref(), Expr::Add(l, ..)
+                    if matches!(l.as_ref(), Expr::Variable(n, _) if n == name)) =>
+        {
+            true
+        }
+        _ => expr
+            .children()
+            .into_iter()
+            .any(|child| appends_to(name, child)),
+    }
+}
+
+/// Returns true if an expression can let another value share the chars of the str variable
+/// `name`, or give it chars another value shares. Only reading the chars, like indexing, `len` or
+/// adding it to something, and giving it chars of its own are known not to.
+fn shares_str(name: &str, expr: &Expr) -> bool {
+    let is_var = |e: &Expr| matches!(e, Expr::Variable(n, _) if n == name);
+    let operands_share = |operands: &[&Expr]| {
+        operands
+            .iter()
+            .any(|operand| !is_var(operand) && shares_str(name, operand))
+    };
+    match expr {
+        Expr::Variable(n, _) => n == name,
+        Expr::Nth(value, index, _) if is_var(value) => shares_str(name, index),
+        Expr::Add(l, r, _) | Expr::EqualEqual(l, r, _) | Expr::BangEqual(l, r, _) => {
+            operands_share(&[l, r])
+        }
+        Expr::Print(value, _) => operands_share(&[value]),
+        Expr::CallFunc(function, _, args, _)
+            if matches!(function.as_str(), "len" | "slice" | "drop") =>
+        {
+            operands_share(&args.iter().map(|arg| arg.as_ref()).collect::<Vec<_>>())
+        }
+        Expr::Assign(place, value, _) | Expr::CompoundAssign(place, value, _) if is_var(place) => {
+            !has_own_chars(value) || shares_str(name, value)
+        }
+        // a lambda holds a copy of the variables it uses, and a new variable hides it
+        Expr::Function(..) | Expr::DeclareFunction(..) => mentions(name, expr),
+        Expr::Declare(n, ..) | Expr::Delete(n) if n == name => true,
+        _ => expr
+            .children()
+            .into_iter()
+            .any(|child| shares_str(name, child)),
+    }
+}
+
+/// Returns true if the variable `name` appears anywhere in an expression.
+fn mentions(name: &str, expr: &Expr) -> bool {
+    matches!(expr, Expr::Variable(n, _) if n == name)
+        || expr
+            .children()
+            .into_iter()
+            .any(|child| mentions(name, child))
+}
+
+/// Lets the array literal a statement declares a local with keep its elements on the stack if
+/// none of the statements after it lets the array outlive the block it is declared in. Otherwise
+/// every evaluation of the literal copies them into a new buffer on the heap.
+fn mark_local_array(cte: &mut CompileTimeEnv, stmt: &Expr, rest: &[Box<Expr>]) {
+    let stmt = match stmt {
+        Expr::Discard(inner) => inner.as_ref(),
+        _ => stmt,
+    };
+    let on_stack = match stmt {
+        Expr::Declare(name, _, Some(value), ..) => {
+            matches!(value.as_ref(), Expr::Array(items) if !items.is_empty())
+                && !cte.is_global_scope()
+                && !rest.iter().any(|stmt| array_escapes(name, stmt))
+        }
+        _ => false,
+    };
+    cte.set_array_on_stack(on_stack);
+}
+
+/// Returns true if an expression can let the array in the variable `name` outlive the block it
+/// is declared in. Only indexing it, taking its `len` and looping over it are known not to.
+fn array_escapes(name: &str, expr: &Expr) -> bool {
+    let is_var = |e: &Expr| matches!(e, Expr::Variable(n, _) if n == name);
+    match expr {
+        Expr::Variable(n, _) => n == name,
+        Expr::Nth(array, index, _) if is_var(array) => array_escapes(name, index),
+        Expr::CallFunc(function, _, args, _)
+            if function == "len" && args.len() == 1 && is_var(&args[0]) =>
+        {
+            false
+        }
+        Expr::For(_, looper, block, ..) if is_var(looper) => array_escapes(name, block),
+        // a reference to an element points into the array, and a lambda holds a copy of it
+        Expr::Ref(..) | Expr::Function(..) | Expr::DeclareFunction(..) => mentions(name, expr),
+        _ => expr
+            .children()
+            .into_iter()
+            .any(|child| array_escapes(name, child)),
+    }
+}
+
+/// Replaces the left operand of the operation of a compound assignment.
+fn with_left_operand(operation: &Expr, left: Box<Expr>) -> Expr {
+    match operation.clone() {
+        Expr::Add(_, r, span) => Expr::Add(left, r, span),
+        Expr::Sub(_, r, span) => Expr::Sub(left, r, span),
+        Expr::Mult(_, r, span) => Expr::Mult(left, r, span),
+        Expr::Div(_, r, span) => Expr::Div(left, r, span),
+        Expr::Mod(_, r, span) => Expr::Mod(left, r, span),
+        other => other,
+    }
+}
+
+/// Returns the type both operands of a binary operator are used as: the type they share, or the
+/// type of the other operand for a number literal without a suffix. Returns the types of both
+/// operands if they do not match.
+pub fn operand_type(cte: &mut CompileTimeEnv, l: &Expr, r: &Expr) -> Result<Type, (Type, Type)> {
+    let left_type = l.get_type(cte);
+    let right_type = r.get_type(cte);
+    let takes = |literal: &Expr, ty: &Type| match literal {
+        Expr::Int(_) => ty.is_integer() || ty.is_float(),
+        Expr::Float(_) => ty.is_float(),
+        _ => false,
+    };
+    if left_type == right_type || takes(r, &left_type) {
+        Ok(left_type)
+    } else if takes(l, &right_type) {
+        Ok(right_type)
+    } else {
+        Err((left_type, right_type))
+    }
+}
+
+/// Emits the body of a loop, ending with the C label `continue` jumps to if it is used. Returns
+/// the C label `break` jumps to, which goes right after the loop.
+fn loop_body_to_c(
+    block: &Expr,
+    label: &Option<String>,
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+) -> Option<String> {
+    cte.enter_loop(label.clone());
+    block.to_c(cte, ctx);
+    let (break_label, continue_label) = cte.exit_loop();
+    push_label(continue_label, ctx);
+    break_label
+}
+
+/// Emits a C label for a `goto`, as an empty statement so it can end a block.
+fn push_label(label: Option<String>, ctx: &mut CodeGenContext) {
+    if let Some(label) = label {
+        ctx.body.push_str(&format!("\n{}:;\n", label));
+    }
+}
+
+/// Returns the type a block yields, with its own locals declared so its last expression can
+/// refer to them.
+fn block_type(cte: &mut CompileTimeEnv, block: &Expr) -> Type {
+    cte.push_scope();
+    declare_locals_from_block(cte, block);
+    let ty = block.get_type(cte);
+    cte.pop_scope();
+    ty
+}
+
+/// Returns true if a branch of an if expression is a lone value, so the whole if can become a
+/// ternary. `~?` chains count, as they lower to an expression of their own.
+fn is_simple_branch(branch: &Expr) -> bool {
+    match branch {
+        Expr::StmtBlockWithScope(exprs, _) => {
+            exprs.len() == 1 && !matches!(exprs[0].as_ref(), Expr::Discard(..) | Expr::Declare(..))
+        }
+        Expr::If(..) => true,
+        _ => false,
+    }
+}
+
+/// The expression an if expression branch yields: the last expression of its block, or the
+/// nested if of a `~?` chain.
+fn branch_value(branch: &Expr) -> &Expr {
+    match branch {
+        Expr::StmtBlockWithScope(exprs, _) => exprs.last().map_or(branch, |last| last),
+        _ => branch,
+    }
+}
+
+/// Emits one branch of an if expression as a block that ends by assigning its value to `tmp`.
+fn branch_to_c(
+    branch: &Expr,
+    tmp: Option<&String>,
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+) {
+    cte.push_scope();
+    ctx.body.push_str("{\n");
+    let exprs = match branch {
+        Expr::StmtBlockWithScope(exprs, _) => &exprs[..],
+        _ => &[][..],
+    };
+    let (stmts, value) = match exprs.split_last() {
+        Some((value, stmts)) => (stmts, value.as_ref()),
+        None => (&[][..], branch),
+    };
+    for (i, stmt) in stmts.iter().enumerate() {
+        mark_local_array(cte, stmt, &exprs[i + 1..]);
+        if stmt.to_c(cte, ctx) {
+            ctx.body.push(';');
+        }
+        ctx.body.push('\n');
+        mark_unshared_str(cte, stmt, &exprs[i + 1..]);
+    }
+    // a branch that returns from the function has no value to hand over
+    if let Some(tmp) = tmp
+        && !matches!(value, Expr::Return(..))
+    {
+        ctx.body.push_str(&format!("{} = ", tmp));
+    }
+    if value.to_c(cte, ctx) {
+        ctx.body.push(';');
+    }
+    ctx.body.push_str("\n}");
+    cte.pop_scope();
+}
+
+/// Returns the return type of a lambda or function, inferring it from the `ret` statements in
+/// its body when none was written.
+pub fn inferred_return_type(
+    cte: &mut CompileTimeEnv,
+    body: &Expr,
+    return_type: &Type,
+    params: &[(String, Type, bool)],
+) -> Type {
+    if *return_type != nil_type() {
+        return return_type.clone();
+    }
+
+    cte.push_scope();
+    for param in params {
+        cte.declare_var(param.0.clone(), param.2, param.1.clone());
+    }
+    declare_locals_from_block(cte, body);
+    cte.enter_function(nil_type());
+    let ret_type = with_implicit_return(cte, body).returned_type(cte, Span::empty());
+    cte.exit_function();
+    cte.pop_scope();
+
+    ret_type.unwrap_or_else(nil_type)
+}
+
+/// Collects the names of every variable and called function an expression refers to.
+fn collect_used_names(expr: &Expr, names: &mut Vec<String>) {
+    match expr {
+        Expr::Variable(name, _) | Expr::CallFunc(name, ..) if !names.contains(name) => {
+            names.push(name.clone());
+        }
+        _ => {}
+    }
+    for child in expr.children() {
+        collect_used_names(child, names);
+    }
+}
+
+/// Returns the outer local variables a lambda body refers to, as (name, C name, type).\
+/// Globals and functions are reachable from the lifted function directly and are not captured.
+fn lambda_captures(
+    cte: &CompileTimeEnv,
+    body: &Expr,
+    params: &[(String, Type, bool)],
+) -> Vec<(String, String, Type)> {
+    let mut names = vec![];
+    collect_used_names(body, &mut names);
+
+    names
+        .into_iter()
+        .filter(|name| !params.iter().any(|p| &p.0 == name))
+        .filter_map(|name| {
+            let (_, ty) = cte.get_var(&name)?;
+            if cte.is_local(&name) {
+                let c_name = cte.c_var_name(&name, Span::empty());
+                Some((name, c_name, ty))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Returns the argument type a generic builtin's C instance is keyed by: the first argument,
+/// looking through the reference when a reference is passed where one is expected.
+fn generic_builtin_key(
+    cte: &mut CompileTimeEnv,
+    func_type: &Type,
+    args: &[Box<Expr>],
+) -> Vec<Type> {
+    let Some(first) = args.first() else {
+        return vec![];
+    };
+    let arg_type = first.get_type(cte);
+    let takes_ref = func_type
+        .generics()
+        .first()
+        .is_some_and(|p| p.name() == "ref");
+    if takes_ref && arg_type.name() == "ref" {
+        vec![arg_type.generics()[0].clone()]
+    } else {
+        vec![arg_type]
+    }
+}
+
+/// Returns the return type of a call, binding the conceptual types of generic builtins to the
+/// argument types.
+fn call_return_type(
+    cte: &mut CompileTimeEnv,
+    func_type: &Type,
+    args: &[Box<Expr>],
+    span: Span,
+) -> Type {
+    let Some((ret_type, params)) = func_type.generics().split_last() else {
+        return nil_type();
+    };
+    if !ret_type.contains_conceptual() {
+        return ret_type.clone();
+    }
+
+    let mut bindings = HashMap::new();
+    for (param, arg) in params.iter().zip(args) {
+        let arg_type = arg.get_type(cte);
+        // a value is accepted where a reference to it is expected
+        let param = if param.name() == "ref" && arg_type.name() != "ref" {
+            &param.generics()[0]
+        } else {
+            param
+        };
+        unify(param, &arg_type, &mut bindings);
+    }
+    substitute(ret_type, &bindings, span)
+}
+
+/// Replaces every type written in an expression (declared types, parameter types, return types
+/// and explicit generics) with the result of `f`.
+fn map_types(expr: &mut Expr, f: &impl Fn(&Type) -> Type) {
+    match expr {
+        Expr::Declare(_, Some(ty), ..) => *ty = f(ty),
+        Expr::CallFunc(_, gens, ..) => {
+            for g in gens.iter_mut() {
+                *g = f(g);
+            }
+        }
+        Expr::DeclareFunction(_, _, return_type, params, ..) => {
+            if let Some(ty) = return_type {
+                *ty = f(ty);
+            }
+            for param in params.iter_mut() {
+                param.1 = f(&param.1);
+            }
+        }
+        Expr::Function(_, return_type, params, _) => {
+            *return_type = f(return_type);
+            for param in params.iter_mut() {
+                param.1 = f(&param.1);
+            }
+        }
+        _ => {}
+    }
+    for child in expr.children_mut() {
+        map_types(child, f);
+    }
+}
+
+/// Returns the types a call binds the generic parameters of a generic function to, in the order
+/// they were declared. Types that were not given explicitly are inferred from the arguments.
+fn generic_call_generics(
+    cte: &mut CompileTimeEnv,
+    name: &str,
+    decl: &Expr,
+    explicit: &[Type],
+    args: &[Box<Expr>],
+    span: Span,
+) -> Vec<Type> {
+    let Expr::DeclareFunction(_, _, _, params, gen_names, _) = decl else {
+        return vec![];
+    };
+    if explicit.len() > gen_names.len() {
+        error(
+            span,
+            &format!(
+                "Function '{}' takes {} generic types, but {} were given",
+                name,
+                gen_names.len(),
+                explicit.len()
+            ),
+            "type checker",
+        );
+    }
+
+    let mut bindings: HashMap<String, Type> = gen_names
+        .iter()
+        .cloned()
+        .zip(explicit.iter().cloned())
+        .collect();
+    for (param, arg) in params.iter().zip(args) {
+        let arg_type = arg.get_type(cte);
+        if !unify(&param.1, &arg_type, &mut bindings) {
+            error(
+                span,
+                &format!(
+                    "Expected an argument of type '{}' for '{}', found '{}'",
+                    substitute(&param.1, &bindings, span),
+                    name,
+                    arg_type
+                ),
+                "type checker",
+            );
+        }
+    }
+
+    gen_names
+        .iter()
+        .map(|g| {
+            bindings.get(g).cloned().unwrap_or_else(|| {
+                error(
+                    span,
+                    &format!(
+                        "Could not infer generic type '{}' of '{}', give it explicitly with '<<>>'",
+                        g, name
+                    ),
+                    "type checker",
+                );
+                nil_type()
+            })
+        })
+        .collect()
+}
+
+/// Returns the declaration of a generic function with its generic parameters replaced by the
+/// given types.
+fn generic_instance(decl: &Expr, gens: &[Type], span: Span) -> Expr {
+    let mut instance = decl.clone();
+    if let Expr::DeclareFunction(.., gen_names, _) = &mut instance {
+        let bindings: HashMap<String, Type> =
+            gen_names.drain(..).zip(gens.iter().cloned()).collect();
+        map_types(&mut instance, &|ty| substitute(ty, &bindings, span));
+    }
+    instance
+}
+
+/// Emits the C instance of a generic function for the given generic types, if that has not been
+/// done yet, and returns its C name. Like lambdas, instances are lifted into ctx.unnamed.
+fn instantiate_generic_function(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    name: &str,
+    decl: &Expr,
+    gens: &[Type],
+    span: Span,
+) -> String {
+    let func_name = cte.c_func_instance_name(name, gens, span);
+    if !cte.add_generic_instance(&func_name) {
+        return func_name;
+    }
+
+    let Expr::DeclareFunction(_, block, return_type, args, _, _) = generic_instance(decl, gens, span)
+    else {
+        return func_name;
+    };
+
+    // the instance sees the globals, not the locals of the function it was first called from
+    let outer_body = std::mem::take(&mut ctx.body);
+    let header = cte.with_only_globals(|cte| {
+        emit_function(cte, ctx, name, &block, &return_type, &args, gens, span)
+    });
+    ctx.declarations.push_str(&format!("{};\n", header));
+    let instance = std::mem::replace(&mut ctx.body, outer_body);
+    ctx.unnamed.push_str(&instance);
+    ctx.unnamed.push('\n');
+
+    func_name
+}
+
+/// Emits the definition of a function (or of an instance of a generic function) to ctx.body and
+/// returns its C header.
+#[allow(clippy::too_many_arguments)]
+fn emit_function(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    name: &str,
+    block: &Expr,
+    return_type: &Option<Type>,
+    args: &[(String, Type, bool)],
+    generics: &[Type],
+    span: Span,
+) -> String {
+    // Push scope and declare parameters. Struct params use pointers in C.
+    cte.push_scope();
+    for arg in args {
+        cte.declare_var(arg.0.clone(), arg.2, arg.1.clone());
+    }
+
+    // Infer return type from return statements in the body.
+    // Parameters are already declared in scope. We also need to declare
+    // local variables from the body so they're resolvable.
+    declare_locals_from_block(cte, block);
+    cte.enter_function(return_type.clone().unwrap_or_else(nil_type));
+    let block = with_implicit_return(cte, block);
+    let ret_type = block.returned_type(cte, span);
+    let return_type = return_type.clone().or(ret_type).unwrap_or_else(nil_type);
+    let block = &with_success_at_end(block, &return_type);
+    cte.exit_function();
+
+    let return_type_name = cte.c_type_name(&return_type, ctx, span);
+    let func_name = cte.c_func_instance_name(name, generics, span);
+
+    let mut params: Vec<String> = Vec::new();
+    for arg in args {
+        let param = cte.c_param_type(&arg.1, ctx, span);
+        let var = cte.c_var_name(&arg.0, span);
+        params.push(format!("{} {}", param, var));
+    }
+    let header = format!("{} {}({})", return_type_name, func_name, params.join(", "));
+    ctx.body.push_str(&header);
+
+    cte.enter_function(return_type);
+    block.to_c(cte, ctx);
+    cte.exit_function();
+
+    cte.pop_scope();
+
+    header
+}
+
+/// Declares a top-level variable as a C global. A literal initialiser is emitted with the global,
+/// any other one is assigned in `obs_init_globals`, which runs before `main` in declaration order.\
+/// Immutable globals initialised with a literal or `comp` can also be read by `comp` code.
+fn declare_global(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    name: &str,
+    var_type: &Option<Type>,
+    expr: &Option<Box<Expr>>,
+    is_mutable: bool,
+    span: Span,
+) {
+    if cte.get_var(name).is_some() {
+        error(
+            span,
+            &format!("Global '{}' already exists", name),
+            "pre-transpiling",
+        );
+        return;
+    }
+    let var_type = match (var_type, expr) {
+        (Some(var_type), _) => var_type.clone(),
+        (None, Some(expr)) => expr.get_type(cte),
+        (None, None) => nil_type(),
+    };
+    cte.declare_global_value(name.to_string(), is_mutable, var_type.clone());
+
+    let var_type_name = cte.c_type_name(&var_type, ctx, span);
+    let var_name = cte.c_var_name(name, span);
+    // a comp initialiser is computed once, here
+    let comp_value = match expr.as_deref() {
+        Some(Expr::Comp(comp)) => Some(comp.evaluate_comp(cte.comp_env())),
+        _ => None,
+    };
+    let comp_expr = comp_value.as_ref().map(|value| value_to_expr(value, span));
+    // an empty literal takes its element type from the declared type
+    let expr = comp_expr.as_ref().or(expr.as_deref()).filter(|expr| {
+        !matches!(expr, Expr::Vector(exprs) | Expr::Array(exprs) if exprs.is_empty())
+    });
+    let is_constant = matches!(
+        expr,
+        Some(
+            Expr::Int(_)
+                | Expr::Number(..)
+                | Expr::Float(_)
+                | Expr::Bool(_)
+                | Expr::Char(_)
+                | Expr::Str(_)
+        )
+    );
+    if !is_mutable && let Some(expr) = expr.filter(|_| is_constant || comp_value.is_some()) {
+        let value = comp_value.clone().unwrap_or_else(|| expr.evaluate_comp(cte.comp_env()));
+        cte.comp_env().declare(name.to_string(), value, false);
+    }
+
+    let outer_body = std::mem::take(&mut ctx.body);
+    match expr {
+        Some(expr) if is_constant => {
+            expr.to_c(cte, ctx);
+            let value = std::mem::take(&mut ctx.body);
+            ctx.declarations
+                .push_str(&format!("{} {}={};\n", var_type_name, var_name, value));
+        }
+        Some(expr) => {
+            ctx.declarations
+                .push_str(&format!("{} {};\n", var_type_name, var_name));
+            let expr_type = expr.get_type(cte);
+            if var_type.name() == "ref"
+                && let Some(cast) = upcast(cte, ctx, &expr_type, &var_type, span)
+            {
+                ctx.body.push_str(&cast);
+            }
+            expr.to_c(cte, ctx);
+            let value = std::mem::take(&mut ctx.body);
+            ctx.init.push_str(&format!("{}={};\n", var_name, value));
+        }
+        None => match class_initialiser(cte, ctx, &var_type, span) {
+            Some(init) => ctx
+                .declarations
+                .push_str(&format!("{} {}={};\n", var_type_name, var_name, init)),
+            None => ctx
+                .declarations
+                .push_str(&format!("{} {};\n", var_type_name, var_name)),
+        },
+    }
+    ctx.body = outer_body;
+}
+
+/// Returns true if values of a type can be converted with `->`: numbers, chars and bools.
+fn is_castable(ty: &Type) -> bool {
+    ty.is_integer() || ty.is_float() || *ty == Type::simple("char") || *ty == Type::simple("bool")
+}
+
+/// Returns the class a member is looked up in and the C operator it is accessed with.\
+/// Members of a class are also accessible through a reference to it, with `->`.
+fn member_access(value_type: Type) -> (Type, &'static str) {
+    if value_type.name() == "ref" {
+        (value_type.generics()[0].clone(), "->")
+    } else {
+        (value_type, ".")
+    }
+}
+
+/// Lowers a method to the function it is emitted as, named `{class}::{method}` (so static
+/// methods are called as `Type::func(args)`). It is generic over the generics of its class, and
+/// unless it is static, it takes a reference to its class as its first parameter, `this`.
+pub fn lower_method(class_type: &Type, kind: &MethodKind, method: &Expr) -> Expr {
+    let Expr::DeclareFunction(name, block, return_type, params, gens, span) = method else {
+        return method.clone();
+    };
+
+    let mut params = params.clone();
+    if !matches!(kind, MethodKind::Static) {
+        let this_type = Type::with_generics("ref", vec![class_type.clone()]);
+        params.insert(0, (THIS.to_string(), this_type, false));
+    }
+    let mut generics: Vec<String> = class_type
+        .generics()
+        .iter()
+        .map(|g| g.name().to_string())
+        .collect();
+    generics.extend(gens.iter().cloned());
+
+    Expr::DeclareFunction(
+        format!("{}::{}", class_type.name(), name),
+        block.clone(),
+        return_type.clone(),
+        params,
+        generics,
+        *span,
+    )
+}
+
+/// Returns the class of the value a method is called on, and the reference to it that is passed
+/// as `this`.
+fn method_receiver(cte: &mut CompileTimeEnv, value: &Expr, span: Span) -> (Type, Expr) {
+    let value_type = value.get_type(cte);
+    if value_type.name() == "ref" {
+        (value_type.generics()[0].clone(), value.clone())
+    } else {
+        (value_type, Expr::Ref(Box::new(value.clone()), span))
+    }
+}
+
+/// Returns the class declaring the method a call on a value of `class` runs: the class itself, or
+/// the nearest parent with the method.
+fn method_owner(cte: &CompileTimeEnv, class: &Type, method: &str) -> Type {
+    let mut owner = class;
+    while !cte.is_function(&format!("{}::{}", owner.name(), method)) {
+        match cte.parent_of(owner) {
+            Some(parent) => owner = parent,
+            None => return class.clone(),
+        }
+    }
+    owner.clone()
+}
+
+/// Lowers a method call to a call of the function the method is emitted as, passing a reference
+/// to the value as `this`. Methods the class does not declare itself are looked up on its parents.
+pub fn lower_method_call(
+    cte: &mut CompileTimeEnv,
+    value: &Expr,
+    method: &str,
+    args: &[Expr],
+    span: Span,
+) -> Expr {
+    let (class_type, this) = method_receiver(cte, value, span);
+    let owner = method_owner(cte, &class_type, method);
+
+    let mut call_args = vec![Box::new(this)];
+    call_args.extend(args.iter().cloned().map(Box::new));
+    Expr::CallFunc(
+        format!("{}::{}", owner.name(), method),
+        vec![],
+        call_args,
+        span,
+    )
+}
+
+/// Returns the cast a value of `arg_type` needs to be used where `param_type` is expected, if it
+/// is (a reference to) a subclass of it. Subclasses start with the layout of their parents, so
+/// only the C pointer type differs.
+fn upcast(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    arg_type: &Type,
+    param_type: &Type,
+    span: Span,
+) -> Option<String> {
+    let (arg_class, param_class) = if arg_type.name() == "ref" && param_type.name() == "ref" {
+        (&arg_type.generics()[0], &param_type.generics()[0])
+    } else {
+        (arg_type, param_type)
+    };
+    if arg_class == param_class || !cte.is_subclass(arg_class, param_class) {
+        return None;
+    }
+    Some(format!("({})", cte.c_param_type(param_type, ctx, span)))
+}
+
+/// Returns the C name of the vtable of a class
+fn c_vtable_name(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    ty: &Type,
+    span: Span,
+) -> String {
+    format!("{}_vt", cte.c_type_name(ty, ctx, span))
+}
+
+/// Returns the function value of a declared function: the function itself can not be given an
+/// environment, so the value holds a function that takes one and calls it.
+fn function_value(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    name: &str,
+    span: Span,
+) -> String {
+    let function = cte.c_func_instance_name(name, &[], span);
+    let func_type = cte.get_var(name).map(|v| v.1).unwrap_or_else(nil_type);
+    let func_type_name = cte.c_type_name(&func_type, ctx, span);
+    let value_name = format!("{}_value", function);
+    if cte.add_function_value(&function) {
+        let (return_type, param_types) = func_type.generics().split_last().unwrap();
+        let mut params = vec!["void* env".to_string()];
+        let mut args = vec![];
+        for (i, ty) in param_types.iter().enumerate() {
+            params.push(format!("{} a{}", cte.c_type_name(ty, ctx, span), i));
+            args.push(format!("a{}", i));
+        }
+        let header = format!(
+            "{} {}({})",
+            cte.c_type_name(return_type, ctx, span),
+            value_name,
+            params.join(", ")
+        );
+        let call = format!("{}({})", function, args.join(", "));
+        let body = if *return_type == nil_type() {
+            call
+        } else {
+            format!("return {}", call)
+        };
+        ctx.declarations.push_str(&format!("{};\n", header));
+        ctx.unnamed.push_str(&format!(
+            "\n{} {{ // {} as a value\n    {};\n}}\n",
+            header, name, body
+        ));
+    }
+    format!("(({}){{ {}, NULL }})", func_type_name, value_name)
+}
+
+/// Returns the C initialiser of a class value that is not given one, if it cannot just be zeroed:
+/// classes with `ovr` methods start out pointing to their vtable, and so do the members of other
+/// classes that are of such a class.
+fn class_initialiser(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    ty: &Type,
+    span: Span,
+) -> Option<String> {
+    if !cte.is_class(ty) {
+        return None;
+    }
+    let mut fields = vec![];
+    if cte.vtable(ty).is_some() {
+        fields.push(format!(".vt={}", c_vtable_name(cte, ctx, ty, span)));
+    }
+    for (name, member_type) in cte.class_members(ty) {
+        if let Some(init) = class_initialiser(cte, ctx, &member_type, span) {
+            fields.push(format!(".{}={}", cte.c_member_name(ty, &name, span), init));
+        }
+    }
+    (!fields.is_empty()).then(|| format!("{{{}}}", fields.join(", ")))
+}
+
+/// Emits the struct of a non-generic class. It is emitted as soon as the class is declared, before
+/// the instances of generic classes that can hold it.
+fn emit_class_struct(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    ty: &Type,
+    has_vtable: bool,
+    span: Span,
+) {
+    let c_type_name = cte.c_type_name(ty, ctx, span);
+    ctx.types.push_str("struct ");
+    ctx.types.push_str(&c_type_name);
+    ctx.types.push_str(" {\n");
+    // the vtable pointer comes first, so it is in the same place for every subclass
+    if has_vtable {
+        ctx.types.push_str("obs_vslot* vt;\n");
+    }
+    let member_lines: Vec<String> = cte
+        .class_members(ty)
+        .iter()
+        .map(|(name, member_type)| {
+            let ty_name = cte.c_type_name(member_type, ctx, span);
+            let m_name = cte.c_member_name(ty, name, span);
+            format!("{} {}", ty_name, m_name)
+        })
+        .collect();
+    ctx.types.push_str(&member_lines.join(";\n"));
+    ctx.types.push_str(";\n");
+    if ctx.types.ends_with("; \n") {
+        ctx.types.pop();
+        ctx.types.pop();
+        ctx.types.push('\n');
+    }
+    ctx.types.push_str("};\n");
+}
+
+/// Builds the vtable of a class from the one of its parent: its `ovr` methods replace the slots
+/// of the methods they override, or get new slots. Classes without any slots get no vtable.
+fn declare_vtable(
+    cte: &mut CompileTimeEnv,
+    ctx: &mut CodeGenContext,
+    ty: &Type,
+    parent: &Option<Type>,
+    methods: &[(MethodKind, Expr)],
+    span: Span,
+) {
+    let mut slots = parent
+        .as_ref()
+        .and_then(|parent| cte.vtable(parent).cloned())
+        .unwrap_or_default();
+
+    for (kind, method) in methods {
+        let Expr::DeclareFunction(name, ..) = method else {
+            continue;
+        };
+        let func_name = format!("{}::{}", ty.name(), name);
+        let slot = slots.iter().position(|(method, _)| method == name);
+
+        match (kind, slot) {
+            (MethodKind::Override, Some(slot)) => {
+                // apart from `this`, an override has to take and return the same types
+                let overridden = cte.get_var(&slots[slot].1).map(|v| v.1);
+                let overriding = cte.get_var(&func_name).map(|v| v.1);
+                if overridden.map(|t| t.generics()[1..].to_vec())
+                    != overriding.map(|t| t.generics()[1..].to_vec())
+                {
+                    error(
+                        span,
+                        &format!(
+                            "'ovr' method '{}' of '{}' does not match the method it overrides",
+                            name, ty
+                        ),
+                        "transpiling",
+                    );
+                }
+                slots[slot].1 = func_name;
+            }
+            (MethodKind::Override, None) => slots.push((name.clone(), func_name)),
+            (_, Some(_)) => error(
+                span,
+                &format!(
+                    "Method '{}' of '{}' overrides an inherited 'ovr' method, so it must be marked 'ovr'",
+                    name, ty
+                ),
+                "transpiling",
+            ),
+            (_, None) => {}
+        }
+    }
+
+    if slots.is_empty() {
+        return;
+    }
+
+    let c_slots: Vec<String> = slots
+        .iter()
+        .map(|(_, func_name)| {
+            format!(
+                "(obs_vslot){}",
+                cte.c_func_instance_name(func_name, &[], span)
+            )
+        })
+        .collect();
+    let vtable = c_vtable_name(cte, ctx, ty, span);
+    ctx.declarations.push_str(&format!(
+        "obs_vslot {}[] = {{{}}};\n",
+        vtable,
+        c_slots.join(", ")
+    ));
+    cte.set_vtable(ty.clone(), slots);
+}
+
 impl Expr {
     pub fn to_c(&self, cte: &mut CompileTimeEnv, ctx: &mut CodeGenContext) -> bool {
         // if true - requires a semicolon at end of statement
         match self {
             Expr::Int(n) => {
-                ctx.body.push_str(&n.to_string());
+                // in parentheses, so that x - -1 does not become x--1
+                if *n < 0 {
+                    ctx.body.push_str(&format!("({})", n));
+                } else {
+                    ctx.body.push_str(&n.to_string());
+                }
                 false
             }
             Expr::Float(n) => {
-                if n.fract() == 0.0 {
-                    ctx.body.push_str(&n.to_string());
-                    ctx.body.push_str(".0");
+                let n = if n.fract() == 0.0 {
+                    format!("{}.0", n)
                 } else {
-                    ctx.body.push_str(&n.to_string());
+                    n.to_string()
+                };
+                if n.starts_with('-') {
+                    ctx.body.push_str(&format!("({})", n));
+                } else {
+                    ctx.body.push_str(&n);
                 }
                 false
             }
 
+            Expr::Number(digits, ty) => {
+                let c_type = cte.c_type_name(ty, ctx, Span::empty());
+                // a u64 can be too large for a plain C literal, and the smallest i64 can only be
+                // written as a subtraction
+                let digits = match (ty.name(), digits.as_str()) {
+                    ("u64", _) => format!("{}ULL", digits),
+                    ("i64", "-9223372036854775808") => "(-9223372036854775807LL - 1)".into(),
+                    ("i64", _) => format!("{}LL", digits),
+                    _ => digits.clone(),
+                };
+                ctx.body.push_str(&format!("(({}){})", c_type, digits));
+                false
+            }
+
             Expr::Bool(b) => {
                 ctx.body.push_str(if *b { "true" } else { "false" });
                 false
@@ -102,29 +1533,23 @@ impl Expr {
             Expr::Add(l, r, span) => {
                 let left_type = l.get_type(cte);
                 let right_type = r.get_type(cte);
-                if left_type.name() == "strlit" && right_type.name() == "char" {
-                    // strlit + char: use dedicated strlit+char helper
-                    ctx.body.push_str("v_1s_0Ct_6Ct_4CDD(");
-                    l.to_c(cte, ctx);
-                    ctx.body.push_str(",");
-                    r.to_c(cte, ctx);
-                    ctx.body.push_str(")");
+                // strings can have other kinds of strings or chars added to them, which is a
+                // different instance of _add
+                let gens = if matches!(left_type.name(), "strlit" | "str") && left_type != right_type
+                {
+                    vec![left_type, right_type]
                 } else {
-                    Expr::CallFunc(
-                        "_add".into(),
-                        vec![left_type],
-                        vec![l.clone(), r.clone()],
-                        *span,
-                    )
+                    vec![operand_type(cte, l, r).unwrap_or(left_type)]
+                };
+                Expr::CallFunc("_add".into(), gens, vec![l.clone(), r.clone()], *span)
                     .to_c(cte, ctx);
-                }
                 false
             }
 
             Expr::Div(l, r, span) => {
                 Expr::CallFunc(
                     "_div".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -135,7 +1560,7 @@ impl Expr {
             Expr::Less(l, r, span) => {
                 Expr::CallFunc(
                     "_less".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -146,7 +1571,7 @@ impl Expr {
             Expr::Power(l, r, span) => {
                 Expr::CallFunc(
                     "_pow".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -156,7 +1581,7 @@ impl Expr {
             Expr::Sub(l, r, span) => {
                 Expr::CallFunc(
                     "_sub".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -166,7 +1591,17 @@ impl Expr {
             Expr::Mult(l, r, span) => {
                 Expr::CallFunc(
                     "_mult".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
+                    vec![l.clone(), r.clone()],
+                    *span,
+                )
+                .to_c(cte, ctx);
+                false
+            }
+            Expr::Mod(l, r, span) => {
+                Expr::CallFunc(
+                    "_mod".into(),
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -176,7 +1611,7 @@ impl Expr {
             Expr::EqualEqual(l, r, span) => {
                 Expr::CallFunc(
                     "_equal".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -186,7 +1621,7 @@ impl Expr {
             Expr::Greater(l, r, span) => {
                 Expr::CallFunc(
                     "_greater".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -196,7 +1631,7 @@ impl Expr {
             Expr::GreaterEqual(l, r, span) => {
                 Expr::CallFunc(
                     "_greater_equal".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -207,7 +1642,7 @@ impl Expr {
             Expr::LessEqual(l, r, span) => {
                 Expr::CallFunc(
                     "_less_equal".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -218,7 +1653,7 @@ impl Expr {
             Expr::BangEqual(l, r, span) => {
                 Expr::CallFunc(
                     "_bang_equal".into(),
-                    vec![l.get_type(cte)],
+                    vec![operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)],
                     vec![l.clone(), r.clone()],
                     *span,
                 )
@@ -226,29 +1661,130 @@ impl Expr {
                 false
             }
 
-            Expr::Not(expr, span) => {
-                Expr::CallFunc("not".into(), vec![], vec![expr.clone()], *span).to_c(cte, ctx);
+            Expr::Negate(expr, span) => {
+                let expr_type = expr.get_type(cte);
+                if !expr_type.is_integer() && !expr_type.is_float() {
+                    error(
+                        *span,
+                        &format!("Cannot negate a value of type '{}'", expr_type),
+                        "transpiling",
+                    );
+                }
+                ctx.body.push_str("(-");
+                expr.to_c(cte, ctx);
+                ctx.body.push(')');
                 false
             }
 
-            Expr::Or(l, r, span) => {
-                Expr::CallFunc("or".into(), vec![], vec![l.clone(), r.clone()], *span)
+            // !, & and | are bitwise on integers, and logical on bools
+            Expr::Not(expr, span) => {
+                let expr_type = expr.get_type(cte);
+                if expr_type.is_integer() {
+                    Expr::CallFunc(
+                        "_bit_not".into(),
+                        vec![expr_type],
+                        vec![expr.clone()],
+                        *span,
+                    )
                     .to_c(cte, ctx);
+                } else {
+                    Expr::CallFunc("not".into(), vec![], vec![expr.clone()], *span).to_c(cte, ctx);
+                }
                 false
             }
 
-            Expr::And(l, r, span) => {
-                Expr::CallFunc("and".into(), vec![], vec![l.clone(), r.clone()], *span)
-                    .to_c(cte, ctx);
+            Expr::And(l, r, span) | Expr::Or(l, r, span) => {
+                let is_and = matches!(self, Expr::And(..));
+                let (left_type, right_type) = match operand_type(cte, l, r) {
+                    Ok(ty) if ty.is_integer() => {
+                        Expr::CallFunc(
+                            if is_and { "_bit_and" } else { "_bit_or" }.into(),
+                            vec![ty],
+                            vec![l.clone(), r.clone()],
+                            *span,
+                        )
+                        .to_c(cte, ctx);
+                        return false;
+                    }
+                    Ok(ty) => (ty.clone(), ty),
+                    Err(types) => types,
+                };
+
+                let bool_type = Type::simple("bool");
+                if left_type != bool_type || right_type != bool_type {
+                    error(
+                        *span,
+                        &format!(
+                            "'{}' expects two bools or two integers of the same type but got '{}' and '{}'",
+                            if is_and { "&" } else { "|" },
+                            left_type,
+                            right_type
+                        ),
+                        "transpiling",
+                    );
+                }
+                // C's && and || only evaluate the right side when the left one does not already
+                // decide the result, so `i < len(s) & s[i] == 'x'` never indexes out of bounds
+                ctx.body.push('(');
+                l.to_c(cte, ctx);
+                ctx.body.push_str(if is_and { " && " } else { " || " });
+                r.to_c(cte, ctx);
+                ctx.body.push(')');
+                false
+            }
+
+            Expr::Xor(l, r, span) | Expr::ShiftLeft(l, r, span) | Expr::ShiftRight(l, r, span) => {
+                let (operator, takes_bools) = match self {
+                    Expr::Xor(..) => ("_xor", true),
+                    Expr::ShiftLeft(..) => ("_shl", false),
+                    _ => ("_shr", false),
+                };
+                let operand_type = match operand_type(cte, l, r) {
+                    Ok(ty) if ty.is_integer() || takes_bools && ty == Type::simple("bool") => ty,
+                    Ok(ty) => {
+                        error(
+                            *span,
+                            &format!(
+                                "'{}' cannot be applied to a value of type '{}'",
+                                &operator[1..],
+                                ty
+                            ),
+                            "transpiling",
+                        );
+                        ty
+                    }
+                    Err((left_type, right_type)) => {
+                        error(
+                            *span,
+                            &format!(
+                                "'{}' expects two operands of the same type but got '{}' and '{}'",
+                                &operator[1..],
+                                left_type,
+                                right_type
+                            ),
+                            "transpiling",
+                        );
+                        left_type
+                    }
+                };
+                Expr::CallFunc(
+                    operator.into(),
+                    vec![operand_type],
+                    vec![l.clone(), r.clone()],
+                    *span,
+                )
+                .to_c(cte, ctx);
                 false
             }
 
             Expr::StmtBlock(exprs, _span) => {
-                for expr in exprs {
+                for (i, expr) in exprs.iter().enumerate() {
+                    mark_local_array(cte, expr, &exprs[i + 1..]);
                     if expr.to_c(cte, ctx) {
                         ctx.body.push(';');
                     }
                     ctx.body.push('\n');
+                    mark_unshared_str(cte, expr, &exprs[i + 1..]);
                 }
                 false
             }
@@ -263,20 +1799,57 @@ impl Expr {
                         ctx.body.push_str(" else ");
                         else_block.clone().unwrap().to_c(cte, ctx);
                     }
+                } else if let Some(else_block) = else_block {
+                    if is_simple_branch(if_block) && is_simple_branch(else_block) {
+                        ctx.body.push_str("((");
+                        if_cond.to_c(cte, ctx);
+                        ctx.body.push_str(") ? (");
+                        branch_value(if_block).to_c(cte, ctx);
+                        ctx.body.push_str(") : (");
+                        branch_value(else_block).to_c(cte, ctx);
+                        ctx.body.push_str("))");
+                    } else {
+                        // a (gcc) statement expression, so the branches can hold statements
+                        // and the condition is still evaluated wherever the expression is
+                        cte.push_scope();
+                        let ty = block_type(cte, if_block);
+                        let tmp = if ty == nil_type() {
+                            None
+                        } else {
+                            let tmp = cte.declare_temp(ty.clone());
+                            let ty = cte.c_type_name(&ty, ctx, if_block.get_span());
+                            Some((ty, tmp))
+                        };
+                        ctx.body.push_str("({\n");
+                        if let Some((ty, tmp)) = &tmp {
+                            ctx.body.push_str(&format!("{} {};\n", ty, tmp));
+                        }
+                        ctx.body.push_str("if (");
+                        if_cond.to_c(cte, ctx);
+                        ctx.body.push(')');
+                        branch_to_c(if_block, tmp.as_ref().map(|t| &t.1), cte, ctx);
+                        ctx.body.push_str(" else ");
+                        branch_to_c(else_block, tmp.as_ref().map(|t| &t.1), cte, ctx);
+                        if let Some((_, tmp)) = &tmp {
+                            ctx.body.push_str(&format!("\n{};", tmp));
+                        }
+                        ctx.body.push_str("\n})");
+                        cte.pop_scope();
+                    }
                 } else {
                     error(
-                        if_cond.get_span(),
-                        "EXPRESSION IF STATEMENTS NOT IMPLEMENTED",
+                        if_block.get_span(),
+                        "An if expression needs a '~' branch to take its value from",
                         "transpiling",
                     );
                 }
 
                 let else_type = if let Some(ty) = else_block {
-                    ty.get_type(cte)
+                    block_type(cte, ty)
                 } else {
                     nil_type()
                 };
-                if if_block.get_type(cte) != else_type {
+                if block_type(cte, if_block) != else_type {
                     error(
                         if_block.get_span(),
                         "if and else blocks had different types",
@@ -284,7 +1857,7 @@ impl Expr {
                     );
                 }
 
-                false
+                *is_expr
             }
 
             Expr::StmtBlockWithScope(exprs, span) => {
@@ -313,23 +1886,50 @@ impl Expr {
             }
 
             Expr::Variable(name, span) => {
-                ctx.body.push_str(&cte.c_var_name(name, *span));
+                if cte.get_generic_function(name).is_some() {
+                    error(
+                        *span,
+                        &format!("Generic function '{}' cannot be used as a value", name),
+                        "transpiling",
+                    );
+                } else if cte.is_comp_function(name) {
+                    error(
+                        *span,
+                        &format!("'{}' is a 'comp fn', it can only be called", name),
+                        "transpiling",
+                    );
+                } else if cte.is_function(name) {
+                    let value = function_value(cte, ctx, name, *span);
+                    ctx.body.push_str(&value);
+                } else {
+                    ctx.body.push_str(&cte.c_var_name(name, *span));
+                }
                 false
             }
 
             Expr::This(span) => {
+                if !cte.var_exists(THIS) {
+                    error(*span, "'`v' can only be used inside a method", "transpiling");
+                    return false;
+                }
                 ctx.body.push(' ');
-                ctx.body.push_str(&cte.c_var_name(cte.this(), *span));
+                ctx.body.push_str(&cte.c_var_name(THIS, *span));
                 ctx.body.push(' ');
                 false
             }
 
+            // globals are emitted when they are declared, in pre_transpile
+            Expr::Declare(..) if cte.is_global_scope() => false,
+
             Expr::Declare(name, var_type, expr, is_mutable, span) => {
                 let var_type = if var_type.is_some() {
                     cte.declare_var(name.clone(), *is_mutable, var_type.clone().unwrap());
                     var_type.clone().unwrap()
                 } else {
-                    let t = expr.clone().unwrap().get_type(cte);
+                    // the type checker already found the types of the locals it checked
+                    let t = cte
+                        .checked_type(name, *span)
+                        .unwrap_or_else(|| expr.clone().unwrap().get_type(cte));
                     cte.declare_var(name.clone(), *is_mutable, t.clone());
                     t
                 };
@@ -338,14 +1938,37 @@ impl Expr {
                 let var_name = cte.c_var_name(&name, *span);
                 ctx.body += format!("{} {}", var_type_name, var_name).as_str();
 
-                if expr.is_some() {
-                    ctx.body.push('=');
-                    expr.clone().unwrap().to_c(cte, ctx);
+                // an empty literal takes its element type from the declared type
+                let is_empty_literal = matches!(
+                    expr.as_deref(),
+                    Some(Expr::Vector(exprs) | Expr::Array(exprs)) if exprs.is_empty()
+                );
+                match expr {
+                    Some(expr) if !is_empty_literal => {
+                        ctx.body.push('=');
+                        // a reference to a subclass can be stored as a reference to its parent
+                        let expr_type = expr.get_type(cte);
+                        if var_type.name() == "ref"
+                            && let Some(cast) = upcast(cte, ctx, &expr_type, &var_type, *span)
+                        {
+                            ctx.body.push_str(&cast);
+                        }
+                        res_value_to_c(cte, ctx, expr, &var_type, *span);
+                    }
+                    _ => {
+                        let init = class_initialiser(cte, ctx, &var_type, *span);
+                        ctx.body.push('=');
+                        ctx.body.push_str(init.as_deref().unwrap_or("{0}"));
+                    }
                 }
                 true
             }
 
             Expr::CallFunc(name, gens, exprs, span) => {
+                if cte.is_comp_function(name) {
+                    let value = self.evaluate_comp(cte.comp_env());
+                    return value_to_expr(&value, *span).to_c(cte, ctx);
+                }
                 if !cte.var_exists(name) {
                     error(
                         *span,
@@ -353,12 +1976,64 @@ impl Expr {
                         "transpiling",
                     );
                 }
-                ctx.body
-                    .push_str(format!("{}(", cte.c_func_instance_name(name, gens, *span)).as_str());
-                for expr in exprs.iter() {
+                let func_type = cte.get_var(name).map(|v| v.1).unwrap_or_else(nil_type);
+                let generic_function = cte.get_generic_function(name).cloned();
+                // Generic builtins (like push) have a C instance per type of the first argument
+                let is_generic_builtin =
+                    generic_function.is_none() && func_type.contains_conceptual();
+                let gens = if let Some(decl) = generic_function {
+                    let gens = generic_call_generics(cte, name, &decl, gens, exprs, *span);
+                    instantiate_generic_function(cte, ctx, name, &decl, &gens, *span);
+                    gens
+                } else if gens.is_empty() && is_generic_builtin {
+                    let key = generic_builtin_key(cte, &func_type, exprs);
+                    for ty in key.iter() {
+                        // make sure the instances for this type have been emitted
+                        cte.c_type_name(ty, ctx, *span);
+                    }
+                    key
+                } else {
+                    gens.clone()
+                };
+
+                // a str or res can first appear as the result of a call, like to_str("")
+                let return_type = call_return_type(cte, &func_type, exprs, *span);
+                if matches!(return_type.name(), "str" | "res") {
+                    cte.c_type_name(&return_type, ctx, *span);
+                }
+
+                // calling a variable that holds a function value (e.g. a lambda), which is given
+                // its environment
+                let is_value_call = cte.var_exists(name) && !cte.is_function(name);
+                if is_value_call {
+                    let value = cte.c_var_name(name, *span);
+                    ctx.body.push_str(&format!("{v}.fn({v}.env,", v = value));
+                } else {
+                    ctx.body.push_str(
+                        format!("{}(", cte.c_func_instance_name(name, &gens, *span)).as_str(),
+                    );
+                }
+                for (i, expr) in exprs.iter().enumerate() {
                     // Check if this argument is a struct — if so, pass by pointer
                     let arg_type = expr.get_type(cte);
-                    if cte.is_class(&arg_type) {
+                    // Generic builtins expecting a reference are given the value itself
+                    let auto_ref = is_generic_builtin
+                        && arg_type.name() != "ref"
+                        && func_type
+                            .generics()
+                            .get(i)
+                            .is_some_and(|p| p.name() == "ref");
+                    if let Some(param_type) = func_type.generics().get(i)
+                        && let Some(cast) = upcast(cte, ctx, &arg_type, param_type, *span)
+                    {
+                        ctx.body.push_str(&cast);
+                    }
+                    if auto_ref {
+                        check_referenceable(cte, expr, *span);
+                    }
+                    if i == 0 && cte.method(name).is_some() {
+                        receiver_to_c(cte, ctx, name, expr, *span);
+                    } else if cte.is_class(&arg_type) || auto_ref {
                         ctx.body.push_str("&");
                         expr.to_c(cte, ctx);
                     } else {
@@ -366,7 +2041,7 @@ impl Expr {
                     }
                     ctx.body.push(',');
                 }
-                if exprs.len() >= 1 {
+                if exprs.len() >= 1 || is_value_call {
                     ctx.body.pop();
                 }
 
@@ -375,64 +2050,182 @@ impl Expr {
             }
 
             Expr::Stmt(expr) => {
+                // a block on its own is just a block, its value is not used
+                if let Expr::Block(block) = expr.as_ref() {
+                    return block.to_c(cte, ctx);
+                }
                 expr.to_c(cte, ctx);
                 true
             }
 
-            Expr::DeclareFunction(name, block, return_type, args, _gens, span) => {
-                let mut arg_types = vec![];
-                for arg in args {
-                    arg_types.push(arg.1.clone());
+            Expr::Comp(expr) => {
+                // a comp fn is evaluated wherever it is called, and never emitted
+                if matches!(expr.as_ref(), Expr::DeclareFunction(..)) {
+                    return false;
                 }
+                let value = expr.evaluate_comp(cte.comp_env());
+                value_to_expr(&value, expr.get_span()).to_c(cte, ctx)
+            }
+
+            Expr::Block(block) => {
+                // a (gcc) statement expression, the value of which is its last statement
+                let Expr::StmtBlockWithScope(exprs, _) = block.as_ref() else {
+                    return block.to_c(cte, ctx);
+                };
+                let value = trailing_value(block);
+                let stmts = if value.is_some() {
+                    &exprs[..exprs.len() - 1]
+                } else {
+                    &exprs[..]
+                };
 
-                // Push scope and declare parameters. Struct params use pointers in C.
                 cte.push_scope();
-                for arg in args {
-                    cte.declare_var(arg.0.clone(), arg.2, arg.1.clone());
+                ctx.body.push_str("({\n");
+                for (i, stmt) in stmts.iter().enumerate() {
+                    // the value of the block is the rest of it too
+                    mark_local_array(cte, stmt, &exprs[i + 1..]);
+                    if stmt.to_c(cte, ctx) {
+                        ctx.body.push(';');
+                    }
+                    ctx.body.push('\n');
+                    mark_unshared_str(cte, stmt, &exprs[i + 1..]);
+                }
+                if let Some(value) = value {
+                    value.to_c(cte, ctx);
+                    ctx.body.push_str(";\n");
                 }
+                ctx.body.push_str("})");
+                cte.pop_scope();
+                true
+            }
 
-                // Infer return type from return statements in the body.
-                // Parameters are already declared in scope. We also need to declare
-                // local variables from the body so they're resolvable.
-                declare_locals_from_block(cte, block);
-                let ret_type = block.returned_type(cte, *span);
-                let return_type = if return_type.is_some() {
-                    return_type.clone().unwrap()
+            Expr::DeclareFunction(name, block, return_type, args, gens, span) => {
+                // generic functions are emitted once per instance, where they are called
+                if gens.is_empty() {
+                    emit_function(cte, ctx, name, block, return_type, args, &[], *span);
+                }
+                false
+            }
+
+            Expr::Function(body, return_type, params, gens) => {
+                if !gens.is_empty() {
+                    error(
+                        body.get_span(),
+                        "Lambdas cannot be generic, declare a generic function with 'fn' instead",
+                        "transpiling",
+                    );
+                }
+
+                let return_type = inferred_return_type(cte, body, return_type, params);
+                let mut func_generics: Vec<Type> = params.iter().map(|p| p.1.clone()).collect();
+                func_generics.push(return_type.clone());
+                let func_type = Type::with_generics("func", func_generics);
+                let func_type_name = cte.c_type_name(&func_type, ctx, Span::empty());
+
+                let captures = lambda_captures(cte, body, params);
+                let func_name = cte.declare_lambda(func_type);
+                let env_name = format!("{}_env", func_name);
+
+                // Every time the lambda is evaluated, the captured values are copied into a new
+                // environment, which the function value carries along with the lifted function.
+                if captures.is_empty() {
+                    ctx.body
+                        .push_str(&format!("(({}){{ {}, NULL }})", func_type_name, func_name));
                 } else {
-                    if let Some(ty) = ret_type.clone() {
-                        ty
-                    } else {
-                        nil_type()
+                    ctx.body.push_str(&format!(
+                        "({{ struct {e}* env = malloc(sizeof(struct {e})); ",
+                        e = env_name
+                    ));
+                    for (_, c_name, _) in captures.iter() {
+                        ctx.body
+                            .push_str(&format!("env->{} = {}; ", c_name, c_name));
                     }
-                };
+                    ctx.body.push_str(&format!(
+                        "({}){{ {}, env }}; }})",
+                        func_type_name, func_name
+                    ));
+                }
 
-                let return_type_name = cte.c_type_name(&return_type, ctx, *span);
-                let func_name = cte.c_func_instance_name(&name, &[], *span);
-                ctx.body
-                    .push_str(&format!("{} {}(", return_type_name, func_name));
+                // Lift the body into its own C function
+                let outer_body = std::mem::take(&mut ctx.body);
+                let mut lifted = String::new();
 
-                let mut params: Vec<String> = Vec::new();
-                for arg in args {
-                    let param = cte.c_param_type(&arg.1, ctx, *span);
-                    let var = cte.c_var_name(&arg.0, *span);
-                    params.push(format!("{} {}", param, var));
+                if !captures.is_empty() {
+                    lifted.push_str(&format!("struct {} {{\n", env_name));
+                    for (_, c_name, ty) in captures.iter() {
+                        let ty_name = cte.c_type_name(ty, ctx, Span::empty());
+                        lifted.push_str(&format!("{} {};\n", ty_name, c_name));
+                    }
+                    lifted.push_str("};\n");
                 }
-                ctx.body.push_str(&params.join(", "));
-                if ctx.body.ends_with(", ") {
-                    ctx.body.pop();
-                    ctx.body.pop();
+
+                cte.push_scope();
+
+                // the environment comes first, like in the typedef of the function type
+                let mut c_params = vec!["void* env".to_string()];
+                for param in params {
+                    cte.declare_var(param.0.clone(), param.2, param.1.clone());
+                    let param_type = cte.c_param_type(&param.1, ctx, Span::empty());
+                    let var = cte.c_var_name(&param.0, Span::empty());
+                    c_params.push(format!("{} {}", param_type, var));
                 }
 
-                ctx.body.push(')');
+                let return_type_name = cte.c_type_name(&return_type, ctx, Span::empty());
+                let header = format!("{} {}({})", return_type_name, func_name, c_params.join(", "));
+                ctx.declarations.push_str(&format!("{};\n", header));
 
-                block.to_c(cte, ctx);
+                ctx.body.push_str(&header);
+                ctx.body.push_str("{\n");
+                // Captured variables become immutable locals of the lifted function
+                for (name, c_name, ty) in captures.iter() {
+                    cte.declare_var(name.clone(), false, ty.clone());
+                    let ty_name = cte.c_type_name(ty, ctx, Span::empty());
+                    let local = cte.c_var_name(name, Span::empty());
+                    ctx.body.push_str(&format!(
+                        "{} {} = ((struct {}*)env)->{};\n",
+                        ty_name, local, env_name, c_name
+                    ));
+                }
+                cte.enter_function(return_type.clone());
+                let body = with_implicit_return(cte, body);
+                with_success_at_end(body, &return_type).to_c(cte, ctx);
+                cte.exit_function();
+                ctx.body.push_str("\n}\n");
 
                 cte.pop_scope();
 
+                lifted.push_str(&std::mem::replace(&mut ctx.body, outer_body));
+                ctx.unnamed.push_str(&lifted);
+
                 false
             }
 
             Expr::Assign(left, right, span) => {
+                check_assignable(cte, left, *span);
+
+                // `s = s + x` grows the str s in place, instead of building a new one, if no other
+                // value shares its chars
+                if let Expr::Add(l, r, _) = right.as_ref()
+                    && let (Expr::Variable(target, _), Expr::Variable(source, _)) =
+                        (left.as_ref(), l.as_ref())
+                    && target == source
+                    && left.get_type(cte).name() == "str"
+                    && cte.is_unshared_str(target)
+                {
+                    let right_type = r.get_type(cte);
+                    if matches!(right_type.name(), "str" | "strlit" | "char") {
+                        let str_type = cte.c_type_name(&Type::simple("str"), ctx, *span);
+                        let right_type = cte.c_type_name(&right_type, ctx, *span);
+                        ctx.body
+                            .push_str(&format!("{}_append_{}(&", str_type, right_type));
+                        left.to_c(cte, ctx);
+                        ctx.body.push(',');
+                        r.to_c(cte, ctx);
+                        ctx.body.push(')');
+                        return true;
+                    }
+                }
+
                 match left.as_ref() {
                     Expr::Variable(name, _) => {
                         let var_info = cte.get_var(name).unwrap_or_else(|| {
@@ -444,8 +2237,6 @@ impl Expr {
                             (false, nil_type())
                         });
 
-                        cte.push_this(name);
-
                         if var_info.1.name() == "ref" {
                             ctx.body.push_str("(*");
                             ctx.body.push_str(&cte.c_var_name(name, *span));
@@ -455,14 +2246,13 @@ impl Expr {
                         }
 
                         ctx.body.push('=');
-                        right.to_c(cte, ctx);
-                        cte.pop_this();
+                        res_value_to_c(cte, ctx, right, &var_info.1, *span);
                     }
                     Expr::Member(expr, member, span) => {
-                        let var_type = expr.get_type(cte);
+                        let (class_type, _) = member_access(expr.get_type(cte));
 
                         let member_type =
-                            cte.get_member_type(&var_type, member).unwrap_or_else(|| {
+                            cte.get_member_type(&class_type, member).unwrap_or_else(|| {
                                 error(
                                     *span,
                                     &format!("Could not find member '{}'", member),
@@ -471,21 +2261,20 @@ impl Expr {
                                 nil_type()
                             });
 
+                        // like variables, a member holding a reference is assigned through it
                         if member_type.name() == "ref" {
                             ctx.body.push_str("(*");
-                        }
-
-                        expr.to_c(cte, ctx);
-
-                        if member_type.name() == "ref" {
+                            left.to_c(cte, ctx);
                             ctx.body.push(')');
+                        } else {
+                            left.to_c(cte, ctx);
                         }
 
-                        ctx.body.push('.');
-
-                        ctx.body
-                            .push_str(&cte.c_member_name(&var_type, member, *span));
-
+                        ctx.body.push('=');
+                        right.to_c(cte, ctx);
+                    }
+                    Expr::Nth(..) | Expr::Deref(..) => {
+                        left.to_c(cte, ctx);
                         ctx.body.push('=');
                         right.to_c(cte, ctx);
                     }
@@ -496,9 +2285,99 @@ impl Expr {
                 true
             }
 
-            Expr::Return(expr, _span) => {
+            Expr::CompoundAssign(target, operation, span) => {
+                // reading a variable twice is harmless, and `s += x` grows a str in place
+                if matches!(target.as_ref(), Expr::Variable(..)) {
+                    return Expr::Assign(target.clone(), operation.clone(), *span).to_c(cte, ctx);
+                }
+                if !matches!(target.as_ref(), Expr::Member(..) | Expr::Nth(..)) {
+                    error(*span, "Invalid assignment target", "transpiler");
+                    return true;
+                }
+                check_assignable(cte, target, *span);
+
+                // a member or element is only evaluated once, through a pointer to it
+                let ptr_type = Type::with_generics("ref", vec![target.get_type(cte)]);
+                cte.push_scope();
+                cte.declare_var(TARGET.into(), false, ptr_type.clone());
+                let ptr_c_type = cte.c_type_name(&ptr_type, ctx, *span);
+                let ptr = cte.c_var_name(TARGET, *span);
+                ctx.body
+                    .push_str(&format!("{{\n{} {} = &", ptr_c_type, ptr));
+                target.to_c(cte, ctx);
+                ctx.body.push_str(";\n");
+
+                let deref = Box::new(Expr::Deref(
+                    Box::new(Expr::Variable(TARGET.into(), *span)),
+                    *span,
+                ));
+                let operation = with_left_operand(operation, deref.clone());
+                Expr::Assign(deref, Box::new(operation), *span).to_c(cte, ctx);
+                ctx.body.push_str(";\n}");
+                cte.pop_scope();
+                false
+            }
+
+            Expr::Return(expr, span) => {
+                let return_type = cte.return_type().cloned().unwrap_or_else(nil_type);
+                if return_type.name() == "res" && matches!(expr.as_ref(), Expr::If(.., true)) {
+                    return returning_branches(expr, *span).to_c(cte, ctx);
+                }
                 ctx.body.push_str("return ");
+                res_value_to_c(cte, ctx, expr, &return_type, *span);
+                true
+            }
+
+            Expr::Err(message, span) => match cte.return_type().cloned() {
+                Some(res_type) if res_type.name() == "res" => {
+                    err_to_c(cte, ctx, message, &res_type, *span)
+                }
+                _ => {
+                    error(
+                        *span,
+                        "'err' can only be used in a function that returns a res",
+                        "transpiling",
+                    );
+                    false
+                }
+            },
+
+            Expr::Propagate(expr, span) => {
+                let res_type = expr.get_type(cte);
+                if res_type.name() != "res" {
+                    error(
+                        *span,
+                        &format!("'$?' can only be used on a res, found '{}'", res_type),
+                        "transpiling",
+                    );
+                    return false;
+                }
+                let Some(return_type) = cte.return_type().filter(|t| t.name() == "res").cloned()
+                else {
+                    error(
+                        *span,
+                        "'$?' can only be used in a function that returns a res",
+                        "transpiling",
+                    );
+                    return false;
+                };
+
+                // ({ res tmp = value; if (!tmp.ok) return (ret){ .ok = false, .err = tmp.err };
+                // tmp.value; })
+                let res_name = cte.c_type_name(&res_type, ctx, *span);
+                let return_name = cte.c_type_name(&return_type, ctx, *span);
+                let tmp = cte.declare_temp(res_type.clone());
+                ctx.body.push_str(&format!("({{ {} {} = ", res_name, tmp));
                 expr.to_c(cte, ctx);
+                ctx.body.push_str(&format!(
+                    "; if (!{tmp}.ok) return ({ret}){{ .ok = false, .err = {tmp}.err }}; ",
+                    tmp = tmp,
+                    ret = return_name,
+                ));
+                if res_type.generics()[0] != nil_type() {
+                    ctx.body.push_str(&format!("{}.value; ", tmp));
+                }
+                ctx.body.push_str("})");
                 true
             }
 
@@ -509,54 +2388,293 @@ impl Expr {
 
             Expr::Nothing() => false,
 
-            Expr::While(cond, block) => {
+            Expr::While(cond, block, label, _) => {
                 ctx.body.push_str("while (");
                 cond.to_c(cte, ctx);
                 ctx.body.push_str("){\n");
-                block.to_c(cte, ctx);
-                ctx.body.push_str("}");
+                let break_label = loop_body_to_c(block, label, cte, ctx);
+                ctx.body.push('}');
+                push_label(break_label, ctx);
+                false
+            }
+
+            Expr::Break(label, span) | Expr::Continue(label, span) => {
+                let is_break = matches!(self, Expr::Break(..));
+                match (cte.loop_jump(label.as_deref(), is_break), label) {
+                    (Some(c_label), _) => ctx.body.push_str(&format!("goto {}", c_label)),
+                    (None, Some(label)) => error(
+                        *span,
+                        &format!("There is no loop labelled '{}' to leave", label),
+                        "transpiling",
+                    ),
+                    (None, None) => error(
+                        *span,
+                        &format!(
+                            "'{}' can only be used inside a loop",
+                            if is_break { "break" } else { "continue" }
+                        ),
+                        "transpiling",
+                    ),
+                }
+                true
+            }
+
+            Expr::For(name, looper, block, label, span) => {
+                let looper_type = looper.get_type(cte);
+
+                // The loop variable (and any hidden counters) live in their own scope
+                cte.push_scope();
+
+                if looper_type.is_integer() {
+                    // for i : n  ->  0, 1, ..., n - 1 (n is evaluated once, before the loop
+                    // variable hides a variable it may name)
+                    let end = cte.declare_temp(looper_type.clone());
+                    let ty = cte.c_type_name(&looper_type, ctx, *span);
+                    ctx.body.push_str(&format!("{{\n{} {} = ", ty, end));
+                    looper.to_c(cte, ctx);
+                    ctx.body.push_str(";\n");
+
+                    cte.declare_var(name.clone(), false, looper_type.clone());
+                    let var = cte.c_var_name(name, *span);
+                    ctx.body.push_str(&format!(
+                        "for ({} {} = 0; {} < {}; {}++) {{\n",
+                        ty, var, var, end, var
+                    ));
+                    let break_label = loop_body_to_c(block, label, cte, ctx);
+                    ctx.body.push_str("\n}\n}");
+                    push_label(break_label, ctx);
+                } else if let Some(elem_type) = looper_type.element_type() {
+                    let seq = cte.declare_temp(looper_type.clone());
+                    let seq_type = cte.c_type_name(&looper_type, ctx, *span);
+                    let elem_type_name = cte.c_type_name(&elem_type, ctx, *span);
+
+                    ctx.body.push_str(&format!("{{\n{} {} = ", seq_type, seq));
+                    // the array only lives as long as the loop
+                    cte.set_array_on_stack(matches!(looper.as_ref(), Expr::Array(..)));
+                    looper.to_c(cte, ctx);
+                    ctx.body.push_str(";\n");
+
+                    if looper_type.name() == "strlit" {
+                        // walk the string up to its terminating '\0'
+                        ctx.body.push_str(&format!(
+                            "for (; *{} != '\\0'; {}++) {{\n",
+                            seq, seq
+                        ));
+                        cte.declare_var(name.clone(), false, elem_type);
+                        let var = cte.c_var_name(name, *span);
+                        ctx.body
+                            .push_str(&format!("{} {} = *{};\n", elem_type_name, var, seq));
+                    } else {
+                        // vec, arr and str values carry their own length
+                        let idx = cte.declare_temp(Type::simple("i32"));
+                        ctx.body.push_str(&format!(
+                            "for (t_0CD {} = 0; {} < {}.len; {}++) {{\n",
+                            idx, idx, seq, idx
+                        ));
+                        cte.declare_var(name.clone(), false, elem_type);
+                        let var = cte.c_var_name(name, *span);
+                        ctx.body.push_str(&format!(
+                            "{} {} = {}.data[{}];\n",
+                            elem_type_name, var, seq, idx
+                        ));
+                    }
+
+                    let break_label = loop_body_to_c(block, label, cte, ctx);
+                    ctx.body.push_str("\n}\n}");
+                    push_label(break_label, ctx);
+                } else {
+                    error(
+                        *span,
+                        &format!("Cannot iterate over a value of type '{}'", looper_type),
+                        "transpiling",
+                    );
+                }
+
+                cte.pop_scope();
                 false
             }
 
-            Expr::Use { .. } => false,
+            // calls to macros have already been expanded by the parser
+            Expr::Use { .. } | Expr::DeclareMacro(..) => false,
 
-            Expr::Class(ty, members, span) => {
+            Expr::Class(ty, _, _, methods, _) => {
+                for (kind, method) in methods {
+                    lower_method(ty, kind, method).to_c(cte, ctx);
+                    ctx.body.push('\n');
+                }
+                // the struct was emitted in pre_transpile, and instances of generic classes are
+                // emitted where they are first used
+                !ty.contains_conceptual()
+            }
+
+            Expr::Enum(ty, variants, span) => {
+                // struct t_{id}CD { t_0CD tag; union { struct { ... } p{tag}; ... } data; };
                 let c_type_name = cte.c_type_name(ty, ctx, *span);
-                ctx.types.push_str("struct ");
-                ctx.types.push_str(&c_type_name);
-                ctx.types.push_str(" {\n");
-                let member_lines: Vec<String> = members
-                    .iter()
-                    .map(|(name, member_type)| {
-                        let ty_name = cte.c_type_name(member_type, ctx, *span);
-                        let m_name = cte.c_member_name(ty, name, *span);
-                        format!("{} {}", ty_name, m_name)
+                let mut def = format!("struct {} {{\nt_0CD tag;\n", c_type_name);
+                if variants.iter().any(|v| !v.1.is_empty()) {
+                    def.push_str("union {\n");
+                    for (tag, (_, payload)) in variants.iter().enumerate() {
+                        if payload.is_empty() {
+                            continue;
+                        }
+                        let members: Vec<String> = payload
+                            .iter()
+                            .enumerate()
+                            .map(|(i, ty)| format!("{} m_{};", cte.c_type_name(ty, ctx, *span), i))
+                            .collect();
+                        def.push_str(&format!("struct {{ {} }} p{};\n", members.join(" "), tag));
+                    }
+                    def.push_str("} data;\n");
+                }
+                def.push_str("};\n");
+                ctx.types.push_str(&def);
+                false
+            }
+
+            Expr::Match(value, arms, is_expr, span) => {
+                let enum_type = value.get_type(cte);
+                let Some(variants) = cte.enum_variants(&enum_type).cloned() else {
+                    error(
+                        *span,
+                        &format!("Can only 'match' on an enm, found '{}'", enum_type),
+                        "transpiling",
+                    );
+                    return false;
+                };
+                check_match_arms(&enum_type, &variants, arms, *span);
+
+                cte.push_scope();
+                // a match expression is a (gcc) statement expression, like an if expression
+                let result = if *is_expr {
+                    let ty = arms.first().map_or_else(nil_type, |arm| arm_type(cte, &enum_type, arm));
+                    if arms.iter().any(|arm| arm_type(cte, &enum_type, arm) != ty) {
+                        error(*span, "match arms had different types", "transpiling");
+                    }
+                    (ty != nil_type()).then(|| {
+                        let ty_name = cte.c_type_name(&ty, ctx, *span);
+                        (ty_name, cte.declare_temp(ty))
                     })
-                    .collect();
-                ctx.types.push_str(&member_lines.join(";\n"));
-                ctx.types.push_str(";\n");
-                if ctx.types.ends_with("; \n") {
-                    ctx.types.pop();
-                    ctx.types.pop();
-                    ctx.types.push('\n');
+                } else {
+                    None
+                };
+                ctx.body.push_str(if *is_expr { "({\n" } else { "{\n" });
+                if let Some((ty_name, result)) = &result {
+                    ctx.body.push_str(&format!("{} {};\n", ty_name, result));
                 }
-                ctx.types.push_str("};\n");
 
-                true
+                let enum_name = cte.c_type_name(&enum_type, ctx, *span);
+                let tmp = cte.declare_temp(enum_type.clone());
+                ctx.body.push_str(&format!("{} {} = ", enum_name, tmp));
+                value.to_c(cte, ctx);
+                ctx.body.push_str(&format!(";\nswitch ({}.tag) {{\n", tmp));
+                for arm in arms {
+                    match variants.iter().position(|v| v.0 == arm.0) {
+                        Some(tag) => ctx.body.push_str(&format!("case {}: {{\n", tag)),
+                        None => ctx.body.push_str("default: {\n"),
+                    }
+                    cte.push_scope();
+                    let bindings = declare_arm_bindings(cte, ctx, &variants, arm, &tmp, *span);
+                    ctx.body.push_str(&bindings);
+                    if *is_expr {
+                        branch_to_c(&arm.2, result.as_ref().map(|r| &r.1), cte, ctx);
+                    } else {
+                        arm.2.to_c(cte, ctx);
+                    }
+                    cte.pop_scope();
+                    ctx.body.push_str("\nbreak;\n}\n");
+                }
+                ctx.body.push_str("}\n");
+                if let Some((_, result)) = &result {
+                    ctx.body.push_str(&format!("{};\n", result));
+                }
+                ctx.body.push_str(if *is_expr { "})" } else { "}" });
+                cte.pop_scope();
+                *is_expr
             }
 
             Expr::Member(expr, member, span) => {
-                let var_type = expr.get_type(cte);
+                let (class_type, access) = member_access(expr.get_type(cte));
                 expr.to_c(cte, ctx);
 
-                ctx.body.push('.');
+                ctx.body.push_str(access);
 
                 ctx.body
-                    .push_str(&cte.c_member_name(&var_type, member, *span));
+                    .push_str(&cte.c_member_name(&class_type, member, *span));
                 false
             }
 
-            Expr::Ref(expr, _span) => {
+            Expr::CallMethod(value, method, args, span) => {
+                let call = lower_method_call(cte, value, method, args, *span);
+                if let Expr::CallFunc(name, ..) = &call
+                    && !cte.is_function(name)
+                {
+                    error(
+                        *span,
+                        &format!("Method '{}' does not exist", name),
+                        "transpiling",
+                    );
+                    return true;
+                }
+
+                let (class_type, this) = method_receiver(cte, value, *span);
+                let slot = cte
+                    .vtable(&class_type)
+                    .and_then(|slots| slots.iter().position(|(name, _)| name == method));
+                let Some(slot) = slot else {
+                    return call.to_c(cte, ctx);
+                };
+
+                // `ovr` methods are called through the vtable of the value, so the function of
+                // the class it was created as is called. The slot is cast to a plain pointer to
+                // the method, and the value is only evaluated once, into a variable of its own.
+                let Expr::CallFunc(name, _, call_args, _) = &call else {
+                    unreachable!()
+                };
+                let func_type = cte.get_var(name).map(|v| v.1).unwrap_or_else(nil_type);
+                let (return_type, params) = func_type
+                    .generics()
+                    .split_last()
+                    .map(|(ret, params)| (ret.clone(), params.to_vec()))
+                    .unwrap_or_else(|| (nil_type(), vec![]));
+                let return_type = cte.c_type_name(&return_type, ctx, *span);
+                let params: Vec<String> = params
+                    .iter()
+                    .map(|param| cte.c_param_type(param, ctx, *span))
+                    .collect();
+                let c_name = cte.c_func_instance_name(name, &[], *span);
+
+                // a receiver that cannot be changed is reported when it is emitted
+                let receiver_type = Type::with_generics("ref", vec![class_type]);
+                let outer_body = std::mem::take(&mut ctx.body);
+                receiver_to_c(cte, ctx, name, &this, *span);
+                let this = std::mem::replace(&mut ctx.body, outer_body);
+
+                cte.push_scope();
+                cte.declare_var("%this".to_string(), false, receiver_type.clone());
+                let receiver = cte.c_var_name("%this", *span);
+                let mut args = vec![Box::new(Expr::Variable("%this".to_string(), *span))];
+                args.extend(call_args.iter().skip(1).cloned());
+                let outer_body = std::mem::take(&mut ctx.body);
+                Expr::CallFunc(name.clone(), vec![], args, *span).to_c(cte, ctx);
+                let call = std::mem::replace(&mut ctx.body, outer_body);
+                cte.pop_scope();
+
+                let receiver_type = cte.c_type_name(&receiver_type, ctx, *span);
+                ctx.body.push_str(&format!(
+                    "({{ {t} {r} = {}; (({} (*)({}))({r}->vt[{}])){}; }})",
+                    this,
+                    return_type,
+                    params.join(", "),
+                    slot,
+                    &call[c_name.len()..],
+                    t = receiver_type,
+                    r = receiver,
+                ));
+                true
+            }
+
+            Expr::Ref(expr, span) => {
+                check_referenceable(cte, expr, *span);
                 ctx.body.push('&');
                 expr.to_c(cte, ctx);
                 false
@@ -568,9 +2686,46 @@ impl Expr {
                 false
             }
 
-            Expr::Nth(left, right, _span) => {
+            Expr::Cast(expr, ty, span) => {
+                let from = expr.get_type(cte);
+                if !is_castable(&from) || !is_castable(ty) || ty.name() == "bool" {
+                    error(
+                        *span,
+                        &format!("Cannot cast '{}' to '{}'", from, ty),
+                        "transpiling",
+                    );
+                }
+                let c_type = cte.c_type_name(ty, ctx, *span);
+                ctx.body.push_str(&format!("(({})(", c_type));
+                expr.to_c(cte, ctx);
+                ctx.body.push_str("))");
+                false
+            }
+
+            Expr::Nth(left, right, span) => {
+                let left_type = left.get_type(cte);
+                let is_sequence = matches!(left_type.name(), "vec" | "arr" | "str");
+
+                if is_sequence && cte.var_exists("DEBUG") {
+                    // bounds checked, reporting the Obstruct file and line when out of bounds
+                    let seq_c_type = cte.c_type_name(&left_type, ctx, *span);
+                    ctx.body.push_str(&format!("(*{}_at(", seq_c_type));
+                    left.to_c(cte, ctx);
+                    ctx.body.push(',');
+                    right.to_c(cte, ctx);
+                    ctx.body.push_str(&format!(
+                        ", {:?}, {}))",
+                        crate::get_current_file().unwrap_or_default(),
+                        span.line
+                    ));
+                    return false;
+                }
+
                 left.to_c(cte, ctx);
 
+                if is_sequence {
+                    ctx.body.push_str(".data");
+                }
                 ctx.body.push('[');
                 right.to_c(cte, ctx);
                 ctx.body.push(']');
@@ -578,6 +2733,58 @@ impl Expr {
                 false
             }
 
+            Expr::Vector(exprs) | Expr::Array(exprs) => {
+                let on_stack = cte.take_array_on_stack() && matches!(self, Expr::Array(..));
+                let seq_type = self.get_type(cte);
+                if exprs.is_empty() {
+                    error(
+                        Span::empty(),
+                        "Cannot infer the element type of an empty literal, declare the variable with a type",
+                        "transpiling",
+                    );
+                    return false;
+                }
+
+                let elem_type = seq_type.generics()[0].clone();
+                for expr in exprs.iter().skip(1) {
+                    let expr_type = expr.get_type(cte);
+                    if expr_type != elem_type {
+                        error(
+                            expr.get_span(),
+                            &format!(
+                                "Literal elements must all have the same type, expected '{}' but found '{}'",
+                                elem_type, expr_type
+                            ),
+                            "type checker",
+                        );
+                    }
+                }
+
+                let seq_c_type = cte.c_type_name(&seq_type, ctx, Span::empty());
+                let elem_c_type = cte.c_type_name(&elem_type, ctx, Span::empty());
+
+                // An array that stays in its block points to a compound literal, which lives as
+                // long as the block. Otherwise the elements are copied into a new heap buffer.
+                if on_stack {
+                    ctx.body
+                        .push_str(&format!("(({}){{ ({}[]){{", seq_c_type, elem_c_type));
+                } else {
+                    ctx.body
+                        .push_str(&format!("{}_from(({}[]){{", seq_c_type, elem_c_type));
+                }
+                for expr in exprs.iter() {
+                    expr.to_c(cte, ctx);
+                    ctx.body.push(',');
+                }
+                ctx.body.pop();
+                if on_stack {
+                    ctx.body.push_str(&format!("}}, {} }})", exprs.len()));
+                } else {
+                    ctx.body.push_str(&format!("}}, {})", exprs.len()));
+                }
+                false
+            }
+
             _ => panic!("unexpected expression (for transpilation) '{:?}'", self),
         }
     }
@@ -590,7 +2797,7 @@ impl Expr {
         current_file_dir: &str,
     ) {
         match self {
-            Expr::DeclareFunction(name, block, return_type, args, _gens, span) => {
+            Expr::DeclareFunction(name, block, return_type, args, gens, span) => {
                 if cte.get_var(name).is_some() {
                     error(
                     *span,
@@ -601,6 +2808,20 @@ impl Expr {
                         .as_str(),"pre-transpiling"
                 );
                 }
+
+                if !gens.is_empty() {
+                    // Generic functions are only type checked and emitted once instantiated
+                    let mut decl = self.clone();
+                    map_types(&mut decl, &|ty| ty.with_conceptuals(gens));
+                    if let Expr::DeclareFunction(_, _, return_type, args, _, _) = &decl {
+                        let mut func_generics: Vec<Type> =
+                            args.iter().map(|arg| arg.1.clone()).collect();
+                        func_generics.push(return_type.clone().unwrap_or_else(nil_type));
+                        let func_type = Type::with_generics("func", func_generics);
+                        cte.declare_generic_function(name.clone(), func_type, decl.clone());
+                    }
+                    return;
+                }
                 let mut arg_types = vec![];
                 for arg in args {
                     arg_types.push(arg.1.clone());
@@ -617,7 +2838,7 @@ impl Expr {
                 // and get_type() calls that resolve variable names.
                 block.pre_transpile(cte, ctx, programs_to_transpile, current_file_dir);
 
-                let ret_type = block.returned_type(cte, *span);
+                let ret_type = with_implicit_return(cte, block).returned_type(cte, *span);
                 let return_type = if return_type.is_some() {
                     return_type.clone().unwrap()
                 } else {
@@ -703,10 +2924,56 @@ impl Expr {
                 expr.pre_transpile(cte, ctx, programs_to_transpile, current_file_dir)
             }
 
-            Expr::Class(ty, members, span) => {
+            Expr::Class(ty, parent, members, methods, span) if ty.contains_conceptual() => {
+                if parent.is_some() {
+                    error(*span, "Generic classes cannot inherit", "transpiling");
+                }
+                if methods.iter().any(|m| matches!(m.0, MethodKind::Override)) {
+                    error(
+                        *span,
+                        "Generic classes cannot have 'ovr' methods",
+                        "transpiling",
+                    );
+                }
+                cte.declare_generic_class(ty.clone(), members.clone());
+
+                for (kind, method) in methods {
+                    let lowered = lower_method(ty, kind, method);
+                    if let Expr::DeclareFunction(name, block, ..) = &lowered
+                        && !matches!(kind, MethodKind::Static)
+                    {
+                        cte.declare_method(name.clone(), ty.clone(), block.as_ref().clone());
+                    }
+                    lowered.pre_transpile(cte, ctx, programs_to_transpile, current_file_dir);
+                }
+            }
+
+            Expr::Class(ty, parent, members, methods, span) => {
                 cte.register_class(ty.clone());
 
+                if let Some(parent) = parent {
+                    if cte.is_class(parent) && !parent.has_generics() {
+                        cte.inherit(ty, parent);
+                    } else {
+                        error(
+                            *span,
+                            &format!(
+                                "Parent class '{}' of '{}' must be a non-generic class declared before it",
+                                parent, ty
+                            ),
+                            "transpiling",
+                        );
+                    }
+                }
+
                 for member in members {
+                    if cte.get_member_type(ty, &member.0).is_some() {
+                        error(
+                            *span,
+                            &format!("Member '{}' of '{}' is already inherited", member.0, ty),
+                            "transpiling",
+                        );
+                    }
                     cte.declare_member(member.0.clone(), member.1.clone(), ty.clone());
                 }
 
@@ -720,6 +2987,118 @@ impl Expr {
                 ctx.types.push(' ');
                 ctx.types.push_str(&c_type_name);
                 ctx.types.push_str(";\n");
+
+                // it has a vtable if it has `ovr` methods of its own or inherits them
+                let has_vtable = parent.as_ref().is_some_and(|p| cte.vtable(p).is_some())
+                    || methods.iter().any(|m| matches!(m.0, MethodKind::Override));
+                emit_class_struct(cte, ctx, ty, has_vtable, *span);
+
+                for (kind, method) in methods {
+                    let lowered = lower_method(ty, kind, method);
+                    if let Expr::DeclareFunction(name, block, ..) = &lowered
+                        && !matches!(kind, MethodKind::Static)
+                    {
+                        cte.declare_method(name.clone(), ty.clone(), block.as_ref().clone());
+                    }
+                    lowered.pre_transpile(cte, ctx, programs_to_transpile, current_file_dir);
+                }
+
+                declare_vtable(cte, ctx, ty, parent, methods, *span);
+            }
+
+            Expr::Enum(ty, variants, span) => {
+                cte.declare_enum(ty.clone(), variants.clone());
+
+                let c_type_name = cte.c_type_name(ty, ctx, *span);
+                ctx.include
+                    .push_str(&format!("struct {};\n", c_type_name));
+                ctx.types
+                    .push_str(&format!("\ntypedef struct {} {};\n", c_type_name, c_type_name));
+
+                // Variants without a payload are constants, the others are built by a function:
+                // Enum::Variant and Enum::Variant(a, b)
+                for (tag, (variant, payload)) in variants.iter().enumerate() {
+                    let name = format!("{}::{}", ty, variant);
+                    if payload.is_empty() {
+                        cte.declare_global_value(name.clone(), false, ty.clone());
+                        let var = cte.c_var_name(&name, *span);
+                        ctx.declarations.push_str(&format!(
+                            "{} {} = {{ .tag = {} }};\n",
+                            c_type_name, var, tag
+                        ));
+                        continue;
+                    }
+
+                    let mut func_generics = payload.clone();
+                    func_generics.push(ty.clone());
+                    cte.declare_global_var(
+                        name.clone(),
+                        false,
+                        Type::with_generics("func", func_generics),
+                    );
+                    let func_name = cte.c_func_instance_name(&name, &[], *span);
+                    let mut params = vec![];
+                    let mut body = format!("{} e = {{ .tag = {} }};\n", c_type_name, tag);
+                    for (i, member_type) in payload.iter().enumerate() {
+                        let param_type = cte.c_param_type(member_type, ctx, *span);
+                        params.push(format!("{} m_{}", param_type, i));
+                        // structs are passed by pointer
+                        let deref = if cte.is_class(member_type) { "*" } else { "" };
+                        body.push_str(&format!("e.data.p{}.m_{} = {}m_{};\n", tag, i, deref, i));
+                    }
+                    let header = format!("{} {}({})", c_type_name, func_name, params.join(", "));
+                    ctx.declarations.push_str(&format!("{};\n", header));
+                    ctx.unnamed.push_str(&format!(
+                        "\n{} {{ // {}\n{}return e;\n}}\n",
+                        header, name, body
+                    ));
+                }
+            }
+
+            Expr::Comp(decl) => {
+                let Expr::DeclareFunction(name, block, return_type, params, gens, span) =
+                    decl.as_ref()
+                else {
+                    return;
+                };
+                if !gens.is_empty() {
+                    error(*span, "'comp fn's cannot be generic", "pre-transpiling");
+                    return;
+                }
+                if cte.get_var(name).is_some() {
+                    error(
+                        *span,
+                        &format!("'{}' already exists, could not declare 'comp fn'", name),
+                        "pre-transpiling",
+                    );
+                    return;
+                }
+
+                let return_type = inferred_return_type(
+                    cte,
+                    block,
+                    &return_type.clone().unwrap_or_else(nil_type),
+                    params,
+                );
+                let mut func_generics: Vec<Type> = params.iter().map(|p| p.1.clone()).collect();
+                func_generics.push(return_type.clone());
+                cte.declare_comp_function(name.clone(), Type::with_generics("func", func_generics));
+                cte.comp_env().make_func(
+                    name,
+                    block.clone(),
+                    return_type,
+                    params.clone(),
+                    vec![],
+                    false,
+                    *span,
+                );
+            }
+
+            Expr::Declare(name, var_type, expr, is_mutable, span) if cte.is_global_scope() => {
+                if let Some(expr_box) = expr {
+                    expr_box.pre_transpile(cte, ctx, programs_to_transpile, current_file_dir);
+                }
+                declare_global(cte, ctx, name, var_type, expr, *is_mutable, *span);
             }
 
             Expr::Declare(name, var_type, expr, is_mutable, _span) => {
@@ -738,10 +3117,12 @@ impl Expr {
         }
     }
 
-    fn get_type(&self, cte: &mut CompileTimeEnv) -> Type {
+    pub fn get_type(&self, cte: &mut CompileTimeEnv) -> Type {
         match self {
+            Expr::Nothing() => nil_type(),
             Expr::Int(_) => "i32".into(),
             Expr::Float(_) => "f64".into(),
+            Expr::Number(_, ty) | Expr::Cast(_, ty, _) => ty.clone(),
             Expr::Str(_) => "strlit".into(),
             Expr::Bool(_)
             | Expr::EqualEqual(..)
@@ -751,14 +3132,81 @@ impl Expr {
             | Expr::Greater(..)
             | Expr::GreaterEqual(..) => "bool".into(),
             Expr::Char(_) => "char".into(),
-            Expr::Add(l, _, _)
-            | Expr::Sub(l, ..)
-            | Expr::Mult(l, ..)
-            | Expr::Power(l, ..)
-            | Expr::Div(l, ..) => l.get_type(cte),
+            Expr::And(l, r, _) | Expr::Or(l, r, _) | Expr::Xor(l, r, _) => {
+                match operand_type(cte, l, r) {
+                    Ok(ty) if ty.is_integer() => ty,
+                    _ => "bool".into(),
+                }
+            }
+            Expr::Not(l, _) => {
+                let ty = l.get_type(cte);
+                if ty.is_integer() { ty } else { "bool".into() }
+            }
+            Expr::ShiftLeft(l, r, _) | Expr::ShiftRight(l, r, _) => {
+                operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)
+            }
+            Expr::Negate(l, _) => l.get_type(cte),
+            Expr::Add(l, r, _) => {
+                let left_type = l.get_type(cte);
+                // strings have other kinds of strings or chars added to them
+                if matches!(left_type.name(), "strlit" | "str") {
+                    left_type
+                } else {
+                    operand_type(cte, l, r).unwrap_or(left_type)
+                }
+            }
+            Expr::Sub(l, r, _)
+            | Expr::Mult(l, r, _)
+            | Expr::Power(l, r, _)
+            | Expr::Mod(l, r, _)
+            | Expr::Div(l, r, _) => {
+                operand_type(cte, l, r).unwrap_or_else(|(left_type, _)| left_type)
+            }
             Expr::Return(_, _span) => nil_type(),
-            Expr::Nth(..) => "char".into(),
-            Expr::CallFunc(name, _, _, span) => {
+            Expr::Err(..) => cte
+                .return_type()
+                .filter(|t| t.name() == "res")
+                .cloned()
+                .unwrap_or_else(|| Type::with_generics("res", vec![nil_type()])),
+            Expr::Propagate(expr, _) => {
+                let res_type = expr.get_type(cte);
+                match res_type.name() {
+                    "res" => res_type.generics()[0].clone(),
+                    _ => nil_type(),
+                }
+            }
+            Expr::Nth(left, _, span) => {
+                let left_type = left.get_type(cte);
+                left_type.element_type().unwrap_or_else(|| {
+                    error(
+                        *span,
+                        &format!("Cannot index into a value of type '{}'", left_type),
+                        "type checker",
+                    );
+                    nil_type()
+                })
+            }
+            Expr::Vector(exprs) => {
+                let elem_type = exprs.first().map_or_else(nil_type, |e| e.get_type(cte));
+                Type::with_generics("vec", vec![elem_type])
+            }
+            Expr::Array(exprs) => match exprs.first() {
+                Some(first) => Type::with_generics("arr", vec![first.get_type(cte)]),
+                None => nil_type(),
+            },
+            Expr::CallFunc(name, gens, args, span) => {
+                if let Some(decl) = cte.get_generic_function(name).cloned() {
+                    let gens = generic_call_generics(cte, name, &decl, gens, args, *span);
+                    let Expr::DeclareFunction(_, block, return_type, params, ..) =
+                        generic_instance(&decl, &gens, *span)
+                    else {
+                        return nil_type();
+                    };
+                    let return_type = return_type.unwrap_or_else(nil_type);
+                    return cte.with_only_globals(|cte| {
+                        inferred_return_type(cte, &block, &return_type, &params)
+                    });
+                }
                 let function = cte
                     .get_var(name)
                     .unwrap_or_else(|| {
@@ -770,10 +3218,12 @@ impl Expr {
                         (false, nil_type())
                     })
                     .1;
-                function.generics().last().cloned().unwrap_or_else(nil_type)
+                call_return_type(cte, &function, args, *span)
             }
-            Expr::StmtBlock(exprs, _span) => exprs.last().unwrap().get_type(cte),
-            Expr::StmtBlockWithScope(exprs, _span) => exprs.last().unwrap().get_type(cte),
+            // an empty block has no value
+            Expr::StmtBlock(exprs, _span) | Expr::StmtBlockWithScope(exprs, _span) => exprs
+                .last()
+                .map_or_else(nil_type, |last| last.get_type(cte)),
             Expr::Variable(name, span) => {
                 cte.get_var(name)
                     .unwrap_or_else(|| {
@@ -786,11 +3236,53 @@ impl Expr {
                     })
                     .1
             }
-            Expr::Discard(..) => nil_type(),
+            // statements have no value
+            Expr::Discard(..)
+            | Expr::While(..)
+            | Expr::For(..)
+            | Expr::Break(..)
+            | Expr::Continue(..)
+            | Expr::Declare(..)
+            | Expr::Assign(..)
+            | Expr::CompoundAssign(..)
+            | Expr::Delete(_)
+            | Expr::DeclareFunction(..)
+            | Expr::DeclareMacro(..)
+            | Expr::Class(..)
+            | Expr::Enum(..)
+            | Expr::Use { .. } => nil_type(),
             Expr::Print(expr, _) => expr.get_type(cte),
-            Expr::If(_, block, ..) => block.get_type(cte),
+            Expr::Stmt(expr) | Expr::Comp(expr) => expr.get_type(cte),
+            Expr::Block(block) => {
+                cte.push_scope();
+                declare_locals_from_block(cte, block);
+                let ty = trailing_value(block).map_or_else(nil_type, |value| value.get_type(cte));
+                cte.pop_scope();
+                ty
+            }
+            Expr::If(_, block, ..) => block_type(cte, block),
+            Expr::Match(value, arms, ..) => {
+                let enum_type = value.get_type(cte);
+                arms.first()
+                    .map_or_else(nil_type, |arm| arm_type(cte, &enum_type, arm))
+            }
+            Expr::CallMethod(value, method, args, span) => {
+                lower_method_call(cte, value, method, args, *span).get_type(cte)
+            }
+            Expr::This(span) => {
+                cte.get_var(THIS)
+                    .unwrap_or_else(|| {
+                        error(
+                            *span,
+                            "'`v' can only be used inside a method",
+                            "type checker",
+                        );
+                        (false, nil_type())
+                    })
+                    .1
+            }
             Expr::Member(expr, member_name, span) => {
-                let var_type = expr.get_type(cte);
+                let (var_type, _) = member_access(expr.get_type(cte));
 
                 let member = cte.get_member_type(&var_type, member_name);
 
@@ -811,6 +3303,12 @@ impl Expr {
                 member_type
             }
 
+            Expr::Function(body, return_type, params, _) => {
+                let mut func_generics: Vec<Type> = params.iter().map(|p| p.1.clone()).collect();
+                func_generics.push(inferred_return_type(cte, body, return_type, params));
+                Type::with_generics("func", func_generics)
+            }
+
             Expr::Ref(expr, _span) => {
                 let expr_type = expr.get_type(cte);
                 cte.register_type(expr_type.clone());
@@ -824,14 +3322,32 @@ impl Expr {
                 }
                 expr_type.generics()[0].clone()
             }
-            _ => panic!("unexpected expression (for type check) '{:?}'", self),
+            Expr::Custom(_) | Expr::Custom2(_) | Expr::Value(_) => {
+                error(
+                    self.get_span(),
+                    "Values of the interpreter have no type when transpiling",
+                    "type checker",
+                );
+                nil_type()
+            }
         }
     }
 
     fn returned_type(&self, cte: &mut CompileTimeEnv, span: Span) -> Option<Type> {
         match self {
-            Expr::Return(expr, _span) => Some(expr.get_type(cte)),
-            Expr::Discard(expr) => expr.returned_type(cte, span),
+            Expr::Return(expr, _span) => {
+                let returned = expr.get_type(cte);
+                // returning a value from a function that returns a res is returning its success
+                match cte.return_type() {
+                    Some(res) if res.name() == "res" && res.generics()[0] == returned => {
+                        Some(res.clone())
+                    }
+                    _ => Some(returned),
+                }
+            }
+            Expr::Discard(expr) | Expr::Stmt(expr) | Expr::Block(expr) => {
+                expr.returned_type(cte, span)
+            }
             Expr::StmtBlock(exprs, span) => {
                 let mut return_type = None;
                 for expr in exprs {
@@ -866,7 +3382,7 @@ impl Expr {
         }
     }
 
-    fn get_span(&self) -> Span {
+    pub fn get_span(&self) -> Span {
         match self {
             Expr::Add(_, _, span)
             | Expr::Sub(_, _, span)
@@ -880,7 +3396,34 @@ impl Expr {
             | Expr::LessEqual(_, _, span)
             | Expr::Less(_, _, span)
             | Expr::Greater(_, _, span)
-            | Expr::Return(_, span) => *span,
+            | Expr::Xor(_, _, span)
+            | Expr::ShiftLeft(_, _, span)
+            | Expr::ShiftRight(_, _, span)
+            | Expr::For(.., span)
+            | Expr::While(.., span)
+            | Expr::Cast(_, _, span)
+            | Expr::Break(_, span)
+            | Expr::Continue(_, span)
+            | Expr::Return(_, span)
+            | Expr::And(_, _, span)
+            | Expr::Or(_, _, span)
+            | Expr::Nth(_, _, span)
+            | Expr::Not(_, span)
+            | Expr::Negate(_, span)
+            | Expr::Ref(_, span)
+            | Expr::Deref(_, span)
+            | Expr::Variable(_, span)
+            | Expr::This(span)
+            | Expr::Member(_, _, span)
+            | Expr::CallFunc(.., span)
+            | Expr::CallMethod(.., span)
+            | Expr::Propagate(_, span)
+            | Expr::Err(_, span)
+            | Expr::Assign(_, _, span)
+            | Expr::CompoundAssign(_, _, span)
+            | Expr::Declare(.., span)
+            | Expr::StmtBlock(_, span)
+            | Expr::StmtBlockWithScope(_, span) => *span,
             _ => Span::empty(),
         }
     }
@@ -0,0 +1,497 @@
+use crate::error::ObstructError;
+use crate::expr::Expr;
+use crate::span::Span;
+use crate::transpiler::compiletime_env::CompileTimeEnv;
+use crate::transpiler::expr_to_c::{
+    declare_locals_from_block, inferred_return_type, lower_method, lower_method_call, operand_type,
+    trailing_value,
+};
+use crate::type_env::{Type, nil_type};
+use crate::{error, get_current_file};
+
+/// Checks the types of a pre-transpiled program before any C is emitted for it: the arguments of
+/// calls against the parameters of the functions they call, declared and assigned values against
+/// the type of what they are stored in, returned values against the declared return type,
+/// conditions against `bool`, and the operands of binary operators against each other.\
+/// Every mismatch is reported with its span as it is found, and all of them are returned, so the
+/// caller can stop before writing C. The types found for locals declared without one are recorded
+/// in `cte`, so `to_c` emits them with those. Generic functions and classes are checked per
+/// instance, when they are emitted.
+pub fn type_check(program: &Expr, cte: &mut CompileTimeEnv) -> Vec<ObstructError> {
+    cte.without_new_ids(|cte| {
+        let mut checker = TypeChecker {
+            cte,
+            return_types: vec![],
+            mismatches: vec![],
+        };
+        checker.check(program);
+        checker.mismatches
+    })
+}
+
+struct TypeChecker<'a> {
+    cte: &'a mut CompileTimeEnv,
+    /// The declared return types of the functions being checked, innermost last. None for a
+    /// function that infers its return type from its body, so has nothing to return against.
+    return_types: Vec<Option<Type>>,
+    mismatches: Vec<ObstructError>,
+}
+
+impl TypeChecker<'_> {
+    /// Checks an expression and everything in it, declaring what it declares like `to_c` does.
+    fn check(&mut self, expr: &Expr) {
+        match expr {
+            Expr::StmtBlockWithScope(..) | Expr::Block(..) => {
+                self.cte.push_scope();
+                self.check_children(expr);
+                self.cte.pop_scope();
+            }
+
+            Expr::Declare(name, var_type, value, is_mutable, span) => {
+                if let Some(value) = value {
+                    self.check(value);
+                }
+                let var_type_is_inferred = var_type.is_none();
+                let var_type = match (var_type, value) {
+                    (Some(var_type), Some(value)) => {
+                        let found = value.get_type(self.cte);
+                        if !self.accepts(var_type, &found, value, span_of(value, *span)) {
+                            self.mismatch(
+                                span_of(value, *span),
+                                format!(
+                                    "Expected a value of type '{}' for '{}', found '{}'",
+                                    var_type,
+                                    name,
+                                    shown(&found)
+                                ),
+                            );
+                        }
+                        var_type.clone()
+                    }
+                    (Some(var_type), None) => var_type.clone(),
+                    (None, Some(value)) => value.get_type(self.cte),
+                    (None, None) => nil_type(),
+                };
+                // globals were declared when the program was pre-transpiled
+                if !self.cte.is_global_scope() {
+                    if var_type_is_inferred {
+                        self.cte.record_checked_type(name, *span, var_type.clone());
+                    }
+                    self.cte.declare_var(name.clone(), *is_mutable, var_type);
+                }
+            }
+
+            Expr::Assign(target, value, span) => {
+                self.check_children(expr);
+                let target_type = target.get_type(self.cte);
+                // a variable or member holding a reference is assigned through it
+                let target_type = match target.as_ref() {
+                    Expr::Variable(..) | Expr::Member(..) if target_type.name() == "ref" => {
+                        target_type.generics()[0].clone()
+                    }
+                    _ => target_type,
+                };
+                let found = value.get_type(self.cte);
+                if !self.accepts(&target_type, &found, value, *span) {
+                    self.mismatch(
+                        *span,
+                        format!(
+                            "Cannot assign a value of type '{}' to a place of type '{}'",
+                            shown(&found),
+                            target_type
+                        ),
+                    );
+                }
+            }
+
+            Expr::Return(value, span) => {
+                self.check(value);
+                self.returned(value, *span);
+            }
+
+            Expr::If(cond, block, ..) | Expr::While(cond, block, ..) => {
+                self.check_children(expr);
+                let found = cond.get_type(self.cte);
+                if found != Type::simple("bool") {
+                    self.mismatch(
+                        span_of(cond, block.get_span()),
+                        format!(
+                            "Expected a condition of type 'bool', found '{}'",
+                            shown(&found)
+                        ),
+                    );
+                }
+            }
+
+            Expr::For(name, looper, block, ..) => {
+                self.check(looper);
+                let looper_type = looper.get_type(self.cte);
+                let var_type = if looper_type.is_integer() {
+                    looper_type
+                } else {
+                    looper_type.element_type().unwrap_or_else(nil_type)
+                };
+                self.cte.push_scope();
+                self.cte.declare_var(name.clone(), false, var_type);
+                self.check(block);
+                self.cte.pop_scope();
+            }
+
+            Expr::Match(value, arms, ..) => {
+                self.check(value);
+                let enum_type = value.get_type(self.cte);
+                for (variant, bindings, block) in arms {
+                    let payload = self
+                        .cte
+                        .enum_variants(&enum_type)
+                        .and_then(|variants| variants.iter().find(|v| &v.0 == variant))
+                        .map(|v| v.1.clone())
+                        .unwrap_or_default();
+                    self.cte.push_scope();
+                    for (binding, ty) in bindings.iter().zip(payload) {
+                        self.cte.declare_var(binding.clone(), false, ty);
+                    }
+                    self.check(block);
+                    self.cte.pop_scope();
+                }
+            }
+
+            Expr::DeclareFunction(_, block, return_type, params, gens, span) => {
+                if gens.is_empty() {
+                    self.function(block, return_type.clone(), params, *span);
+                }
+            }
+
+            Expr::Function(body, return_type, params, gens) => {
+                if gens.is_empty() {
+                    let return_type = (*return_type != nil_type()).then(|| return_type.clone());
+                    self.function(body, return_type, params, body.get_span());
+                }
+            }
+
+            Expr::Class(ty, _, _, methods, _) => {
+                if !ty.contains_conceptual() {
+                    for (kind, method) in methods {
+                        self.check(&lower_method(ty, kind, method));
+                    }
+                }
+            }
+
+            Expr::CallFunc(..) => {
+                self.check_children(expr);
+                self.arguments(expr);
+            }
+
+            Expr::Add(l, r, span)
+            | Expr::Sub(l, r, span)
+            | Expr::Mult(l, r, span)
+            | Expr::Div(l, r, span)
+            | Expr::Mod(l, r, span)
+            | Expr::Power(l, r, span)
+            | Expr::EqualEqual(l, r, span)
+            | Expr::BangEqual(l, r, span)
+            | Expr::Less(l, r, span)
+            | Expr::LessEqual(l, r, span)
+            | Expr::Greater(l, r, span)
+            | Expr::GreaterEqual(l, r, span)
+            | Expr::And(l, r, span)
+            | Expr::Or(l, r, span)
+            | Expr::Xor(l, r, span)
+            | Expr::ShiftLeft(l, r, span)
+            | Expr::ShiftRight(l, r, span) => {
+                self.check_children(expr);
+                self.operands(expr, l, r, *span);
+            }
+
+            Expr::CallMethod(value, method, args, span) => {
+                self.check_children(expr);
+                let call = lower_method_call(self.cte, value, method, args, *span);
+                if let Expr::CallFunc(name, ..) = &call
+                    && self.cte.is_function(name)
+                {
+                    self.arguments(&call);
+                }
+            }
+
+            // evaluated by the compiler, or declaring nothing with a value
+            Expr::Comp(..) | Expr::Enum(..) | Expr::Use { .. } | Expr::DeclareMacro(..) => {}
+
+            _ => self.check_children(expr),
+        }
+    }
+
+    fn check_children(&mut self, expr: &Expr) {
+        for child in expr.children() {
+            self.check(child);
+        }
+    }
+
+    /// Checks the body of a function or lambda, with its parameters declared.
+    fn function(
+        &mut self,
+        body: &Expr,
+        return_type: Option<Type>,
+        params: &[(String, Type, bool)],
+        span: Span,
+    ) {
+        self.cte.push_scope();
+        for param in params {
+            self.cte
+                .declare_var(param.0.clone(), param.2, param.1.clone());
+        }
+        // like when emitting, locals are known before they are reached
+        declare_locals_from_block(self.cte, body);
+        let inferred = inferred_return_type(
+            self.cte,
+            body,
+            &return_type.clone().unwrap_or_else(nil_type),
+            params,
+        );
+        self.cte.enter_function(inferred);
+        self.return_types.push(return_type);
+
+        self.check(body);
+        // the value a body ends with is returned like a `ret`
+        if let Some(value) = trailing_value(body) {
+            self.cte.push_scope();
+            declare_locals_from_block(self.cte, body);
+            self.returned(&value, span);
+            self.cte.pop_scope();
+        }
+
+        self.return_types.pop();
+        self.cte.exit_function();
+        self.cte.pop_scope();
+    }
+
+    /// Checks a returned value against the declared return type of the function it returns from.
+    fn returned(&mut self, value: &Expr, span: Span) {
+        let Some(Some(return_type)) = self.return_types.last().cloned() else {
+            return;
+        };
+        let found = value.get_type(self.cte);
+        if !self.accepts(&return_type, &found, value, span_of(value, span)) {
+            self.mismatch(
+                span_of(value, span),
+                format!(
+                    "Expected to return a value of type '{}', found '{}'",
+                    return_type,
+                    shown(&found)
+                ),
+            );
+        }
+    }
+
+    /// Checks that both operands of a binary operator have the same type, once number literals
+    /// without a suffix take the type of the other operand.
+    fn operands(&mut self, operator: &Expr, l: &Expr, r: &Expr, span: Span) {
+        // strings have other kinds of strings or chars added to them, anything else is reported
+        // like any other mismatch
+        if matches!(operator, Expr::Add(..))
+            && matches!(l.get_type(self.cte).name(), "strlit" | "str")
+            && matches!(r.get_type(self.cte).name(), "strlit" | "str" | "char")
+        {
+            return;
+        }
+        match operand_type(self.cte, l, r) {
+            Ok(ty) => {
+                self.literal_fits(&ty, l, span);
+                self.literal_fits(&ty, r, span);
+            }
+            // an operand without a type was reported where its type was looked for
+            Err((left_type, right_type))
+                if left_type == nil_type() && !self.has_no_value(l)
+                    || right_type == nil_type() && !self.has_no_value(r) => {}
+            Err((left_type, right_type)) => self.mismatch(
+                span,
+                format!(
+                    "Cannot apply '{}' to values of type '{}' and '{}'",
+                    symbol(operator),
+                    shown(&left_type),
+                    shown(&right_type)
+                ),
+            ),
+        }
+    }
+
+    /// Reports a number literal without a suffix that is used as an integer type it does not fit in.
+    fn literal_fits(&mut self, ty: &Type, value: &Expr, span: Span) {
+        let (Expr::Int(n), Some((bits, signed))) = (value, ty.integer_bits()) else {
+            return;
+        };
+        let n = i128::from(*n);
+        let (min, max) = if signed {
+            (-(1i128 << (bits - 1)), (1i128 << (bits - 1)) - 1)
+        } else {
+            (0, (1i128 << bits) - 1)
+        };
+        if n < min || n > max {
+            self.mismatch(
+                span,
+                format!("The integer '{}' does not fit in '{}'", n, ty),
+            );
+        }
+    }
+
+    /// Checks the arguments of a call against the parameters of the function it calls.
+    fn arguments(&mut self, call: &Expr) {
+        let Expr::CallFunc(name, gens, args, span) = call else {
+            return;
+        };
+        if self.cte.is_comp_function(name) {
+            return;
+        }
+        if self.cte.get_generic_function(name).is_some() {
+            // binding the generic types of a call checks its arguments
+            call.get_type(self.cte);
+            return;
+        }
+        // calls to functions that do not exist are reported when they are emitted, and
+        // generic builtins (like push) take any type they have an instance for
+        let Some((_, func_type)) = self.cte.get_var(name) else {
+            return;
+        };
+        if func_type.name() != "func" || func_type.contains_conceptual() || !gens.is_empty() {
+            return;
+        }
+
+        let params = &func_type.generics()[..func_type.generics().len() - 1];
+        if params.len() != args.len() {
+            self.mismatch(
+                *span,
+                format!(
+                    "Function '{}' takes {} arguments but was given {}",
+                    name,
+                    params.len(),
+                    args.len()
+                ),
+            );
+            return;
+        }
+        for (param, arg) in params.iter().zip(args) {
+            let found = arg.get_type(self.cte);
+            if !self.accepts(param, &found, arg, span_of(arg, *span)) {
+                self.mismatch(
+                    span_of(arg, *span),
+                    format!(
+                        "Expected an argument of type '{}' for '{}', found '{}'",
+                        param,
+                        name,
+                        shown(&found)
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Returns true if `value`, of type `found`, can be used where a value of type `expected` is.\
+    /// A number literal that is accepted but does not fit in `expected` is reported at `span`.
+    fn accepts(&mut self, expected: &Type, found: &Type, value: &Expr, span: Span) -> bool {
+        // a place without a type was reported where its type was looked for
+        if expected == found || *expected == nil_type() || expected.contains_conceptual() {
+            return true;
+        }
+        if *found == nil_type() {
+            // so was a value without one, unless it is a call or block that produces nothing
+            return !self.has_no_value(value);
+        }
+        let is_number = |ty: &Type| ty.is_integer() || ty.is_float();
+        match value {
+            // number literals take the type they are used as
+            Expr::Int(_) if is_number(expected) => {
+                self.literal_fits(expected, value, span);
+                return true;
+            }
+            Expr::Float(_) if expected.is_float() => return true,
+            // an empty literal takes its element type from the type it is used as
+            Expr::Vector(exprs) | Expr::Array(exprs)
+                if exprs.is_empty() && expected.name() == found.name() =>
+            {
+                return true;
+            }
+            // a value of the success type, or an `err`, becomes a res
+            Expr::Err(..) if expected.name() == "res" => return true,
+            _ => {}
+        }
+        if expected.name() == "res" && expected.generics()[0] == *found {
+            return true;
+        }
+
+        // a (reference to a) subclass can be used as its parent
+        let (found_class, expected_class) = if found.name() == "ref" && expected.name() == "ref" {
+            (&found.generics()[0], &expected.generics()[0])
+        } else {
+            (found, expected)
+        };
+        self.cte.is_subclass(found_class, expected_class)
+    }
+
+    /// Returns true if `value`, whose type is nil, is an expression that produces nothing rather
+    /// than one whose type could not be found.
+    fn has_no_value(&mut self, value: &Expr) -> bool {
+        match value {
+            Expr::Nothing() => true,
+            Expr::CallFunc(name, ..) => {
+                self.cte.get_var(name).is_some() || self.cte.get_generic_function(name).is_some()
+            }
+            Expr::CallMethod(receiver, method, args, span) => {
+                let call = lower_method_call(self.cte, receiver, method, args, *span);
+                self.has_no_value(&call)
+            }
+            Expr::Block(block) => {
+                trailing_value(block).is_some_and(|value| self.has_no_value(&value))
+            }
+            _ => false,
+        }
+    }
+
+    fn mismatch(&mut self, span: Span, message: String) {
+        error(span, &message, "type checker");
+        self.mismatches.push(ObstructError::new(
+            span.line,
+            span.column,
+            message,
+            get_current_file(),
+        ));
+    }
+}
+
+/// Returns how a binary operator is written.
+fn symbol(operator: &Expr) -> &'static str {
+    match operator {
+        Expr::Add(..) => "+",
+        Expr::Sub(..) => "-",
+        Expr::Mult(..) => "*",
+        Expr::Div(..) => "/",
+        Expr::Mod(..) => "%",
+        Expr::Power(..) => "**",
+        Expr::EqualEqual(..) => "==",
+        Expr::BangEqual(..) => "!=",
+        Expr::Less(..) => "<",
+        Expr::LessEqual(..) => "<=",
+        Expr::Greater(..) => ">",
+        Expr::GreaterEqual(..) => ">=",
+        Expr::And(..) => "&",
+        Expr::Or(..) => "|",
+        Expr::Xor(..) => "^",
+        Expr::ShiftLeft(..) => "<<",
+        _ => ">>",
+    }
+}
+
+/// Returns how a found type is shown in a mismatch, where nil is the type of what has no value.
+fn shown(ty: &Type) -> String {
+    if *ty == nil_type() {
+        "nothing".to_string()
+    } else {
+        ty.to_string()
+    }
+}
+
+/// Returns the span of an expression, or `fallback` for expressions that do not keep one.
+fn span_of(expr: &Expr, fallback: Span) -> Span {
+    match expr.get_span() {
+        span if span == Span::empty() => fallback,
+        span => span,
+    }
+}
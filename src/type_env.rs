@@ -85,6 +85,30 @@ impl Type {
         }
     }
 
+    /// Returns true if this type is, or has among its generics, a conceptual type.
+    pub fn contains_conceptual(&self) -> bool {
+        match self {
+            Type::Conceptual(_) => true,
+            Type::Concrete { generics, .. } => generics.iter().any(|g| g.contains_conceptual()),
+            Type::Generic { .. } => false,
+        }
+    }
+
+    /// Returns this type with every simple type named in `names` (like a function's generic
+    /// parameters) turned into a conceptual type, so that it can be unified and substituted.
+    pub fn with_conceptuals(&self, names: &[String]) -> Type {
+        match self {
+            Type::Concrete { name, generics } if generics.is_empty() && names.contains(name) => {
+                Type::Conceptual(name.clone())
+            }
+            Type::Concrete { name, generics } => Type::Concrete {
+                name: name.clone(),
+                generics: generics.iter().map(|g| g.with_conceptuals(names)).collect(),
+            },
+            _ => self.clone(),
+        }
+    }
+
     pub fn conceptual(name: &str) -> Self {
         Type::Conceptual(name.into())
     }
@@ -114,6 +138,39 @@ impl Type {
     pub fn has_tag(&self, tag: &str) -> bool {
         self.name() == tag
     }
+
+    /// Returns true for the integer types, `i8` to `i64` and `u8` to `u64`.
+    pub fn is_integer(&self) -> bool {
+        self.generics().is_empty()
+            && matches!(
+                self.name(),
+                "i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64"
+            )
+    }
+
+    /// Returns true for the float types, `f32` and `f64`.
+    pub fn is_float(&self) -> bool {
+        self.generics().is_empty() && matches!(self.name(), "f32" | "f64")
+    }
+
+    /// Returns the number of bits of an integer type and whether it is signed.
+    pub fn integer_bits(&self) -> Option<(u32, bool)> {
+        if !self.is_integer() {
+            return None;
+        }
+        let signed = self.name().starts_with('i');
+        self.name()[1..].parse().ok().map(|bits| (bits, signed))
+    }
+
+    /// Returns the type of a single element when iterating over or indexing into this type.\
+    /// `strlit` and `str` yield `char`, `vec<<T>>` and `arr<<T>>` yield `T`.
+    pub fn element_type(&self) -> Option<Type> {
+        match self.name() {
+            "strlit" | "str" => Some(Type::simple("char")),
+            "vec" | "arr" if self.generics().len() == 1 => Some(self.generics()[0].clone()),
+            _ => None,
+        }
+    }
 }
 
 impl From<&str> for Type {
@@ -151,6 +208,11 @@ impl fmt::Display for Type {
     }
 }
 
+/// The primitive number types, which are also the suffixes of number literals like `10u64`
+pub const NUMBER_TYPES: [&str; 10] = [
+    "i8", "i16", "i32", "i64", "u8", "u16", "u32", "u64", "f32", "f64",
+];
+
 pub fn nil_type() -> Type {
     Type::simple("arr")
 }
@@ -14,6 +14,8 @@ pub struct Value {
     pub native:
         Option<fn(&mut RuntimeEnvironment, &mut TypeEnvironment, Vec<Value>, Span) -> Value>,
     pub is_return: bool,
+    /// the `break` or `continue` this value is leaving its loop with
+    pub loop_jump: Option<Box<Expr>>,
 }
 
 impl Value {
@@ -62,6 +64,31 @@ pub fn nil() -> Value {
         body: None,
         native: None,
         is_return: false,
+        loop_jump: None,
+    }
+}
+
+pub fn literal_val(value_type: Type, value: String) -> Value {
+    Value {
+        value_type,
+        value,
+        value_vec: None,
+        body: None,
+        native: None,
+        is_return: false,
+        loop_jump: None,
+    }
+}
+
+pub fn seq_val(value_type: Type, items: Vec<Value>) -> Value {
+    Value {
+        value_type,
+        value: "".to_string(),
+        value_vec: Some(items),
+        body: None,
+        native: None,
+        is_return: false,
+        loop_jump: None,
     }
 }
 
@@ -73,6 +100,7 @@ pub fn func_val(func: Func) -> Value {
         body: Some(func),
         native: None,
         is_return: false,
+        loop_jump: None,
     }
 }
 
@@ -86,6 +114,7 @@ pub fn native_func(
         body: None,
         native: Some(f),
         is_return: false,
+        loop_jump: None,
     }
 }
 #[derive(Clone, Debug)]